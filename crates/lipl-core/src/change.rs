@@ -0,0 +1,130 @@
+//! Notifications of changes to lyrics and playlists
+
+use std::pin::Pin;
+
+use futures_core::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio_stream::{
+    StreamExt,
+    wrappers::{BroadcastStream, errors::BroadcastStreamRecvError},
+};
+
+use crate::{Etag, Lyric, LyricId, Playlist, PlaylistId, Uuid};
+
+/// Changes that are not yet received by a slow subscriber, before it misses some
+const CAPACITY: usize = 256;
+
+/// Stream of changes returned by `Repo::subscribe`
+pub type ChangeStream = Pin<Box<dyn Stream<Item = Change> + Send>>;
+
+/// A lyric or playlist that was stored or deleted, with the entity tag it has now.
+/// Deleting a lyric also removes it from the playlists, each of them follows as `PlaylistUpserted`.
+/// `Lagged` tells a subscriber that fell behind how many changes it missed.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "change", rename_all = "kebab-case")]
+pub enum Change {
+    LyricUpserted { id: LyricId, etag: String },
+    LyricDeleted { id: LyricId },
+    PlaylistUpserted { id: PlaylistId, etag: String },
+    PlaylistDeleted { id: PlaylistId },
+    Lagged { missed: u64 },
+}
+
+impl Change {
+    /// Id of the lyric or playlist that changed, none if changes are missed
+    #[must_use]
+    pub fn id(&self) -> Option<Uuid> {
+        match self {
+            Self::LyricUpserted { id, .. } | Self::LyricDeleted { id } => Some((*id).into()),
+            Self::PlaylistUpserted { id, .. } | Self::PlaylistDeleted { id } => Some((*id).into()),
+            Self::Lagged { .. } => None,
+        }
+    }
+
+    #[must_use]
+    pub fn lyric_upserted(lyric: &Lyric) -> Self {
+        Self::LyricUpserted {
+            id: lyric.id,
+            etag: lyric.etag().unwrap_or_default(),
+        }
+    }
+
+    #[must_use]
+    pub fn playlist_upserted(playlist: &Playlist) -> Self {
+        Self::PlaylistUpserted {
+            id: playlist.id,
+            etag: playlist.etag().unwrap_or_default(),
+        }
+    }
+
+    /// The deletion of the lyric with id `id`, followed by the `playlists` it was removed from as they are now
+    #[must_use]
+    pub fn lyric_deleted(id: LyricId, playlists: &[Playlist]) -> Vec<Self> {
+        std::iter::once(Self::LyricDeleted { id })
+            .chain(playlists.iter().map(Self::playlist_upserted))
+            .collect()
+    }
+}
+
+/// In-process broadcast of changes, for backends without notifications of their own.
+/// Clones share the subscribers.
+#[derive(Clone, Debug)]
+pub struct Changes {
+    sender: broadcast::Sender<Change>,
+}
+
+impl Default for Changes {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl Changes {
+    /// Sends `change` to every subscriber, if there are any
+    pub fn send(&self, change: Change) {
+        let _ = self.sender.send(change);
+    }
+
+    /// Sends `changes` in order, see `send`
+    pub fn send_all(&self, changes: impl IntoIterator<Item = Change>) {
+        changes.into_iter().for_each(|change| self.send(change));
+    }
+
+    /// Changes sent from now on. A subscriber that falls behind gets `Change::Lagged` in place of the changes it missed.
+    #[must_use]
+    pub fn subscribe(&self) -> ChangeStream {
+        Box::pin(
+            BroadcastStream::new(self.sender.subscribe()).map(|received| {
+                received.unwrap_or_else(|BroadcastStreamRecvError::Lagged(missed)| Change::Lagged {
+                    missed,
+                })
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+
+    use super::{CAPACITY, Change, Changes};
+    use crate::PlaylistId;
+
+    #[tokio::test]
+    async fn lagged() {
+        let changes = Changes::default();
+        let mut stream = changes.subscribe();
+        let deleted = (0..=CAPACITY)
+            .map(|_| Change::PlaylistDeleted {
+                id: PlaylistId::default(),
+            })
+            .collect::<Vec<_>>();
+        changes.send_all(deleted.clone());
+
+        assert_eq!(stream.next().await, Some(Change::Lagged { missed: 1 }));
+        assert_eq!(stream.next().await.as_ref(), deleted.get(1));
+    }
+}
@@ -0,0 +1,131 @@
+/*!
+Inline chords in lyric lines.
+
+Chords are written in front of the syllable they are played on, as in `ChordPro`: `[G]Toen den [D]hertog Jan`.
+Lines without chords are plain text, so lyrics without chords are not affected.
+*/
+
+/// Text with the chord that is played at its start
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub chord: Option<String>,
+    pub text: String,
+}
+
+/// Splits `line` at the chords. A `[` without a closing `]` is text.
+#[must_use]
+pub fn segments(line: &str) -> Vec<Segment> {
+    let mut result = vec![];
+    let mut chord = None;
+    let mut rest = line;
+    while let Some((text, after)) = rest.split_once('[') {
+        let Some((name, after)) = after.split_once(']') else {
+            break;
+        };
+        if chord.is_some() || !text.is_empty() {
+            result.push(Segment {
+                chord: chord.take(),
+                text: text.to_owned(),
+            });
+        }
+        chord = Some(name.to_owned());
+        rest = after;
+    }
+    if chord.is_some() || !rest.is_empty() {
+        result.push(Segment {
+            chord,
+            text: rest.to_owned(),
+        });
+    }
+    result
+}
+
+/// The line written with inline chords
+#[must_use]
+pub fn to_line(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|segment| match &segment.chord {
+            Some(chord) => format!("[{chord}]{}", segment.text),
+            None => segment.text.clone(),
+        })
+        .collect()
+}
+
+#[must_use]
+pub fn has_chords(line: &str) -> bool {
+    segments(line).iter().any(|segment| segment.chord.is_some())
+}
+
+/// The line without chords
+#[must_use]
+pub fn strip(line: &str) -> String {
+    segments(line)
+        .into_iter()
+        .map(|segment| segment.text)
+        .collect()
+}
+
+/// The line as a line of chords above a line of text, or just the line if it has no chords
+#[must_use]
+pub fn chords_above(line: &str) -> Vec<String> {
+    if !has_chords(line) {
+        return vec![line.to_owned()];
+    }
+    let mut chords = String::new();
+    let mut text = String::new();
+    for segment in segments(line) {
+        let column = text.chars().count();
+        let width = chords.chars().count();
+        if let Some(chord) = segment.chord {
+            // Keep a space between chords that follow each other closely
+            if width > 0 && width >= column {
+                chords.push(' ');
+            }
+            chords.extend(core::iter::repeat_n(' ', column.saturating_sub(width)));
+            chords.push_str(&chord);
+        }
+        text.push_str(&segment.text);
+    }
+    vec![chords, text.trim_end().to_owned()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Segment, chords_above, has_chords, segments, strip, to_line};
+
+    #[test]
+    fn segments_round_trip() {
+        let line = "[G]Toen den hertog [D]Jan kwam [G]varen";
+        let segments = segments(line);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(
+            segments[1],
+            Segment {
+                chord: Some("D".to_owned()),
+                text: "Jan kwam ".to_owned()
+            }
+        );
+        assert_eq!(to_line(&segments), line);
+    }
+
+    #[test]
+    fn plain_line() {
+        let line = "Toen den hertog Jan [kwam varen";
+        assert!(!has_chords(line));
+        assert_eq!(strip(line), line);
+        assert_eq!(chords_above(line), vec![line.to_owned()]);
+    }
+
+    #[test]
+    fn render_above() {
+        assert_eq!(
+            chords_above("Toen den [Am]hertog [D7]Jan[G]"),
+            vec![
+                "         Am     D7 G".to_owned(),
+                "Toen den hertog Jan".to_owned()
+            ]
+        );
+        assert_eq!(strip("[G]Toen den [D]Jan"), "Toen den Jan");
+    }
+}
@@ -0,0 +1,287 @@
+/*!
+Reading and writing lyrics in the [`ChordPro`](https://www.chordpro.org) format.
+
+Metadata is written as directives, labelled parts as sections and references as `{chorus: label}`.
+Directives that have no counterpart in a lyric are ignored when reading.
+*/
+
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+
+use crate::error::Error;
+use crate::parts::{PartKind, Section, Structure};
+use crate::{Lyric, LyricPost, Metadata};
+
+/// A lyric in `ChordPro` format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChordPro(pub LyricPost);
+
+impl From<Lyric> for ChordPro {
+    fn from(lyric: Lyric) -> Self {
+        Self(lyric.into())
+    }
+}
+
+impl From<ChordPro> for LyricPost {
+    fn from(chordpro: ChordPro) -> Self {
+        chordpro.0
+    }
+}
+
+fn section_name(kind: PartKind) -> &'static str {
+    match kind {
+        PartKind::Chorus => "chorus",
+        PartKind::Bridge => "bridge",
+        _ => "verse",
+    }
+}
+
+fn directives(title: &str, metadata: &Metadata) -> Vec<String> {
+    let standard = [
+        ("title", Some(title.to_owned())),
+        ("subtitle", metadata.subtitle.clone()),
+        ("lyricist", metadata.lyricist.clone()),
+        ("composer", metadata.composer.clone()),
+        ("key", metadata.key.clone()),
+        ("tempo", metadata.tempo.map(|tempo| tempo.to_string())),
+        ("copyright", metadata.copyright.clone()),
+    ];
+    let meta = [
+        ("ccli", metadata.ccli.map(|ccli| ccli.to_string())),
+        ("language", metadata.language.clone()),
+    ];
+    standard
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| format!("{{{name}: {value}}}")))
+        .chain(
+            meta.into_iter()
+                .filter_map(|(name, value)| value.map(|value| format!("{{meta: {name} {value}}}"))),
+        )
+        .chain(
+            metadata
+                .tags
+                .iter()
+                .map(|tag| format!("{{meta: tag {tag}}}")),
+        )
+        .collect()
+}
+
+impl Display for ChordPro {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let lyric = &self.0;
+        let sections = Structure::from(lyric.parts.as_slice())
+            .0
+            .into_iter()
+            .map(|section| match section {
+                Section::Part(part) => match part.label {
+                    Some(label) => {
+                        let name = section_name(part.kind);
+                        format!(
+                            "{{start_of_{name}: {label}}}\n{}\n{{end_of_{name}}}",
+                            part.lines.join("\n")
+                        )
+                    }
+                    None => part.lines.join("\n"),
+                },
+                Section::Reference(label) => format!("{{chorus: {label}}}"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let directives = directives(&lyric.title, &lyric.metadata).join("\n");
+        write!(f, "{directives}\n\n{sections}\n")
+    }
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::Parse(format!("ChordPro {name} {value}")))
+}
+
+#[derive(Default)]
+struct Reader {
+    title: Option<String>,
+    metadata: Metadata,
+    parts: Vec<Vec<String>>,
+    current: Vec<String>,
+    in_section: bool,
+    chorus: Option<String>,
+}
+
+impl Reader {
+    fn flush(&mut self) {
+        if !self.current.is_empty() {
+            self.parts.push(core::mem::take(&mut self.current));
+        }
+    }
+
+    fn meta(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let value = value.to_owned();
+        match name {
+            "title" | "t" => self.title = Some(value),
+            "subtitle" | "st" => self.metadata.subtitle = Some(value),
+            "lyricist" => self.metadata.lyricist = Some(value),
+            "composer" => self.metadata.composer = Some(value),
+            "key" => self.metadata.key = Some(value),
+            "tempo" => self.metadata.tempo = Some(parse_number(name, &value)?),
+            "copyright" => self.metadata.copyright = Some(value),
+            "ccli" => self.metadata.ccli = Some(parse_number(name, &value)?),
+            "language" => self.metadata.language = Some(value),
+            "tag" => self.metadata.tags.push(value),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn start(&mut self, name: &str, label: Option<&str>) {
+        self.flush();
+        self.in_section = true;
+        // Sections other than verses need a label to be referenced
+        let label = label
+            .map(String::from)
+            .or_else(|| (name != "verse").then(|| name.to_owned()));
+        if let Some(label) = label {
+            if name == "chorus" {
+                self.chorus = Some(label.clone());
+            }
+            self.current.push(format!("# {label}"));
+        }
+    }
+
+    fn end(&mut self) {
+        // A section without lines would read back as a reference
+        if self.current.len() == 1 && self.current[0].starts_with("# ") {
+            self.current.clear();
+        }
+        self.flush();
+        self.in_section = false;
+    }
+
+    fn directive(&mut self, directive: &str) -> Result<(), Error> {
+        let (name, value) = directive
+            .split_once(':')
+            .map_or((directive, None), |(name, value)| {
+                (name, Some(value.trim()).filter(|value| !value.is_empty()))
+            });
+        let name = name.trim().to_lowercase();
+        match (name.as_str(), value) {
+            ("meta", Some(value)) => {
+                let (name, value) = value.split_once(' ').unwrap_or((value, ""));
+                self.meta(&name.to_lowercase(), value.trim())?;
+            }
+            ("soc", label) => self.start("chorus", label),
+            ("sov", label) => self.start("verse", label),
+            ("sob", label) => self.start("bridge", label),
+            ("eoc" | "eov" | "eob", _) => self.end(),
+            ("chorus", label) => {
+                self.flush();
+                let label = label
+                    .map(String::from)
+                    .or_else(|| self.chorus.clone())
+                    .unwrap_or_else(|| "chorus".to_owned());
+                self.parts.push(vec![format!("# {label}")]);
+            }
+            (name, label) if name.starts_with("start_of_") => {
+                self.start(&name["start_of_".len()..], label);
+            }
+            (name, _) if name.starts_with("end_of_") => self.end(),
+            (name, Some(value)) => self.meta(name, value)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, line: &str) -> Result<(), Error> {
+        let line = line.trim_end();
+        if line.starts_with('#') {
+            Ok(())
+        } else if let Some(directive) = line
+            .trim_start()
+            .strip_prefix('{')
+            .and_then(|rest| rest.strip_suffix('}'))
+        {
+            self.directive(directive)
+        } else if line.trim().is_empty() {
+            if !self.in_section {
+                self.flush();
+            }
+            Ok(())
+        } else {
+            self.current.push(line.to_owned());
+            Ok(())
+        }
+    }
+}
+
+impl FromStr for ChordPro {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut reader = Reader::default();
+        for line in s.lines() {
+            reader.line(line)?;
+        }
+        reader.flush();
+        let title = reader
+            .title
+            .ok_or_else(|| Error::Parse("ChordPro without title".to_owned()))?;
+        Ok(Self(LyricPost {
+            title,
+            metadata: reader.metadata,
+            parts: reader.parts,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChordPro;
+    use crate::{LyricPost, Metadata, parts::to_parts};
+
+    const KLOKJES: &str = "# couplet 1\n[G]Klokjes [D]klinken\n\n# refrein\n[C]Kling klang\nKling [G]klang\n\nTweede couplet\n\n# refrein";
+
+    fn klokjes() -> LyricPost {
+        LyricPost {
+            title: "Klokjes".to_owned(),
+            metadata: Metadata {
+                key: Some("G".to_owned()),
+                tempo: Some(100),
+                ccli: Some(1234),
+                tags: vec!["kerst".to_owned()],
+                ..Metadata::default()
+            },
+            parts: to_parts(KLOKJES),
+        }
+    }
+
+    #[test]
+    fn chordpro_round_trip() {
+        let text = ChordPro(klokjes()).to_string();
+        assert!(text.starts_with("{title: Klokjes}\n{key: G}\n{tempo: 100}\n{meta: ccli 1234}"));
+        assert!(text.contains("{start_of_chorus: refrein}\n[C]Kling klang"));
+        assert!(text.contains("{chorus: refrein}"));
+        let chordpro: ChordPro = text.parse().unwrap();
+        assert_eq!(chordpro.0, klokjes());
+    }
+
+    #[test]
+    fn chordpro_parse() {
+        let text = "# Comment\n{t: Klokjes}\n{st: Kerstlied}\n{artist: Onbekend}\n\n{soc}\n[C]Kling klang\n\nKling klang\n{eoc}\n\nCouplet\n\n{chorus}\n";
+        let chordpro: ChordPro = text.parse().unwrap();
+        let lyric_post = chordpro.0;
+        assert_eq!(lyric_post.title, "Klokjes");
+        assert_eq!(lyric_post.metadata.subtitle.as_deref(), Some("Kerstlied"));
+        assert_eq!(
+            lyric_post.parts,
+            vec![
+                vec!["# chorus", "[C]Kling klang", "Kling klang"],
+                vec!["Couplet"],
+                vec!["# chorus"],
+            ]
+        );
+    }
+
+    #[test]
+    fn chordpro_without_title() {
+        assert!("Kling klang".parse::<ChordPro>().is_err());
+    }
+}
@@ -0,0 +1,475 @@
+/*!
+Reading and writing lyrics in the [`OpenLyrics`](https://docs.openlyrics.org) xml format, as used by `OpenLP`.
+
+Titles, authors, copyright, ccli number, key, tempo and themes map onto the metadata. Verses are named
+like `v1`, `c1` or `b1` and are sung in the verse order. When reading, a verse that is sung more than once
+is labelled and repeated by reference. Verses that are not in the verse order are not sung and are left out.
+Inline chords are written as `<chord name="G"/>`.
+*/
+
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+use std::collections::{HashMap, HashSet};
+
+use xml::attribute::OwnedAttribute;
+use xml::escape::{escape_str_attribute, escape_str_pcdata};
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::chords::segments;
+use crate::error::Error;
+use crate::parts::{PartKind, Section, Structure};
+use crate::{Lyric, LyricPost, Metadata};
+
+const NAMESPACE: &str = "http://openlyrics.info/namespace/2009/song";
+const WORDS: &str = "words";
+const MUSIC: &str = "music";
+
+/// A lyric in `OpenLyrics` format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpenLyrics(pub LyricPost);
+
+impl From<Lyric> for OpenLyrics {
+    fn from(lyric: Lyric) -> Self {
+        Self(lyric.into())
+    }
+}
+
+impl From<OpenLyrics> for LyricPost {
+    fn from(openlyrics: OpenLyrics) -> Self {
+        openlyrics.0
+    }
+}
+
+fn verse_letter(kind: PartKind) -> char {
+    match kind {
+        PartKind::Verse => 'v',
+        PartKind::PreChorus => 'p',
+        PartKind::Chorus => 'c',
+        PartKind::Bridge => 'b',
+        PartKind::Intro => 'i',
+        PartKind::Outro => 'e',
+        PartKind::Other => 'o',
+    }
+}
+
+/// Label for the verse named `name`, like `chorus 2` for `c2`
+fn verse_label(name: &str) -> String {
+    let mut chars = name.chars();
+    let word = match chars.next().map(|c| c.to_ascii_lowercase()) {
+        Some('v') => "verse",
+        Some('p') => "pre-chorus",
+        Some('c') => "chorus",
+        Some('b') => "bridge",
+        Some('i') => "intro",
+        Some('e') => "outro",
+        _ => "other",
+    };
+    match chars.as_str() {
+        "" => word.to_owned(),
+        number => format!("{word} {number}"),
+    }
+}
+
+fn element(name: &str, value: Option<&str>) -> Option<String> {
+    value.map(|value| format!("<{name}>{}</{name}>", escape_str_pcdata(value)))
+}
+
+fn titles(title: &str, metadata: &Metadata) -> String {
+    let lang = metadata
+        .language
+        .as_deref()
+        .map(|language| format!(" lang=\"{}\"", escape_str_attribute(language)))
+        .unwrap_or_default();
+    core::iter::once(format!("<title{lang}>{}</title>", escape_str_pcdata(title)))
+        .chain(element("title", metadata.subtitle.as_deref()))
+        .collect()
+}
+
+fn authors(metadata: &Metadata) -> Option<String> {
+    let authors = [(WORDS, &metadata.lyricist), (MUSIC, &metadata.composer)]
+        .into_iter()
+        .filter_map(|(kind, name)| {
+            name.as_deref().map(|name| {
+                format!(
+                    "<author type=\"{kind}\">{}</author>",
+                    escape_str_pcdata(name)
+                )
+            })
+        })
+        .collect::<String>();
+    (!authors.is_empty()).then(|| format!("<authors>{authors}</authors>"))
+}
+
+fn themes(tags: &[String]) -> Option<String> {
+    (!tags.is_empty()).then(|| {
+        format!(
+            "<themes>{}</themes>",
+            tags.iter()
+                .filter_map(|tag| element("theme", Some(tag)))
+                .collect::<String>()
+        )
+    })
+}
+
+fn lines(lines: &[String]) -> String {
+    lines
+        .iter()
+        .map(|line| {
+            segments(line)
+                .into_iter()
+                .map(|segment| {
+                    let text = escape_str_pcdata(&segment.text);
+                    match segment.chord {
+                        Some(chord) => {
+                            format!("<chord name=\"{}\"/>{text}", escape_str_attribute(&chord))
+                        }
+                        None => text.into_owned(),
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("<br/>")
+}
+
+impl Display for OpenLyrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let lyric = &self.0;
+        let metadata = &lyric.metadata;
+        let structure = Structure::from(lyric.parts.as_slice());
+
+        let mut counts = HashMap::<char, usize>::new();
+        let mut names = HashMap::<String, String>::new();
+        let mut verses = vec![];
+        let mut part_names = vec![];
+        for section in &structure.0 {
+            if let Section::Part(part) = section {
+                let letter = verse_letter(part.kind);
+                let count = counts.entry(letter).or_default();
+                *count += 1;
+                let name = format!("{letter}{count}");
+                if let Some(label) = &part.label {
+                    names.insert(label.to_lowercase(), name.clone());
+                }
+                verses.push(format!(
+                    "<verse name=\"{name}\"><lines>{}</lines></verse>",
+                    lines(&part.lines)
+                ));
+                part_names.push(name);
+            }
+        }
+        let mut part_names = part_names.into_iter();
+        let order = structure
+            .0
+            .iter()
+            .filter_map(|section| match section {
+                Section::Part(_) => part_names.next(),
+                Section::Reference(label) => names.get(&label.to_lowercase()).cloned(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let properties = [
+            Some(format!(
+                "<titles>{}</titles>",
+                titles(&lyric.title, metadata)
+            )),
+            authors(metadata),
+            element("copyright", metadata.copyright.as_deref()),
+            element(
+                "ccliNo",
+                metadata.ccli.map(|ccli| ccli.to_string()).as_deref(),
+            ),
+            metadata
+                .tempo
+                .map(|tempo| format!("<tempo type=\"bpm\">{tempo}</tempo>")),
+            element("key", metadata.key.as_deref()),
+            element("verseOrder", Some(&order)),
+            themes(&metadata.tags),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<String>();
+
+        write!(
+            f,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<song xmlns=\"{NAMESPACE}\" version=\"0.9\" createdIn=\"lipl\" modifiedIn=\"lipl\"><properties>{properties}</properties><lyrics>{}</lyrics></song>\n",
+            verses.concat()
+        )
+    }
+}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.name.local_name == name)
+        .map(|attribute| attribute.value.as_str())
+}
+
+/// Chord from `<chord name="Am"/>` or `<chord root="A" structure="m" bass="E"/>`
+fn chord(attributes: &[OwnedAttribute]) -> Option<String> {
+    attribute(attributes, "name").map(String::from).or_else(|| {
+        attribute(attributes, "root").map(|root| {
+            let structure = attribute(attributes, "structure").unwrap_or_default();
+            match attribute(attributes, "bass") {
+                Some(bass) => format!("{root}{structure}/{bass}"),
+                None => format!("{root}{structure}"),
+            }
+        })
+    })
+}
+
+fn append(field: &mut Option<String>, name: String) {
+    *field = Some(match field.take() {
+        Some(names) => format!("{names}, {name}"),
+        None => name,
+    });
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::Parse(format!("OpenLyrics {name} {value}")))
+}
+
+#[derive(Default)]
+struct Reader {
+    elements: Vec<String>,
+    text: String,
+    line: String,
+    titles: Vec<String>,
+    author: Option<String>,
+    tempo_bpm: bool,
+    metadata: Metadata,
+    verse_order: Option<String>,
+    verses: Vec<(String, Vec<String>)>,
+}
+
+impl Reader {
+    fn inside(&self, name: &str) -> bool {
+        self.elements.iter().any(|element| element == name)
+    }
+
+    fn end_line(&mut self) {
+        let line = self.line.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.line.clear();
+        if let Some((_, lines)) = self.verses.last_mut().filter(|_| !line.is_empty()) {
+            lines.push(line);
+        }
+    }
+
+    fn start(&mut self, name: &str, attributes: &[OwnedAttribute]) {
+        self.text.clear();
+        match name {
+            "title" if self.titles.is_empty() => {
+                self.metadata.language = attribute(attributes, "lang").map(String::from);
+            }
+            "author" => self.author = attribute(attributes, "type").map(String::from),
+            "tempo" => self.tempo_bpm = attribute(attributes, "type") == Some("bpm"),
+            "verse" => {
+                let name = attribute(attributes, "name")
+                    .map_or_else(|| format!("v{}", self.verses.len() + 1), String::from);
+                self.verses.push((name, vec![]));
+            }
+            "br" => self.end_line(),
+            "chord" => {
+                if let Some(chord) = chord(attributes) {
+                    self.line.push('[');
+                    self.line.push_str(&chord);
+                    self.line.push(']');
+                }
+            }
+            _ => {}
+        }
+        self.elements.push(name.to_owned());
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        let name = self.elements.pop().unwrap_or_default();
+        let text = self.text.trim().to_owned();
+        match name.as_str() {
+            "title" => self.titles.push(text),
+            "author" => match self.author.as_deref() {
+                Some(MUSIC) => append(&mut self.metadata.composer, text),
+                None | Some(WORDS) => append(&mut self.metadata.lyricist, text),
+                _ => {}
+            },
+            "copyright" => self.metadata.copyright = Some(text),
+            "ccliNo" => self.metadata.ccli = Some(parse_number(&name, &text)?),
+            "tempo" if self.tempo_bpm => self.metadata.tempo = Some(parse_number(&name, &text)?),
+            "key" => self.metadata.key = Some(text),
+            "verseOrder" => self.verse_order = Some(text),
+            "theme" => self.metadata.tags.push(text),
+            "lines" => self.end_line(),
+            _ => {}
+        }
+        self.text.clear();
+        Ok(())
+    }
+
+    fn characters(&mut self, text: &str) {
+        if self.inside("lines") {
+            if !self.inside("comment") {
+                self.line.push_str(text);
+            }
+        } else {
+            self.text.push_str(text);
+        }
+    }
+
+    /// The verses in the verse order, labelling those that are sung more than once
+    fn parts(&self) -> Vec<Vec<String>> {
+        let order = match self.verse_order.as_deref() {
+            Some(order) if !order.trim().is_empty() => {
+                order.split_whitespace().map(str::to_lowercase).collect()
+            }
+            _ => self
+                .verses
+                .iter()
+                .map(|(name, _)| name.to_lowercase())
+                .collect::<Vec<_>>(),
+        };
+        let mut sung = HashSet::new();
+        order
+            .iter()
+            .filter_map(|name| {
+                let (_, lines) = self
+                    .verses
+                    .iter()
+                    .find(|(verse, lines)| verse.eq_ignore_ascii_case(name) && !lines.is_empty())?;
+                let repeated = order.iter().filter(|other| *other == name).count() > 1;
+                let label = (repeated || !name.starts_with('v'))
+                    .then(|| format!("# {}", verse_label(name)));
+                if sung.insert(name) {
+                    Some(label.into_iter().chain(lines.iter().cloned()).collect())
+                } else {
+                    label.map(|label| vec![label])
+                }
+            })
+            .collect()
+    }
+}
+
+impl FromStr for OpenLyrics {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut reader = Reader::default();
+        for event in EventReader::new(s.as_bytes()) {
+            match event.map_err(|error| Error::Parse(format!("OpenLyrics {error}")))? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => reader.start(&name.local_name, &attributes),
+                XmlEvent::EndElement { .. } => reader.end()?,
+                XmlEvent::Characters(text) | XmlEvent::CData(text) | XmlEvent::Whitespace(text) => {
+                    reader.characters(&text);
+                }
+                _ => {}
+            }
+        }
+        let parts = reader.parts();
+        let mut titles = reader.titles.into_iter().filter(|title| !title.is_empty());
+        let title = titles
+            .next()
+            .ok_or_else(|| Error::Parse("OpenLyrics without title".to_owned()))?;
+        Ok(Self(LyricPost {
+            title,
+            metadata: Metadata {
+                subtitle: titles.next(),
+                ..reader.metadata
+            },
+            parts,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpenLyrics;
+    use crate::{
+        LyricPost, Metadata,
+        parts::{expand, to_parts},
+    };
+
+    const KLOKJES: &str = "# couplet 1\n[G]Klokjes [D]klinken\n\n# refrein\n[C]Kling & klang\nKling [G]klang\n\nTweede couplet\n\n# refrein";
+
+    fn klokjes() -> LyricPost {
+        LyricPost {
+            title: "Klokjes".to_owned(),
+            metadata: Metadata {
+                subtitle: Some("Kerstlied".to_owned()),
+                lyricist: Some("Onbekend".to_owned()),
+                composer: Some("Ook onbekend".to_owned()),
+                key: Some("G".to_owned()),
+                tempo: Some(100),
+                copyright: Some("Publiek domein".to_owned()),
+                ccli: Some(1234),
+                language: Some("nl".to_owned()),
+                tags: vec!["kerst".to_owned()],
+            },
+            parts: to_parts(KLOKJES),
+        }
+    }
+
+    #[test]
+    fn openlyrics_round_trip() {
+        let text = OpenLyrics(klokjes()).to_string();
+        assert!(text.contains("<verseOrder>v1 c1 v2 c1</verseOrder>"));
+        assert!(text.contains("<verse name=\"c1\"><lines><chord name=\"C\"/>Kling &amp; klang<br/>Kling <chord name=\"G\"/>klang</lines></verse>"));
+        let openlyrics: OpenLyrics = text.parse().unwrap();
+        assert_eq!(openlyrics.0.title, klokjes().title);
+        assert_eq!(openlyrics.0.metadata, klokjes().metadata);
+        assert_eq!(expand(&openlyrics.0.parts), expand(&klokjes().parts));
+    }
+
+    #[test]
+    fn openlyrics_parse() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<song xmlns="http://openlyrics.info/namespace/2009/song" version="0.9">
+  <properties>
+    <titles><title>Amazing Grace</title></titles>
+    <authors>
+      <author>John Newton</author>
+      <author type="translation" lang="nl">Iemand</author>
+    </authors>
+    <tempo type="text">Moderato</tempo>
+    <verseOrder>v1 c v2 c v1</verseOrder>
+  </properties>
+  <lyrics>
+    <verse name="v1">
+      <lines>
+        <comment>Rustig</comment>
+        <chord root="G"/>Amazing grace how <chord root="C" structure="maj7" bass="E"/>sweet<br/>
+        the sound
+      </lines>
+    </verse>
+    <verse name="c"><lines>That saved a wretch</lines><lines>like me</lines></verse>
+    <verse name="v2"><lines>I once was lost</lines></verse>
+    <verse name="b"><lines>Not sung</lines></verse>
+  </lyrics>
+</song>"#;
+        let lyric_post = text.parse::<OpenLyrics>().unwrap().0;
+        assert_eq!(lyric_post.title, "Amazing Grace");
+        assert_eq!(lyric_post.metadata.lyricist.as_deref(), Some("John Newton"));
+        assert_eq!(lyric_post.metadata.tempo, None);
+        assert_eq!(
+            lyric_post.parts,
+            vec![
+                vec![
+                    "# verse 1",
+                    "[G]Amazing grace how [Cmaj7/E]sweet",
+                    "the sound"
+                ],
+                vec!["# chorus", "That saved a wretch", "like me"],
+                vec!["I once was lost"],
+                vec!["# chorus"],
+                vec!["# verse 1"],
+            ]
+        );
+    }
+
+    #[test]
+    fn openlyrics_invalid() {
+        assert!("<song><lyrics/></song>".parse::<OpenLyrics>().is_err());
+        assert!("<song><properties>".parse::<OpenLyrics>().is_err());
+    }
+}
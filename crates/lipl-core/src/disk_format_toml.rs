@@ -24,6 +24,7 @@ fn lines_to_lyric_post(acc: LyricPost, mut lines: Lines) -> Result<LyricPost, to
         lines_to_lyric_post(
             LyricPost {
                 title: meta.title,
+                metadata: meta.metadata,
                 parts: acc.parts,
             },
             lines,
@@ -32,6 +33,7 @@ fn lines_to_lyric_post(acc: LyricPost, mut lines: Lines) -> Result<LyricPost, to
         lines_to_lyric_post(
             LyricPost {
                 title: acc.title,
+                metadata: acc.metadata,
                 parts: acc.parts.into_iter().chain(once(next)).collect::<Vec<_>>(),
             },
             lines,
@@ -52,6 +54,7 @@ impl Display for Lyric {
         let lyric_meta = LyricMeta {
             title: self.title.clone(),
             hash: self.etag(),
+            metadata: self.metadata.clone(),
         };
         let toml = toml::ser::to_string_pretty(&lyric_meta).unwrap();
         let parts_string: String = self
@@ -108,14 +111,15 @@ impl FromStr for LyricMeta {
 #[cfg(test)]
 mod tests {
 
-    use super::{Lyric, LyricMeta, LyricPost, PlaylistPost};
-    use crate::Uuid;
+    use super::{Lyric, LyricMeta, LyricPost, Playlist, PlaylistPost};
+    use crate::{LyricId, Metadata, Transposition, parts::expand};
     use std::vec;
 
     fn hertog_jan_lyric() -> Lyric {
         Lyric {
-            id: "T2NPjHifDf1E1UfZZA6TDB".parse::<Uuid>().unwrap(),
+            id: "T2NPjHifDf1E1UfZZA6TDB".parse::<LyricId>().unwrap(),
             title: "Hertog Jan".to_owned(),
+            metadata: Metadata::default(),
             parts: vec![
                 vec![
                     "Toen den hertog Jan kwam varen".to_owned(),
@@ -242,7 +246,7 @@ mod tests {
     fn lyric_post_parse_equals_display() {
         let lyric_post: LyricPost = hertog_jan_lyric().to_string().parse().unwrap();
         println!("{}", &toml::ser::to_string_pretty(&lyric_post).unwrap());
-        let uuid = HERTOG_JAN_ID.to_owned().parse::<Uuid>().unwrap();
+        let uuid = HERTOG_JAN_ID.to_owned().parse::<LyricId>().unwrap();
         let lyric = Lyric::from((Some(uuid), lyric_post));
         assert_eq!(
             lyric.to_string().as_str(),
@@ -260,17 +264,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lyric_metadata_round_trip() {
+        let mut lyric = hertog_jan_lyric();
+        lyric.metadata = Metadata {
+            subtitle: Some("Brabants volkslied".to_owned()),
+            composer: Some("Traditioneel".to_owned()),
+            key: Some("G".to_owned()),
+            tempo: Some(112),
+            ccli: Some(1234),
+            language: Some("nl".to_owned()),
+            tags: vec!["volkslied".to_owned(), "brabant".to_owned()],
+            ..Metadata::default()
+        };
+        let lyric_post: LyricPost = lyric.to_string().parse().unwrap();
+        assert_eq!(lyric_post.metadata, lyric.metadata);
+        assert_eq!(lyric_post.parts, lyric.parts);
+    }
+
+    #[test]
+    fn lyric_labelled_parts_round_trip() {
+        let mut lyric = hertog_jan_lyric();
+        lyric.parts[0].insert(0, "# couplet 1".to_owned());
+        lyric.parts[1] = vec!["# refrein".to_owned(), "Harba lorifa".to_owned()];
+        lyric.parts[2] = vec!["# refrein".to_owned()];
+        let lyric_post: LyricPost = lyric.to_string().parse().unwrap();
+        assert_eq!(lyric_post.parts, lyric.parts);
+        let expanded = expand(&lyric_post.parts);
+        assert_eq!(expanded[2], vec!["Harba lorifa".to_owned()]);
+    }
+
+    #[test]
+    fn lyric_without_metadata_parse() {
+        let text = "+++\ntitle = \"Hertog Jan\"\n+++\n\nToen den hertog Jan kwam varen\n";
+        let lyric_post: LyricPost = text.parse().unwrap();
+        assert_eq!(lyric_post.title, HERTOG_JAN_TITLE.to_owned());
+        assert!(lyric_post.metadata.is_empty());
+        assert_eq!(lyric_post.parts.len(), 1);
+    }
+
     #[test]
     fn display_playlist() {
+        let member = PLAYLIST_MEMBER1.parse::<LyricId>().unwrap();
         let playlist = PlaylistPost {
             title: "Kerst".to_owned(),
             members: vec![
-                PLAYLIST_MEMBER1.parse::<Uuid>().unwrap(),
-                PLAYLIST_MEMBER2.parse::<Uuid>().unwrap(),
-                PLAYLIST_MEMBER3.parse::<Uuid>().unwrap(),
+                member,
+                PLAYLIST_MEMBER2.parse::<LyricId>().unwrap(),
+                PLAYLIST_MEMBER3.parse::<LyricId>().unwrap(),
             ],
+            transpositions: [(member, Transposition::new(-2))].into_iter().collect(),
         };
 
-        println!("{}", toml::ser::to_string_pretty(&playlist).unwrap());
+        let text = Playlist::from(playlist.clone()).to_string();
+        println!("{text}");
+        let parsed: PlaylistPost = text.parse().unwrap();
+        assert_eq!(parsed.transpositions, playlist.transpositions);
     }
 }
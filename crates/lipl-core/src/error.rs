@@ -1,6 +1,7 @@
 use std::env::VarError;
 
 use crate::Uuid;
+use crate::validate::{FieldError, to_text};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -62,6 +63,21 @@ pub enum Error {
     #[error("Occupied")]
     Occupied,
 
+    #[error("Read only: the library cannot be changed now")]
+    ReadOnly,
+
+    #[error("Transaction log corrupt at line {0}: {1}")]
+    CorruptLog(usize, String),
+
+    #[error("Transaction log could not be written, changes are refused until it is compacted: {0}")]
+    LogFailed(String),
+
+    #[error("Revision {1} of {0} not found")]
+    RevisionNotFound(Uuid, u32),
+
+    #[error("Precondition failed for {0}")]
+    PreconditionFailed(Uuid),
+
     #[error(transparent)]
     Warp(Box<dyn std::error::Error + Send + Sync>),
 
@@ -74,6 +90,9 @@ pub enum Error {
     #[error("Parse error for {0}")]
     Parse(String),
 
+    #[error("Validation failed: {}", to_text(.0))]
+    Validation(Vec<FieldError>),
+
     #[error("Join error for {0}")]
     Join(Box<dyn std::error::Error + Send + Sync>),
 
@@ -102,6 +121,73 @@ pub enum Error {
     Mpsc(#[from] std::sync::mpsc::RecvError),
 }
 
+/// Category of an error, for instance to choose the status of a response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The item or revision does not exist
+    NotFound,
+    /// The request conflicts with the stored data
+    Conflict,
+    /// The entity tag in a conditional request does not match
+    PreconditionFailed,
+    /// The input is invalid
+    Validation,
+    /// The store is read only for now
+    ReadOnly,
+    /// The store cannot be reached or has stopped
+    Unavailable,
+    Internal,
+}
+
+impl Error {
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::NotFound(_) | Error::NoKey(_) | Error::RevisionNotFound(..) => {
+                ErrorKind::NotFound
+            }
+            Error::Occupied => ErrorKind::Conflict,
+            Error::PreconditionFailed(_) => ErrorKind::PreconditionFailed,
+            Error::ReadOnly => ErrorKind::ReadOnly,
+            Error::PlaylistInvalidMember(..)
+            | Error::Parse(_)
+            | Error::Validation(_)
+            | Error::Argument(_)
+            | Error::Bs58DecodeError(_)
+            | Error::UuidError(_) => ErrorKind::Validation,
+            Error::Connection(_)
+            | Error::SendFailed(_)
+            | Error::Canceled(_)
+            | Error::Stop
+            | Error::LogFailed(_)
+            | Error::Mpsc(_) => ErrorKind::Unavailable,
+            _ => ErrorKind::Internal,
+        }
+    }
+
+    /// Machine readable code that stays the same when the message changes
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) | Error::NoKey(_) => "not-found",
+            Error::RevisionNotFound(..) => "revision-not-found",
+            Error::Occupied => "occupied",
+            Error::PreconditionFailed(_) => "precondition-failed",
+            Error::PlaylistInvalidMember(..) => "invalid-member",
+            Error::Parse(_) | Error::Bs58DecodeError(_) | Error::UuidError(_) => "parse",
+            Error::Argument(_) => "invalid-argument",
+            Error::Validation(_) => "validation",
+            Error::ReadOnly => "read-only",
+            Error::CorruptLog(..) => "corrupt-log",
+            Error::LogFailed(_) => "log-failed",
+            _ => match self.kind() {
+                ErrorKind::Unavailable => "unavailable",
+                _ => "internal",
+            },
+        }
+    }
+}
+
 pub fn postgres_error<E>(error: E) -> Error
 where
     E: std::error::Error + Send + Sync + 'static,
@@ -10,19 +10,39 @@ lyrics ands playlist from a store. All implementation except `MemoryRepo` implem
 
 */
 
-pub use crate::uuid::Uuid;
+pub use crate::disk_format_chordpro::ChordPro;
+pub use crate::disk_format_openlyrics::OpenLyrics;
+pub use crate::metadata::Metadata;
+pub use crate::page::{Cursor, Page, PageRequest};
+pub use crate::revision::{Revision, RevisionInfo};
+pub use crate::transpose::{Transposition, Transpositions};
+pub use crate::uuid::{
+    IdFormat, IdGenerator, LyricId, PlaylistId, Uuid, id_format, id_generator, set_id_generator,
+    with_bs58, with_id_format, with_id_format_sync,
+};
+pub use crate::validate::{Rules, Validate};
 use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
-pub use error::{Error, postgres_error, redis_error};
+pub use error::{Error, ErrorKind, postgres_error, redis_error};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
+pub mod change;
+pub mod chords;
+mod disk_format_chordpro;
+mod disk_format_openlyrics;
 mod disk_format_toml;
 pub mod error;
+pub mod metadata;
+pub mod page;
 pub mod parts;
 pub mod reexport;
+pub mod revision;
+pub mod search;
 #[cfg(feature = "transaction")]
 pub mod transaction;
+pub mod transpose;
 mod uuid;
+pub mod validate;
 pub mod vec_ext;
 
 pub type Result<T, E = Error> = core::result::Result<T, E>;
@@ -34,14 +54,45 @@ pub const TOML_PREFIX: &str = "+++";
 pub trait Repo {
     async fn get_lyrics(&self) -> Result<Vec<Lyric>>;
     async fn get_lyric_summaries(&self) -> Result<Vec<Summary>>;
-    async fn get_lyric(&self, id: Uuid) -> Result<Lyric>;
+    async fn get_lyrics_page(&self, page: PageRequest) -> Result<Page<Lyric>>;
+    async fn get_lyric_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>>;
+    async fn get_lyric(&self, id: LyricId) -> Result<Lyric>;
     async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric>;
-    async fn delete_lyric(&self, id: Uuid) -> Result<()>;
+    async fn delete_lyric(&self, id: LyricId) -> Result<()>;
+    /// Upserts the lyric only if the entity tag of the stored lyric matches `if_match`.
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, if_match: String) -> Result<Lyric>;
+    /// Deletes the lyric only if the entity tag of the stored lyric matches `if_match`.
+    async fn delete_lyric_if_match(&self, id: LyricId, if_match: String) -> Result<()>;
+    async fn get_lyric_revisions(&self, id: LyricId) -> Result<Vec<RevisionInfo>>;
+    async fn get_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Revision<Lyric>>;
+    /// Upserts the lyric as it was in `revision`, which creates a new revision.
+    async fn restore_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Lyric>;
+    async fn search(&self, query: &str) -> Result<Vec<search::Hit>>;
     async fn get_playlists(&self) -> Result<Vec<Playlist>>;
     async fn get_playlist_summaries(&self) -> Result<Vec<Summary>>;
-    async fn get_playlist(&self, id: Uuid) -> Result<Playlist>;
+    async fn get_playlists_page(&self, page: PageRequest) -> Result<Page<Playlist>>;
+    async fn get_playlist_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>>;
+    async fn get_playlist(&self, id: PlaylistId) -> Result<Playlist>;
     async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist>;
-    async fn delete_playlist(&self, id: Uuid) -> Result<()>;
+    async fn delete_playlist(&self, id: PlaylistId) -> Result<()>;
+    /// Upserts the playlist only if the entity tag of the stored playlist matches `if_match`.
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        if_match: String,
+    ) -> Result<Playlist>;
+    /// Deletes the playlist only if the entity tag of the stored playlist matches `if_match`.
+    async fn delete_playlist_if_match(&self, id: PlaylistId, if_match: String) -> Result<()>;
+    async fn get_playlist_revisions(&self, id: PlaylistId) -> Result<Vec<RevisionInfo>>;
+    async fn get_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> Result<Revision<Playlist>>;
+    /// Upserts the playlist as it was in `revision`, which creates a new revision.
+    async fn restore_playlist_revision(&self, id: PlaylistId, revision: u32) -> Result<Playlist>;
+    /// Changes made from now on, by this process or, if the backend notifies them, by others.
+    async fn subscribe(&self) -> Result<change::ChangeStream>;
     async fn stop(&self) -> Result<()>;
 }
 
@@ -55,17 +106,19 @@ pub trait HasSummary {
     fn summary(&self) -> Summary;
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Lyric {
-    pub id: Uuid,
+    pub id: LyricId,
     pub title: String,
+    #[serde(flatten)]
+    pub metadata: Metadata,
     pub parts: Vec<Vec<String>>,
 }
 
 impl HasSummary for Lyric {
     fn summary(&self) -> Summary {
         Summary {
-            id: self.id,
+            id: self.id.into(),
             title: self.title.clone(),
         }
     }
@@ -78,17 +131,20 @@ where
     a.summary().title.cmp(&b.summary().title)
 }
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 pub struct LyricPost {
     pub title: String,
+    #[serde(flatten)]
+    pub metadata: Metadata,
     pub parts: Vec<Vec<String>>,
 }
 
-impl From<(Option<Uuid>, LyricPost)> for Lyric {
-    fn from(data: (Option<Uuid>, LyricPost)) -> Lyric {
+impl From<(Option<LyricId>, LyricPost)> for Lyric {
+    fn from(data: (Option<LyricId>, LyricPost)) -> Lyric {
         Lyric {
             id: data.0.unwrap_or_default(),
             title: data.1.title,
+            metadata: data.1.metadata,
             parts: data.1.parts,
         }
     }
@@ -96,11 +152,7 @@ impl From<(Option<Uuid>, LyricPost)> for Lyric {
 
 impl From<LyricPost> for Lyric {
     fn from(lyric_post: LyricPost) -> Self {
-        Self {
-            id: Uuid::default(),
-            title: lyric_post.title,
-            parts: lyric_post.parts,
-        }
+        (None, lyric_post).into()
     }
 }
 
@@ -108,6 +160,7 @@ impl From<Lyric> for LyricPost {
     fn from(lyric: Lyric) -> Self {
         Self {
             title: lyric.title,
+            metadata: lyric.metadata,
             parts: lyric.parts,
         }
     }
@@ -117,22 +170,26 @@ impl From<(&str, &str)> for LyricPost {
     fn from(value: (&str, &str)) -> Self {
         Self {
             title: value.0.to_owned(),
+            metadata: Metadata::default(),
             parts: parts::to_parts(value.1),
         }
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Playlist {
-    pub id: Uuid,
+    pub id: PlaylistId,
     pub title: String,
-    pub members: Vec<Uuid>,
+    pub members: Vec<LyricId>,
+    /// Key per member, by lyric id
+    #[serde(default, skip_serializing_if = "Transpositions::is_empty")]
+    pub transpositions: Transpositions,
 }
 
 impl HasSummary for Playlist {
     fn summary(&self) -> Summary {
         Summary {
-            id: self.id,
+            id: self.id.into(),
             title: self.title.clone(),
         }
     }
@@ -144,20 +201,30 @@ impl From<PlaylistPost> for Playlist {
     }
 }
 
-impl From<(Option<Uuid>, PlaylistPost)> for Playlist {
-    fn from(data: (Option<Uuid>, PlaylistPost)) -> Playlist {
+/// Transpositions of lyrics that are not a member are left out
+impl From<(Option<PlaylistId>, PlaylistPost)> for Playlist {
+    fn from(data: (Option<PlaylistId>, PlaylistPost)) -> Playlist {
+        let PlaylistPost {
+            title,
+            members,
+            mut transpositions,
+        } = data.1;
+        transpositions.retain(|id, _| members.contains(id));
         Playlist {
             id: data.0.unwrap_or_default(),
-            title: data.1.title,
-            members: data.1.members,
+            title,
+            members,
+            transpositions,
         }
     }
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct PlaylistPost {
     pub title: String,
-    pub members: Vec<Uuid>,
+    pub members: Vec<LyricId>,
+    #[serde(default, skip_serializing_if = "Transpositions::is_empty")]
+    pub transpositions: Transpositions,
 }
 
 impl From<Playlist> for PlaylistPost {
@@ -165,6 +232,7 @@ impl From<Playlist> for PlaylistPost {
         PlaylistPost {
             title: p.title,
             members: p.members,
+            transpositions: p.transpositions,
         }
     }
 }
@@ -211,17 +279,20 @@ where
     list.iter().map(to_summary).collect()
 }
 
-pub fn ids<T>(list: impl Iterator<Item = T>) -> Vec<Uuid>
+pub fn ids<T, I>(list: impl Iterator<Item = T>) -> Vec<I>
 where
     T: HasSummary,
+    I: From<Uuid>,
 {
-    list.map(|s| s.summary().id).collect()
+    list.map(|s| s.summary().id.into()).collect()
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct LyricMeta {
     pub title: String,
     pub hash: Option<String>,
+    #[serde(flatten)]
+    pub metadata: Metadata,
 }
 
 impl From<&Lyric> for LyricMeta {
@@ -229,12 +300,25 @@ impl From<&Lyric> for LyricMeta {
         LyricMeta {
             title: l.title.clone(),
             hash: l.etag(),
+            metadata: l.metadata.clone(),
         }
     }
 }
 
 pub trait Etag {
     fn etag(&self) -> Option<String>;
+
+    /// Strong comparison with the value of an `If-Match` header.
+    fn matches_strong(&self, header: &str) -> bool {
+        self.etag()
+            .is_some_and(|etag| header_contains(header, &etag, false))
+    }
+
+    /// Weak comparison with the value of an `If-None-Match` header.
+    fn matches_weak(&self, header: &str) -> bool {
+        self.etag()
+            .is_some_and(|etag| header_contains(header, &etag, true))
+    }
 }
 
 impl<T> Etag for T
@@ -242,17 +326,44 @@ where
     T: Serialize + ?Sized,
 {
     fn etag(&self) -> Option<String> {
-        toml::ser::to_string(self)
-            .inspect(|s| {
-                dbg!(s);
-            })
+        with_bs58(|| toml::ser::to_string(self))
             .map(|s| etag::EntityTag::const_from_data(s.as_bytes()))
             .map(|etag| etag.to_string())
             .ok()
-        // to_stdvec(self)
-        //     .map(|bytes| etag::EntityTag::const_from_data(&bytes))
-        //     .map(|etag| etag.to_string())
-        //     .ok()
+    }
+}
+
+/// Weak comparison of `etag` with the value of an `If-None-Match` header,
+/// for entity tags of a representation that are not computed with `Etag`.
+#[must_use]
+pub fn if_none_match_contains(header: &str, etag: &str) -> bool {
+    header_contains(header, etag, true)
+}
+
+fn header_contains(header: &str, etag: &str, weak: bool) -> bool {
+    header.trim() == "*"
+        || header
+            .split(',')
+            .map(str::trim)
+            .any(|tag| match tag.strip_prefix("W/") {
+                Some(tag) => weak && tag == etag.trim_start_matches("W/"),
+                None => tag == etag,
+            })
+}
+
+/// Checks an `If-Match` precondition against the current value of an item.
+///
+/// # Errors
+///
+/// Returns `Error::PreconditionFailed` if there is no current value or if its entity tag does not match.
+pub fn check_etag<T>(current: Option<&T>, if_match: &str, id: impl Into<Uuid>) -> Result<()>
+where
+    T: Etag,
+{
+    if current.is_some_and(|current| current.matches_strong(if_match)) {
+        Ok(())
+    } else {
+        Err(Error::PreconditionFailed(id.into()))
     }
 }
 
@@ -326,12 +437,16 @@ pub trait Toml {
 
 #[cfg(test)]
 mod test {
-    use crate::{Etag, Lyric, LyricPost, Playlist, PlaylistPost, Uuid};
+    use crate::{
+        Error, Etag, Lyric, LyricId, LyricPost, Metadata, Playlist, PlaylistId, PlaylistPost,
+        Transpositions, check_etag,
+    };
 
     fn lyric() -> Lyric {
         Lyric {
-            id: "T2NPjHifDf1E1UfZZA6TDB".parse::<Uuid>().unwrap(),
+            id: "T2NPjHifDf1E1UfZZA6TDB".parse::<LyricId>().unwrap(),
             title: "Hertog Jan".to_owned(),
+            metadata: Metadata::default(),
             parts: vec![],
         }
     }
@@ -343,15 +458,17 @@ mod test {
     fn lyric_post() -> LyricPost {
         LyricPost {
             title: "Hertog Jan".to_owned(),
+            metadata: Metadata::default(),
             parts: vec![],
         }
     }
 
     fn playlist() -> Playlist {
         Playlist {
-            id: "T2NPjHifDf1E1UfZZA6TDB".parse::<Uuid>().unwrap(),
+            id: "T2NPjHifDf1E1UfZZA6TDB".parse::<PlaylistId>().unwrap(),
             title: "Alles".to_owned(),
             members: vec![],
+            transpositions: Transpositions::default(),
         }
     }
 
@@ -359,6 +476,7 @@ mod test {
         PlaylistPost {
             title: "Alles".to_owned(),
             members: vec![],
+            transpositions: Transpositions::default(),
         }
     }
 
@@ -397,4 +515,30 @@ mod test {
             "\"29-8504763498153674018128170645387736386\""
         );
     }
+
+    #[test]
+    fn etag_matches() {
+        let lyric = lyric();
+        let etag = lyric.etag().unwrap();
+        assert!(lyric.matches_strong(&etag));
+        assert!(lyric.matches_strong(&format!("\"other\", {etag}")));
+        assert!(lyric.matches_strong("*"));
+        assert!(!lyric.matches_strong(&format!("W/{etag}")));
+        assert!(lyric.matches_weak(&format!("W/{etag}")));
+        assert!(!lyric.matches_weak("\"other\""));
+    }
+
+    #[test]
+    fn check_etag_missing() {
+        let lyric = lyric();
+        assert!(check_etag(Some(&lyric), &lyric.etag().unwrap(), lyric.id).is_ok());
+        assert!(matches!(
+            check_etag(Some(&lyric), "\"other\"", lyric.id),
+            Err(Error::PreconditionFailed(_))
+        ));
+        assert!(matches!(
+            check_etag(None::<&Lyric>, "*", lyric.id),
+            Err(Error::PreconditionFailed(_))
+        ));
+    }
 }
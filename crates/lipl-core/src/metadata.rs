@@ -0,0 +1,72 @@
+/*!
+Optional descriptive data of a lyric.
+
+All fields are optional, so lyrics stored before these fields existed load unchanged.
+Fields without a value are not serialized, which keeps the entity tags of those lyrics the same.
+*/
+
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Metadata {
+    pub subtitle: Option<String>,
+    pub lyricist: Option<String>,
+    pub composer: Option<String>,
+    /// Musical key, for example `G` or `Bbm`
+    pub key: Option<String>,
+    /// Tempo in beats per minute
+    pub tempo: Option<u16>,
+    pub copyright: Option<String>,
+    /// Song number in the CCLI catalog
+    pub ccli: Option<u32>,
+    /// Language tag, for example `nl`
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Metadata {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Tags stored as text, one tag per line
+#[must_use]
+pub fn tags_to_text(tags: &[String]) -> String {
+    tags.join("\n")
+}
+
+pub fn to_tags(text: impl AsRef<str>) -> Vec<String> {
+    text.as_ref()
+        .lines()
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Metadata, tags_to_text, to_tags};
+
+    #[test]
+    fn tags_round_trip() {
+        let tags = vec!["kerst".to_owned(), "kinderlied, oud".to_owned()];
+        assert_eq!(to_tags(tags_to_text(&tags)), tags);
+        assert!(to_tags("").is_empty());
+    }
+
+    #[test]
+    fn empty_metadata_is_not_serialized() {
+        let toml = toml::ser::to_string(&Metadata::default()).unwrap();
+        assert!(toml.is_empty());
+        let metadata: Metadata = toml::de::from_str("key = \"G\"\ntempo = 96").unwrap();
+        assert_eq!(metadata.key.as_deref(), Some("G"));
+        assert_eq!(metadata.tempo, Some(96));
+    }
+}
@@ -0,0 +1,214 @@
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use core::str::FromStr;
+
+use bs58::{decode, encode};
+use serde::{Deserialize, Serialize};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+
+use crate::{Error, HasSummary, Summary, Uuid};
+
+pub const DEFAULT_LIMIT: usize = 50;
+/// Largest number of items on a page, a larger limit is lowered to this
+pub const MAX_LIMIT: usize = 1000;
+const SEPARATOR: char = '\n';
+
+/// Position in a list ordered by title and id. The textual form is opaque to clients.
+#[derive(Clone, Debug, PartialEq, Eq, DeserializeFromStr, SerializeDisplay)]
+pub struct Cursor {
+    pub title: String,
+    pub id: Uuid,
+}
+
+impl From<Summary> for Cursor {
+    fn from(summary: Summary) -> Self {
+        Self {
+            title: summary.title,
+            id: summary.id,
+        }
+    }
+}
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let s = format!("{}{SEPARATOR}{}", self.id, self.title);
+        write!(f, "{}", encode(s.as_bytes()).into_string())
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = decode(s).into_vec()?;
+        let decoded = String::from_utf8(bytes).map_err(|_| Error::Parse(format!("cursor {s}")))?;
+        let (id, title) = decoded
+            .split_once(SEPARATOR)
+            .ok_or_else(|| Error::Parse(format!("cursor {s}")))?;
+        Ok(Self {
+            title: title.to_owned(),
+            id: id.parse()?,
+        })
+    }
+}
+
+impl Cursor {
+    /// Returns true if an item with the given title and id comes after this cursor.
+    #[must_use]
+    pub fn is_before(&self, title: &str, id: &Uuid) -> bool {
+        (title, id) > (self.title.as_str(), &self.id)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageRequest {
+    pub limit: usize,
+    pub cursor: Option<Cursor>,
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_LIMIT,
+            cursor: None,
+        }
+    }
+}
+
+impl PageRequest {
+    #[must_use]
+    pub fn new(limit: usize, cursor: Option<Cursor>) -> Self {
+        Self {
+            limit: limit.clamp(1, MAX_LIMIT),
+            cursor,
+        }
+    }
+
+    /// Number of items to fetch from a backend to find out if there is a next page.
+    #[must_use]
+    pub fn fetch_limit(&self) -> usize {
+        self.limit.saturating_add(1)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next: Option<Cursor>,
+}
+
+impl<T> Page<T>
+where
+    T: HasSummary,
+{
+    /// Creates a page from at most `request.fetch_limit()` items that follow the cursor in title order.
+    #[must_use]
+    pub fn from_fetched(mut items: Vec<T>, request: &PageRequest) -> Self {
+        let next = if items.len() > request.limit {
+            items.truncate(request.limit);
+            items.last().map(|item| item.summary().into())
+        } else {
+            None
+        };
+        Self { items, next }
+    }
+}
+
+impl<T> Page<T> {
+    pub fn map<F, R>(self, f: F) -> Page<R>
+    where
+        F: FnMut(T) -> R,
+    {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next: self.next,
+        }
+    }
+}
+
+/// Paginates a complete list in memory. Used by backends that cannot page natively.
+#[must_use]
+pub fn paginate<T>(list: Vec<T>, request: &PageRequest) -> Page<T>
+where
+    T: HasSummary,
+{
+    let mut list = list
+        .into_iter()
+        .map(|item| (item.summary(), item))
+        .filter(|(summary, _)| {
+            request
+                .cursor
+                .as_ref()
+                .is_none_or(|cursor| cursor.is_before(&summary.title, &summary.id))
+        })
+        .collect::<Vec<_>>();
+    list.sort_by(|(a, _), (b, _)| (&a.title, &a.id).cmp(&(&b.title, &b.id)));
+    Page::from_fetched(
+        list.into_iter()
+            .take(request.fetch_limit())
+            .map(|(_, item)| item)
+            .collect(),
+        request,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cursor, MAX_LIMIT, PageRequest, paginate};
+    use crate::{Summary, Uuid};
+
+    fn summary(title: &str) -> Summary {
+        Summary {
+            id: Uuid::default(),
+            title: title.to_owned(),
+        }
+    }
+
+    #[test]
+    fn cursor_roundtrip() {
+        let cursor = Cursor::from(summary("'k Zag twee beren"));
+        let parsed = cursor.to_string().parse::<Cursor>().unwrap();
+        assert_eq!(parsed, cursor);
+    }
+
+    #[test]
+    fn cursor_invalid() {
+        assert!("0OIl".parse::<Cursor>().is_err());
+        assert!("3yZe7d".parse::<Cursor>().is_err());
+    }
+
+    #[test]
+    fn paginate_all_pages() {
+        let list = ["Sofietje", "Catootje", "Roodkapje", "Faria", "Kortjakje"]
+            .into_iter()
+            .map(summary)
+            .collect::<Vec<_>>();
+
+        let first = paginate(list.clone(), &PageRequest::new(2, None));
+        assert_eq!(first.items[0].title, "Catootje");
+        assert_eq!(first.items[1].title, "Faria");
+
+        let second = paginate(list.clone(), &PageRequest::new(2, first.next));
+        assert_eq!(second.items[0].title, "Kortjakje");
+        assert_eq!(second.items[1].title, "Roodkapje");
+
+        let third = paginate(list, &PageRequest::new(2, second.next));
+        assert_eq!(third.items.len(), 1);
+        assert_eq!(third.items[0].title, "Sofietje");
+        assert!(third.next.is_none());
+    }
+
+    #[test]
+    fn huge_limit() {
+        let request = PageRequest::new(usize::MAX, None);
+        assert_eq!(request.limit, MAX_LIMIT);
+        assert_eq!(request.fetch_limit(), MAX_LIMIT + 1);
+
+        let request = PageRequest {
+            limit: usize::MAX,
+            cursor: None,
+        };
+        let page = paginate(vec![summary("Sofietje")], &request);
+        assert_eq!(page.items.len(), 1);
+        assert!(page.next.is_none());
+    }
+}
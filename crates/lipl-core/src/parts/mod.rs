@@ -1,3 +1,7 @@
+mod structure;
+
+pub use structure::{Part, PartKind, Section, Structure, expand};
+
 pub struct Markdown {
     pub frontmatter: Option<String>,
     pub parts: Vec<Vec<String>>,
@@ -71,7 +75,7 @@ mod test {
         let test = "---\nyaml: is_fine\n---\n\nAllemaal\r\n\nWat fijn  \n\r\n";
         let result = super::parse_markdown(test, "---");
         assert_eq!(result.parts, vec![vec!["Allemaal"], vec!["Wat fijn"]]);
-        assert_eq!(result.frontmatter, Some("yaml: is_fine".to_owned()))
+        assert_eq!(result.frontmatter, Some("yaml: is_fine".to_owned()));
     }
 
     #[test]
@@ -79,6 +83,6 @@ mod test {
         let test = "---\nyaml: is_fine\n---";
         let result = super::parse_markdown(test, "---");
         assert!(result.parts.is_empty());
-        assert_eq!(result.frontmatter, Some("yaml: is_fine".to_owned()))
+        assert_eq!(result.frontmatter, Some("yaml: is_fine".to_owned()));
     }
 }
@@ -0,0 +1,211 @@
+/*!
+Labelled parts and their play order.
+
+Parts are stored as blocks of lines. A block whose first line is a markdown heading, like `# chorus`,
+is labelled. A labelled block without further lines refers to the part with that label, so the chorus
+is written once and repeated where it is sung. Blocks without a heading are verses, which keeps plain
+blank-line-separated parts working as before.
+*/
+
+use serde::{Deserialize, Serialize};
+
+const LABEL_PREFIX: &str = "# ";
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartKind {
+    Verse,
+    PreChorus,
+    Chorus,
+    Bridge,
+    Intro,
+    Outro,
+    Other,
+}
+
+impl PartKind {
+    /// The kind named by the first word of `label`, in english or dutch
+    fn from_label(label: &str) -> Self {
+        match label
+            .split_whitespace()
+            .next()
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            Some("verse" | "couplet") => Self::Verse,
+            Some("pre-chorus" | "prechorus") => Self::PreChorus,
+            Some("chorus" | "refrein") => Self::Chorus,
+            Some("bridge" | "brug") => Self::Bridge,
+            Some("intro") => Self::Intro,
+            Some("outro") => Self::Outro,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Part {
+    pub kind: PartKind,
+    pub label: Option<String>,
+    pub lines: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Section {
+    Part(Part),
+    /// Repeats the part with this label
+    Reference(String),
+}
+
+/// The sections of a lyric in the order they are written
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Structure(pub Vec<Section>);
+
+fn to_label(line: &str) -> Option<String> {
+    line.strip_prefix(LABEL_PREFIX)
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(String::from)
+}
+
+fn label_line(label: &str) -> String {
+    format!("{LABEL_PREFIX}{label}")
+}
+
+impl From<&[Vec<String>]> for Structure {
+    fn from(parts: &[Vec<String>]) -> Self {
+        Self(
+            parts
+                .iter()
+                .map(|part| {
+                    let label = part.first().and_then(|line| to_label(line));
+                    match label {
+                        Some(label) if part.len() == 1 => Section::Reference(label),
+                        Some(label) => Section::Part(Part {
+                            kind: PartKind::from_label(&label),
+                            label: Some(label),
+                            lines: part[1..].to_vec(),
+                        }),
+                        None => Section::Part(Part {
+                            kind: PartKind::Verse,
+                            label: None,
+                            lines: part.clone(),
+                        }),
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Structure {
+    /// The parts as stored, with labels as heading lines
+    #[must_use]
+    pub fn to_parts(&self) -> Vec<Vec<String>> {
+        self.0
+            .iter()
+            .map(|section| match section {
+                Section::Part(part) => part
+                    .label
+                    .as_deref()
+                    .map(label_line)
+                    .into_iter()
+                    .chain(part.lines.iter().cloned())
+                    .collect(),
+                Section::Reference(label) => vec![label_line(label)],
+            })
+            .collect()
+    }
+
+    /// The part labelled `label`, labels are compared ignoring case
+    #[must_use]
+    pub fn find(&self, label: &str) -> Option<&Part> {
+        self.0.iter().find_map(|section| match section {
+            Section::Part(part)
+                if part
+                    .label
+                    .as_deref()
+                    .is_some_and(|l| l.eq_ignore_ascii_case(label)) =>
+            {
+                Some(part)
+            }
+            _ => None,
+        })
+    }
+
+    /// The parts in the order they are sung. References to unknown labels are left out.
+    #[must_use]
+    pub fn play_order(&self) -> Vec<&Part> {
+        self.0
+            .iter()
+            .filter_map(|section| match section {
+                Section::Part(part) => Some(part),
+                Section::Reference(label) => self.find(label),
+            })
+            .collect()
+    }
+}
+
+/// The lines of the parts in play order, without labels
+#[must_use]
+pub fn expand(parts: &[Vec<String>]) -> Vec<Vec<String>> {
+    Structure::from(parts)
+        .play_order()
+        .into_iter()
+        .map(|part| part.lines.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PartKind, Section, Structure, expand};
+    use crate::parts::to_parts;
+
+    const KLOKJES: &str = "# couplet 1\nKlokjes klinken\n\n# refrein\nKling klang\nKling klang\n\nTweede couplet\n\n# Refrein\n";
+
+    #[test]
+    fn labels_and_references() {
+        let parts = to_parts(KLOKJES);
+        let structure = Structure::from(parts.as_slice());
+        assert_eq!(structure.0.len(), 4);
+        let Section::Part(chorus) = &structure.0[1] else {
+            panic!("chorus expected");
+        };
+        assert_eq!(chorus.kind, PartKind::Chorus);
+        assert_eq!(chorus.lines.len(), 2);
+        let Section::Part(verse) = &structure.0[2] else {
+            panic!("verse expected");
+        };
+        assert_eq!(verse.kind, PartKind::Verse);
+        assert_eq!(verse.label, None);
+        assert_eq!(structure.0[3], Section::Reference("Refrein".to_owned()));
+        assert_eq!(structure.to_parts(), parts);
+    }
+
+    #[test]
+    fn play_order_expands_references() {
+        let expanded = expand(&to_parts(KLOKJES));
+        assert_eq!(
+            expanded,
+            vec![
+                vec!["Klokjes klinken"],
+                vec!["Kling klang", "Kling klang"],
+                vec!["Tweede couplet"],
+                vec!["Kling klang", "Kling klang"],
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_parts_are_verses() {
+        let parts = to_parts("Eerste\n\nTweede\n#geen label");
+        assert_eq!(expand(&parts), parts);
+        assert!(
+            Structure::from(parts.as_slice())
+                .play_order()
+                .iter()
+                .all(|part| part.kind == PartKind::Verse)
+        );
+    }
+}
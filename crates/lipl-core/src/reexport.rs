@@ -1,3 +1,4 @@
+pub use chrono;
 pub use serde;
 pub use toml;
 pub use uuid;
@@ -0,0 +1,180 @@
+/*!
+Revision history of lyrics and playlists.
+
+Every write of an item records a new revision. The first tracked write of an item that
+already existed also records the state before the write, so every change can be undone.
+Revisions are kept when an item is deleted, so a deleted item can be restored.
+
+The author of a revision is taken from the task that performs the write, see [`with_author`].
+*/
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, Uuid};
+
+tokio::task_local! {
+    static AUTHOR: Option<String>;
+}
+
+/// Runs `f` with `author` as the author of the revisions created by writes in `f`.
+pub async fn with_author<F>(author: Option<String>, f: F) -> F::Output
+where
+    F: Future,
+{
+    AUTHOR.scope(author, f).await
+}
+
+/// The author of revisions created by the current task.
+#[must_use]
+pub fn author() -> Option<String> {
+    AUTHOR.try_with(Clone::clone).ok().flatten()
+}
+
+#[must_use]
+pub fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RevisionInfo {
+    pub revision: u32,
+    pub timestamp: String,
+    pub author: Option<String>,
+}
+
+impl RevisionInfo {
+    /// Revision with number `revision` created now by the author of the current task.
+    #[must_use]
+    pub fn new(revision: u32) -> Self {
+        Self {
+            revision,
+            timestamp: now(),
+            author: author(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Revision<T> {
+    #[serde(flatten)]
+    pub info: RevisionInfo,
+    pub item: T,
+}
+
+/// Revisions to append to a history of `count` revisions when `item` replaces `previous`.
+#[must_use]
+pub fn to_record<T>(count: usize, previous: Option<T>, item: T) -> Vec<Revision<T>> {
+    let baseline = previous.filter(|_| count == 0);
+    let first = u32::try_from(count).unwrap_or(u32::MAX);
+    baseline
+        .into_iter()
+        .map(|previous| (None, previous))
+        .chain(core::iter::once((author(), item)))
+        .zip(1..)
+        .map(|((author, item), offset)| Revision {
+            info: RevisionInfo {
+                revision: first.saturating_add(offset),
+                timestamp: now(),
+                author,
+            },
+            item,
+        })
+        .collect()
+}
+
+/// # Errors
+///
+/// Returns `Error::RevisionNotFound` if there is no revision with number `revision`.
+pub fn find<T>(
+    revisions: Vec<Revision<T>>,
+    id: impl Into<Uuid>,
+    revision: u32,
+) -> Result<Revision<T>> {
+    revisions
+        .into_iter()
+        .find(|r| r.info.revision == revision)
+        .ok_or(Error::RevisionNotFound(id.into(), revision))
+}
+
+/// Revisions of items with ids of type `I`, kept in memory.
+pub struct History<I, T> {
+    revisions: HashMap<I, Vec<Revision<T>>>,
+}
+
+impl<I, T> Default for History<I, T> {
+    fn default() -> Self {
+        Self {
+            revisions: HashMap::new(),
+        }
+    }
+}
+
+impl<I, T> History<I, T>
+where
+    I: Copy + Eq + Hash + Into<Uuid>,
+    T: Clone,
+{
+    /// Records `item`, that replaces `previous`, as the newest revision of the item with id `id`.
+    pub fn record(&mut self, id: I, previous: Option<T>, item: T) {
+        let revisions = self.revisions.entry(id).or_default();
+        revisions.extend(to_record(revisions.len(), previous, item));
+    }
+
+    #[must_use]
+    pub fn list(&self, id: &I) -> Vec<RevisionInfo> {
+        self.revisions
+            .get(id)
+            .map(|revisions| revisions.iter().map(|r| r.info.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// # Errors
+    ///
+    /// Returns `Error::RevisionNotFound` if there is no revision with number `revision`.
+    pub fn get(&self, id: &I, revision: u32) -> Result<Revision<T>> {
+        find(
+            self.revisions.get(id).cloned().unwrap_or_default(),
+            *id,
+            revision,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{History, author, with_author};
+    use crate::{Error, LyricId};
+
+    #[tokio::test]
+    async fn author_in_scope() {
+        assert_eq!(author(), None);
+        let inner = with_author(Some("paul".to_owned()), async { author() }).await;
+        assert_eq!(inner.as_deref(), Some("paul"));
+    }
+
+    #[tokio::test]
+    async fn history_records_baseline() {
+        let id = LyricId::default();
+        let mut history = History::<LyricId, String>::default();
+        with_author(Some("paul".to_owned()), async {
+            history.record(id, Some("original".to_owned()), "first".to_owned());
+            history.record(id, Some("first".to_owned()), "second".to_owned());
+        })
+        .await;
+
+        let list = history.list(&id);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].author, None);
+        assert_eq!(list[2].revision, 3);
+        assert_eq!(list[2].author.as_deref(), Some("paul"));
+        assert_eq!(history.get(&id, 1).unwrap().item, "original");
+        assert!(matches!(
+            history.get(&id, 4),
+            Err(Error::RevisionNotFound(_, 4))
+        ));
+    }
+}
@@ -0,0 +1,357 @@
+/*!
+Full-text search helpers shared by the backends.
+
+Text is normalized before it is tokenized: diacritics are removed, text is lowercased,
+the Dutch contractions `'t`, `'k` and `'n` are expanded to `het`, `ik` and `een`
+and `ij` is written as `y`. A lyric matches a query if every query token occurs as a word
+in the title or in one of the lines. Chords in lines are ignored.
+*/
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+use crate::{HasSummary, Lyric, LyricId, Summary, chords::strip};
+
+const TITLE_WEIGHT: u16 = 3;
+
+const CONTRACTIONS: [(char, &str); 3] = [('t', "het"), ('k', "ik"), ('n', "een")];
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Hit {
+    pub id: LyricId,
+    pub title: String,
+    pub snippet: Option<String>,
+    pub score: f32,
+}
+
+impl HasSummary for Hit {
+    fn summary(&self) -> Summary {
+        Summary {
+            id: self.id.into(),
+            title: self.title.clone(),
+        }
+    }
+}
+
+fn is_apostrophe(c: char) -> bool {
+    c == '\'' || c == '\u{2019}'
+}
+
+fn expand_contractions(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut result = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let at_word_start = i == 0 || !chars[i - 1].is_alphanumeric();
+        let at_word_end = chars.get(i + 2).is_none_or(|c| !c.is_alphanumeric());
+        let expansion = chars.get(i + 1).and_then(|next| {
+            CONTRACTIONS
+                .iter()
+                .find(|(letter, _)| letter == next)
+                .map(|(_, word)| *word)
+        });
+        match expansion {
+            Some(word) if is_apostrophe(chars[i]) && at_word_start && at_word_end => {
+                result.push_str(word);
+                i += 2;
+            }
+            _ => {
+                result.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Normalizes text for searching. Backends that search natively must apply the same rules.
+#[must_use]
+pub fn normalize(text: &str) -> String {
+    let stripped = text
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase();
+    expand_contractions(&stripped).replace("ij", "y")
+}
+
+/// Splits normalized text into words.
+#[must_use]
+pub fn tokens(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Normalized text of title and lines, to be indexed by backends with native full-text search.
+#[must_use]
+pub fn document_text(title: &str, parts: &[Vec<String>]) -> String {
+    core::iter::once(title.to_owned())
+        .chain(parts.iter().flatten().map(|line| strip(line)))
+        .flat_map(|text| tokens(&text))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn word_set(text: &str) -> BTreeSet<String> {
+    tokens(text).into_iter().collect()
+}
+
+/// The line that contains most of the query tokens.
+#[must_use]
+pub fn snippet(parts: &[Vec<String>], query: &str) -> Option<String> {
+    let query_tokens = word_set(query);
+    parts
+        .iter()
+        .flatten()
+        .map(|line| strip(line))
+        .map(|line| (word_set(&line).intersection(&query_tokens).count(), line))
+        .filter(|(count, _)| *count > 0)
+        .fold(
+            None,
+            |best: Option<(usize, String)>, (count, line)| match best {
+                Some((best_count, _)) if best_count >= count => best,
+                _ => Some((count, line)),
+            },
+        )
+        .map(|(_, line)| line)
+}
+
+fn score(
+    title: &BTreeSet<String>,
+    lines: &[BTreeSet<String>],
+    query: &BTreeSet<String>,
+) -> Option<f32> {
+    let mut total: u16 = 0;
+    for token in query {
+        let in_title = title.contains(token);
+        let line_count = lines.iter().filter(|line| line.contains(token)).count();
+        if !in_title && line_count == 0 {
+            return None;
+        }
+        let line_count = u16::try_from(line_count).unwrap_or(u16::MAX);
+        total = total
+            .saturating_add(if in_title { TITLE_WEIGHT } else { 0 })
+            .saturating_add(line_count);
+    }
+    Some(f32::from(total))
+}
+
+/// Orders hits by descending score, then by title.
+#[must_use]
+pub fn rank(mut hits: Vec<Hit>) -> Vec<Hit> {
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    hits
+}
+
+struct Document {
+    title: String,
+    parts: Vec<Vec<String>>,
+    title_words: BTreeSet<String>,
+    line_words: Vec<BTreeSet<String>>,
+}
+
+impl From<&Lyric> for Document {
+    fn from(lyric: &Lyric) -> Self {
+        Self {
+            title: lyric.title.clone(),
+            parts: lyric.parts.clone(),
+            title_words: word_set(&lyric.title),
+            line_words: lyric
+                .parts
+                .iter()
+                .flatten()
+                .map(|l| word_set(&strip(l)))
+                .collect(),
+        }
+    }
+}
+
+impl Document {
+    fn words(&self) -> impl Iterator<Item = &String> {
+        self.title_words
+            .iter()
+            .chain(self.line_words.iter().flatten())
+    }
+
+    fn hit(&self, id: LyricId, query: &str, query_tokens: &BTreeSet<String>) -> Option<Hit> {
+        score(&self.title_words, &self.line_words, query_tokens).map(|score| Hit {
+            id,
+            title: self.title.clone(),
+            snippet: snippet(&self.parts, query),
+            score,
+        })
+    }
+}
+
+/// Inverted index from normalized words to lyric ids.
+#[derive(Default)]
+pub struct Index {
+    words: HashMap<String, BTreeSet<LyricId>>,
+    documents: HashMap<LyricId, Document>,
+}
+
+impl Index {
+    pub fn insert(&mut self, lyric: &Lyric) {
+        self.remove(&lyric.id);
+        let document = Document::from(lyric);
+        for word in document.words() {
+            self.words.entry(word.clone()).or_default().insert(lyric.id);
+        }
+        self.documents.insert(lyric.id, document);
+    }
+
+    pub fn remove(&mut self, id: &LyricId) {
+        if let Some(document) = self.documents.remove(id) {
+            for word in document.words() {
+                if let Some(ids) = self.words.get_mut(word) {
+                    ids.remove(id);
+                    if ids.is_empty() {
+                        self.words.remove(word);
+                    }
+                }
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn search(&self, query: &str) -> Vec<Hit> {
+        let query_tokens = word_set(query);
+        let candidates = query_tokens
+            .iter()
+            .map(|token| self.words.get(token).cloned().unwrap_or_default())
+            .reduce(|acc, ids| acc.intersection(&ids).copied().collect())
+            .unwrap_or_default();
+        rank(
+            candidates
+                .into_iter()
+                .filter_map(|id| {
+                    self.documents
+                        .get(&id)
+                        .and_then(|document| document.hit(id, query, &query_tokens))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl<'a> FromIterator<&'a Lyric> for Index {
+    fn from_iter<T: IntoIterator<Item = &'a Lyric>>(iter: T) -> Self {
+        let mut index = Index::default();
+        for lyric in iter {
+            index.insert(lyric);
+        }
+        index
+    }
+}
+
+/// Searches by scanning all lyrics. Used by backends without an index.
+#[must_use]
+pub fn scan<'a>(lyrics: impl IntoIterator<Item = &'a Lyric>, query: &str) -> Vec<Hit> {
+    let query_tokens = word_set(query);
+    if query_tokens.is_empty() {
+        return vec![];
+    }
+    rank(
+        lyrics
+            .into_iter()
+            .filter_map(|lyric| Document::from(lyric).hit(lyric.id, query, &query_tokens))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Index, normalize, scan, tokens};
+    use crate::{Lyric, LyricId, Metadata};
+
+    fn lyric(title: &str, lines: &[&str]) -> Lyric {
+        Lyric {
+            id: LyricId::default(),
+            title: title.to_owned(),
+            metadata: Metadata::default(),
+            parts: vec![lines.iter().map(|s| (*s).to_owned()).collect()],
+        }
+    }
+
+    fn lyrics() -> Vec<Lyric> {
+        vec![
+            lyric(
+                "Hertog Jan",
+                &[
+                    "Toen den hertog Jan kwam varen",
+                    "Hoe zong men 't allen kant",
+                ],
+            ),
+            lyric(
+                "Daar in dat kleine café",
+                &[
+                    "Daar in dat kleine café aan de haven",
+                    "Daar zijn de mensen gelijk",
+                ],
+            ),
+            lyric("Roodkapje", &["'k ga naar grootmoeder koekjes brengen"]),
+        ]
+    }
+
+    #[test]
+    fn normalize_dutch() {
+        assert_eq!(normalize("Café"), "cafe");
+        assert_eq!(normalize("'t Antwerpen"), "het antwerpen");
+        assert_eq!(normalize("zo'n"), "zo'n");
+        assert_eq!(normalize("Bij de IJssel"), "by de yssel");
+        assert_eq!(normalize("\u{0133}s"), "ys");
+        assert_eq!(tokens("Zilv'ren veren!"), vec!["zilv", "ren", "veren"]);
+    }
+
+    #[test]
+    fn index_search() {
+        let lyrics = lyrics();
+        let index = lyrics.iter().collect::<Index>();
+
+        let hits = index.search("het allen kant");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "Hertog Jan");
+        assert_eq!(
+            hits[0].snippet.as_deref(),
+            Some("Hoe zong men 't allen kant")
+        );
+
+        let hits = index.search("cafe mensen gelyk");
+        assert_eq!(hits[0].title, "Daar in dat kleine café");
+        assert_eq!(
+            hits[0].snippet.as_deref(),
+            Some("Daar zijn de mensen gelijk")
+        );
+
+        assert_eq!(index.search("ik ga").len(), 1);
+        assert!(index.search("sinterklaas").is_empty());
+    }
+
+    #[test]
+    fn index_remove() {
+        let lyrics = lyrics();
+        let mut index = lyrics.iter().collect::<Index>();
+        index.remove(&lyrics[0].id);
+        assert!(index.search("hertog").is_empty());
+    }
+
+    #[test]
+    fn scan_ranks_title_first() {
+        let lyrics = vec![
+            lyric("Molen", &["Daar bij die molen"]),
+            lyric("Daar bij de waterkant", &["Ik zie de molen"]),
+        ];
+        let hits = scan(&lyrics, "molen");
+        assert_eq!(hits[0].title, "Molen");
+        assert_eq!(hits.len(), 2);
+    }
+}
@@ -1,27 +1,35 @@
-use crate::{Error, Lyric, Playlist, Repo, Summary, Uuid};
-use chrono::SecondsFormat;
+use crate::{
+    Error, Lyric, LyricId, Page, PageRequest, Playlist, PlaylistId, Repo, RepoDb, Revision,
+    RevisionInfo, Summary, change::ChangeStream, search::Hit, with_bs58,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
 use futures_core::Stream;
 use serde::{Deserialize, Serialize};
 use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
     io::{BufRead, BufReader},
+    path::{Path, PathBuf},
     pin::Pin,
+    sync::Arc,
     thread::JoinHandle,
 };
+use tokio::sync::Mutex;
 
 pub type ResultSender<T> = futures_channel::oneshot::Sender<crate::Result<T>>;
 pub type OptionalTransaction = Option<Transaction>;
-type LogRecord = (String, Transaction);
+type Line = (String, Transaction);
 
 pub enum RequestNew {
     LyricSummaries,
     LyricList,
-    LyricItem(Uuid),
-    LyricDelete(Uuid),
+    LyricItem(LyricId),
+    LyricDelete(LyricId),
     LyricPost(Lyric),
     PlaylistSummaries,
     PlaylistList,
-    PlaylistItem(Uuid),
-    PlaylistDelete(Uuid),
+    PlaylistItem(PlaylistId),
+    PlaylistDelete(PlaylistId),
     PlaylistPost(Playlist),
 }
 
@@ -32,42 +40,125 @@ pub enum Request {
     LyricSummaries(ResultSender<Vec<Summary>>),
     LyricList(ResultSender<Vec<Lyric>>),
     LyricListStream(ResultSender<BoxedStream<Lyric>>),
-    LyricItem(Uuid, ResultSender<Lyric>),
-    LyricDelete(Uuid, ResultSender<()>),
+    LyricItem(LyricId, ResultSender<Lyric>),
+    /// Answered with the playlists that the lyric is removed from
+    LyricDelete(LyricId, ResultSender<Vec<Playlist>>),
     LyricPost(Lyric, ResultSender<Lyric>),
+    LyricDeleteIfMatch(LyricId, String, ResultSender<Vec<Playlist>>),
+    LyricPostIfMatch(Lyric, String, ResultSender<Lyric>),
+    LyricSearch(String, ResultSender<Vec<Hit>>),
+    LyricRevisions(LyricId, ResultSender<Vec<RevisionInfo>>),
+    LyricRevision(LyricId, u32, ResultSender<Revision<Lyric>>),
     PlaylistSummaries(ResultSender<Vec<Summary>>),
     PlaylistList(ResultSender<Vec<Playlist>>),
     PlaylistListStream(ResultSender<BoxedStream<Playlist>>),
-    PlaylistItem(Uuid, ResultSender<Playlist>),
-    PlaylistDelete(Uuid, ResultSender<()>),
+    PlaylistItem(PlaylistId, ResultSender<Playlist>),
+    PlaylistDelete(PlaylistId, ResultSender<()>),
     PlaylistPost(Playlist, ResultSender<Playlist>),
+    PlaylistDeleteIfMatch(PlaylistId, String, ResultSender<()>),
+    PlaylistPostIfMatch(Playlist, String, ResultSender<Playlist>),
+    PlaylistRevisions(PlaylistId, ResultSender<Vec<RevisionInfo>>),
+    PlaylistRevision(PlaylistId, u32, ResultSender<Revision<Playlist>>),
     Stop(ResultSender<()>),
 }
 
 #[derive(Deserialize, Serialize)]
 pub enum Transaction {
-    LyricDelete(Uuid),
+    LyricDelete(LyricId),
     LyricUpsert(Lyric),
-    PlaylistDelete(Uuid),
+    PlaylistDelete(PlaylistId),
     PlaylistUpsert(Playlist),
 }
 
 impl std::fmt::Display for Transaction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", serde_json::to_string(&(now(), self)).unwrap())
+        let json = with_bs58(|| serde_json::to_string(&(now(), self)).unwrap());
+        write!(f, "{json}")
     }
 }
 
 impl std::str::FromStr for Transaction {
     type Err = crate::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        serde_json::from_str::<LogRecord>(s)
-            .map(|(_, transaction)| transaction)
+        s.parse::<Record>().map(|record| record.transaction)
+    }
+}
+
+/// Prefix of a record in the versioned format
+const VERSION: &str = "v1";
+
+/// A line of the log: a transaction and the moment it was written.
+///
+/// A record with a sequence number is written as `v1 <sequence> <checksum> <json>`, the checksum is the
+/// 64 bit FNV-1a hash of the json in hex. A record without one is only the json, the format of older logs.
+pub struct Record {
+    pub at: DateTime<Utc>,
+    pub sequence: Option<u64>,
+    pub transaction: Transaction,
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let at = self.at.to_rfc3339_opts(SecondsFormat::Micros, true);
+        let json = with_bs58(|| serde_json::to_string(&(at, &self.transaction)).unwrap());
+        match self.sequence {
+            Some(sequence) => write!(f, "{VERSION} {sequence} {:016x} {json}", checksum(&json)),
+            None => write!(f, "{json}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Record {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sequence, json) = match s.strip_prefix(VERSION).and_then(|s| s.strip_prefix(' ')) {
+            Some(versioned) => {
+                let mut fields = versioned.splitn(3, ' ');
+                let sequence = fields.next().and_then(|field| field.parse::<u64>().ok());
+                let sum = fields
+                    .next()
+                    .and_then(|field| u64::from_str_radix(field, 16).ok());
+                match (sequence, sum, fields.next()) {
+                    (Some(sequence), Some(sum), Some(json)) if sum == checksum(json) => {
+                        (Some(sequence), json)
+                    }
+                    (Some(_), Some(_), Some(_)) => {
+                        return Err(Error::Parse("checksum does not match".to_owned()));
+                    }
+                    _ => return Err(Error::Parse("incomplete record".to_owned())),
+                }
+            }
+            None => (None, s),
+        };
+        let (at, transaction) = serde_json::from_str::<Line>(json)
             .map_err(Box::new)
-            .map_err(to_json_error)
+            .map_err(to_json_error)?;
+        Ok(Self {
+            at: parse_timestamp(&at)?,
+            sequence,
+            transaction,
+        })
     }
 }
 
+/// 64 bit FNV-1a hash
+fn checksum(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Parses an RFC 3339 timestamp, like `2025-12-21T10:00:00Z` or `2025-12-21T11:00:00+01:00`
+///
+/// # Errors
+///
+/// Returns an error if `s` is not an RFC 3339 timestamp.
+pub fn parse_timestamp(s: &str) -> crate::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s.trim())
+        .map(|at| at.with_timezone(&Utc))
+        .map_err(|error| Error::Parse(format!("timestamp {s}: {error}")))
+}
+
 impl From<&Request> for OptionalTransaction {
     fn from(request: &Request) -> Self {
         match request {
@@ -102,43 +193,291 @@ where
     Ok(())
 }
 
-fn line_to_transaction(line: std::io::Result<String>) -> crate::Result<Transaction> {
-    line.map_err(crate::Error::from)
-        .and_then(|s| s.parse::<Transaction>())
+/// What a replay did
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Transactions applied
+    pub replayed: usize,
+    /// Transactions left out, because they were written after the moment asked for or are in the snapshot
+    pub skipped: usize,
+    /// Sequence number of the last record, none if the log has no versioned records
+    pub last_sequence: Option<u64>,
+    /// Line number of the last line if it was cut off by a crash, it is left out
+    pub torn_tail: Option<usize>,
+}
+
+/// A line that could not be read
+struct Torn {
+    line: usize,
+    /// Position of the line in the log
+    offset: u64,
+}
+
+/// The records of a log, the last line may be torn
+struct LogContents {
+    records: Vec<Record>,
+    torn: Option<Torn>,
+    /// The last line ends with a newline, new records can be appended
+    complete: bool,
+}
+
+impl LogContents {
+    fn last_sequence(&self) -> Option<u64> {
+        self.records.iter().rev().find_map(|record| record.sequence)
+    }
+}
+
+/// Reads the records of a log. Only the last line may be unreadable, that is a write cut off by a crash.
+/// An unreadable line in the middle or a sequence number that does not follow the one before is an error.
+fn read_log<R>(r: R) -> crate::Result<LogContents>
+where
+    R: std::io::Read,
+{
+    let mut reader = BufReader::new(r);
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut offset = 0;
+    let mut complete = true;
+    loop {
+        line.clear();
+        let length = reader.read_line(&mut line)?;
+        if length == 0 {
+            break;
+        }
+        complete = line.ends_with('\n');
+        if !line.trim().is_empty() {
+            lines.push((
+                lines.len() + 1,
+                offset,
+                complete,
+                line.trim().parse::<Record>(),
+            ));
+        }
+        offset += length as u64;
+    }
+
+    let last = lines.len();
+    let mut records = LogContents {
+        records: vec![],
+        torn: None,
+        complete,
+    };
+    for (index, (number, offset, ended, record)) in lines.into_iter().enumerate() {
+        match record {
+            Ok(record) => {
+                if let (Some(previous), Some(sequence)) = (records.last_sequence(), record.sequence)
+                    && sequence != previous + 1
+                {
+                    return Err(Error::CorruptLog(
+                        number,
+                        format!("sequence number {sequence} follows {previous}"),
+                    ));
+                }
+                records.records.push(record);
+            }
+            // Only a write that was cut off leaves a last line without a newline
+            Err(_) if index + 1 == last && !ended => {
+                records.torn = Some(Torn {
+                    line: number,
+                    offset,
+                });
+            }
+            Err(error) => return Err(Error::CorruptLog(number, error.to_string())),
+        }
+    }
+    Ok(records)
+}
+
+/// Replays a log into `db`. A last line that was cut off by a crash is left out and reported.
+/// Deleting an item that is gone is skipped, so a log can be replayed over a snapshot that already has some of its changes.
+///
+/// # Errors
+///
+/// Returns [`Error::CorruptLog`] if a line in the middle cannot be read or a sequence number is missing,
+/// or an error if a transaction fails.
+pub async fn build_from_log<R, DB>(r: R, db: DB) -> crate::Result<ReplayReport>
+where
+    R: std::io::Read,
+    DB: Repo,
+{
+    replay(read_log(r)?, db, None, 0).await
 }
 
+/// Replays the transactions that were written at or before `until`, leaving `db` as the library was then
+///
 /// # Errors
 ///
-/// Returns an error if the log could not be parsed into transactions.
-pub async fn build_from_log<R, DB>(r: R, db: DB) -> crate::Result<()>
+/// Returns [`Error::CorruptLog`] if a line in the middle cannot be read or a sequence number is missing,
+/// or an error if a transaction fails.
+pub async fn build_from_log_until<R, DB>(
+    r: R,
+    db: DB,
+    until: DateTime<Utc>,
+) -> crate::Result<ReplayReport>
 where
     R: std::io::Read,
     DB: Repo,
 {
-    let transactions = BufReader::new(r)
-        .lines()
-        .map(line_to_transaction)
-        .collect::<crate::Result<Vec<_>>>()?;
+    replay(read_log(r)?, db, Some(until), 0).await
+}
 
-    for transaction in transactions {
-        match transaction {
+/// Applies the records written at or before `until` with a sequence number after `after`
+async fn replay<DB>(
+    records: LogContents,
+    db: DB,
+    until: Option<DateTime<Utc>>,
+    after: u64,
+) -> crate::Result<ReplayReport>
+where
+    DB: Repo,
+{
+    let mut report = ReplayReport {
+        last_sequence: records.last_sequence(),
+        torn_tail: records.torn.map(|torn| torn.line),
+        ..ReplayReport::default()
+    };
+    for record in records.records {
+        if until.is_some_and(|until| record.at > until)
+            || record.sequence.is_some_and(|sequence| sequence <= after)
+        {
+            report.skipped += 1;
+            continue;
+        }
+        report.replayed += 1;
+        match record.transaction {
             Transaction::LyricDelete(id) => {
-                db.delete_lyric(id).await?;
+                db.delete_lyric(id).await.or_else(gone)?;
             }
             Transaction::LyricUpsert(lyric) => {
                 db.upsert_lyric(lyric).await?;
             }
             Transaction::PlaylistDelete(id) => {
-                db.delete_playlist(id).await?;
+                db.delete_playlist(id).await.or_else(gone)?;
             }
             Transaction::PlaylistUpsert(playlist) => {
                 db.upsert_playlist(playlist).await?;
             }
         }
     }
+    Ok(report)
+}
+
+fn gone(error: Error) -> crate::Result<()> {
+    match error {
+        Error::NotFound(_) => Ok(()),
+        error => Err(error),
+    }
+}
+
+/// Path of the snapshot that belongs to the log at `log`, the path with `.snapshot` added
+#[must_use]
+pub fn snapshot_path(log: &Path) -> PathBuf {
+    let mut path = OsString::from(log.as_os_str());
+    path.push(".snapshot");
+    PathBuf::from(path)
+}
+
+/// The library at the moment the snapshot was taken
+#[derive(Deserialize, Serialize)]
+struct Snapshot {
+    taken: String,
+    /// Sequence number of the last record in the snapshot
+    #[serde(default)]
+    sequence: u64,
+    #[serde(flatten)]
+    db: RepoDb,
+}
+
+fn read_snapshot(path: &Path) -> crate::Result<Option<Snapshot>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    serde_json::from_reader::<_, Snapshot>(BufReader::new(File::open(path)?))
+        .map(Some)
+        .map_err(Box::new)
+        .map_err(to_json_error)
+}
+
+/// Writes the lyrics and playlists of `repo` as json to `path`. An earlier snapshot is replaced at once.
+///
+/// # Errors
+///
+/// Returns an error if the repo could not be read or the snapshot could not be written.
+pub async fn snapshot<R: Repo>(repo: &R, path: &Path) -> crate::Result<()> {
+    write_snapshot(repo, path, 0).await
+}
+
+async fn write_snapshot<R: Repo>(repo: &R, path: &Path, sequence: u64) -> crate::Result<()> {
+    let taken = now();
+    let db = RepoDb {
+        lyrics: repo.get_lyrics().await?,
+        playlists: repo.get_playlists().await?,
+    };
+    let json = with_bs58(|| {
+        serde_json::to_string(&Snapshot {
+            taken,
+            sequence,
+            db,
+        })
+    })
+    .map_err(Box::new)
+    .map_err(to_json_error)?;
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, json)?;
+    std::fs::rename(temporary, path)?;
     Ok(())
 }
 
+/// Loads the snapshot of `log` into `repo`, if there is one, and replays the log behind it.
+/// Records that are in the snapshot already are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the snapshot or the log could not be read or replayed.
+pub async fn recover<R: Repo>(log: &Path, repo: R) -> crate::Result<ReplayReport> {
+    recover_at(log, repo, None).await
+}
+
+/// Loads the snapshot of `log` and replays the transactions written at or before `until`
+///
+/// # Errors
+///
+/// Returns an error if the snapshot was taken after `until`, the changes before it are gone,
+/// or if the snapshot or the log could not be read or replayed.
+pub async fn recover_until<R: Repo>(
+    log: &Path,
+    repo: R,
+    until: DateTime<Utc>,
+) -> crate::Result<ReplayReport> {
+    recover_at(log, repo, Some(until)).await
+}
+
+async fn recover_at<R: Repo>(
+    log: &Path,
+    repo: R,
+    until: Option<DateTime<Utc>>,
+) -> crate::Result<ReplayReport> {
+    let mut after = 0;
+    if let Some(snapshot) = read_snapshot(&snapshot_path(log))? {
+        after = snapshot.sequence;
+        let taken = parse_timestamp(&snapshot.taken)?;
+        if until.is_some_and(|until| until < taken) {
+            return Err(Error::Argument("the log is compacted after this moment"));
+        }
+        for lyric in snapshot.db.lyrics {
+            repo.upsert_lyric(lyric).await?;
+        }
+        for playlist in snapshot.db.playlists {
+            repo.upsert_playlist(playlist).await?;
+        }
+    }
+    if log.exists() {
+        replay(read_log(File::open(log)?)?, repo, until, after).await
+    } else {
+        Ok(ReplayReport::default())
+    }
+}
+
 pub fn log_to_transaction<W>(mut writer: W) -> impl FnMut(Transaction) -> crate::Result<()>
 where
     W: std::io::Write,
@@ -165,3 +504,388 @@ where
     });
     (join_handle, log_tx)
 }
+
+/// Appends versioned records with the next sequence number
+struct Log {
+    writer: Box<dyn std::io::Write + Send>,
+    /// Sequence number of the last record written
+    sequence: u64,
+    /// Why a change that was applied could not be appended
+    failed: Option<String>,
+}
+
+impl Log {
+    fn new(writer: Box<dyn std::io::Write + Send>, sequence: u64) -> Self {
+        Self {
+            writer,
+            sequence,
+            failed: None,
+        }
+    }
+
+    fn append(&mut self, transaction: Transaction) -> crate::Result<()> {
+        let record = Record {
+            at: Utc::now(),
+            sequence: Some(self.sequence + 1),
+            transaction,
+        };
+        write(&mut self.writer, record)?;
+        self.sequence += 1;
+        Ok(())
+    }
+}
+
+/// Repo that appends every change that succeeds to a log, a json line per transaction.
+/// Replaying the log with [`build_from_log`] on an empty repo restores the items.
+/// A log in a file can be compacted, [`recover`] then loads the snapshot and replays the rest of the log.
+#[derive(Clone)]
+pub struct LoggedRepo<R> {
+    inner: R,
+    /// Held by every change from the write until the append, so the log has the order in which changes were applied
+    log: Arc<Mutex<Log>>,
+    path: Option<PathBuf>,
+}
+
+impl<R> LoggedRepo<R> {
+    /// Logs to `log`, the first record gets sequence number 1
+    pub fn new<W>(inner: R, log: W) -> Self
+    where
+        W: std::io::Write + Send + 'static,
+    {
+        Self {
+            inner,
+            log: Arc::new(Mutex::new(Log::new(Box::new(log), 0))),
+            path: None,
+        }
+    }
+
+    /// Logs to the file at `path`, which is created if it does not exist.
+    /// Sequence numbers continue after the log and its snapshot, a last line cut off by a crash is removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be opened or is corrupt in the middle.
+    pub fn open(inner: R, path: impl Into<PathBuf>) -> crate::Result<Self> {
+        let path = path.into();
+        let mut sequence =
+            read_snapshot(&snapshot_path(&path))?.map_or(0, |snapshot| snapshot.sequence);
+        if path.exists() {
+            let records = read_log(File::open(&path)?)?;
+            sequence = sequence.max(records.last_sequence().unwrap_or_default());
+            if let Some(torn) = records.torn {
+                OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(torn.offset)?;
+            } else if !records.complete {
+                write(&mut OpenOptions::new().append(true).open(&path)?, "")?;
+            }
+        }
+        let writer = OpenOptions::new().append(true).create(true).open(&path)?;
+        Ok(Self {
+            inner,
+            log: Arc::new(Mutex::new(Log::new(Box::new(writer), sequence))),
+            path: Some(path),
+        })
+    }
+
+    /// Path of the log, none if it is not a file
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Applies `change` and appends the transaction for its result to the log.
+    /// If the append fails the change is applied all the same, so it succeeds,
+    /// but later changes are refused until compaction writes a snapshot that includes it.
+    async fn change<T, F>(
+        &self,
+        change: F,
+        transaction: impl FnOnce(&T) -> Transaction,
+    ) -> crate::Result<T>
+    where
+        F: Future<Output = crate::Result<T>>,
+    {
+        let mut log = self.log.lock().await;
+        if let Some(failed) = &log.failed {
+            return Err(Error::LogFailed(failed.clone()));
+        }
+        let value = change.await?;
+        if let Err(error) = log.append(transaction(&value)) {
+            log.failed = Some(error.to_string());
+        }
+        Ok(value)
+    }
+}
+
+impl<R: Repo + Sync> LoggedRepo<R> {
+    /// Writes a snapshot of the repo next to the log file and empties the log.
+    /// Changes wait until the compaction is done, so no change is missing from both.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log is not a file or the snapshot could not be written.
+    pub async fn compact(&self) -> crate::Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or(Error::Argument("only a log in a file can be compacted"))?;
+        let mut log = self.log.lock().await;
+        write_snapshot(&self.inner, &snapshot_path(path), log.sequence).await?;
+        OpenOptions::new().write(true).open(path)?.set_len(0)?;
+        log.failed = None;
+        Ok(())
+    }
+}
+
+impl<R: Repo + Sync> Repo for LoggedRepo<R> {
+    async fn get_lyrics(&self) -> crate::Result<Vec<Lyric>> {
+        self.inner.get_lyrics().await
+    }
+
+    async fn get_lyric_summaries(&self) -> crate::Result<Vec<Summary>> {
+        self.inner.get_lyric_summaries().await
+    }
+
+    async fn get_lyrics_page(&self, page: PageRequest) -> crate::Result<Page<Lyric>> {
+        self.inner.get_lyrics_page(page).await
+    }
+
+    async fn get_lyric_summaries_page(&self, page: PageRequest) -> crate::Result<Page<Summary>> {
+        self.inner.get_lyric_summaries_page(page).await
+    }
+
+    async fn get_lyric(&self, id: LyricId) -> crate::Result<Lyric> {
+        self.inner.get_lyric(id).await
+    }
+
+    async fn upsert_lyric(&self, lyric: Lyric) -> crate::Result<Lyric> {
+        self.change(self.inner.upsert_lyric(lyric), |lyric| {
+            Transaction::LyricUpsert(lyric.clone())
+        })
+        .await
+    }
+
+    async fn delete_lyric(&self, id: LyricId) -> crate::Result<()> {
+        self.change(self.inner.delete_lyric(id), |()| {
+            Transaction::LyricDelete(id)
+        })
+        .await
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, if_match: String) -> crate::Result<Lyric> {
+        self.change(self.inner.upsert_lyric_if_match(lyric, if_match), |lyric| {
+            Transaction::LyricUpsert(lyric.clone())
+        })
+        .await
+    }
+
+    async fn delete_lyric_if_match(&self, id: LyricId, if_match: String) -> crate::Result<()> {
+        self.change(self.inner.delete_lyric_if_match(id, if_match), |()| {
+            Transaction::LyricDelete(id)
+        })
+        .await
+    }
+
+    async fn get_lyric_revisions(&self, id: LyricId) -> crate::Result<Vec<RevisionInfo>> {
+        self.inner.get_lyric_revisions(id).await
+    }
+
+    async fn get_lyric_revision(
+        &self,
+        id: LyricId,
+        revision: u32,
+    ) -> crate::Result<Revision<Lyric>> {
+        self.inner.get_lyric_revision(id, revision).await
+    }
+
+    async fn restore_lyric_revision(&self, id: LyricId, revision: u32) -> crate::Result<Lyric> {
+        self.change(self.inner.restore_lyric_revision(id, revision), |lyric| {
+            Transaction::LyricUpsert(lyric.clone())
+        })
+        .await
+    }
+
+    async fn search(&self, query: &str) -> crate::Result<Vec<Hit>> {
+        self.inner.search(query).await
+    }
+
+    async fn get_playlists(&self) -> crate::Result<Vec<Playlist>> {
+        self.inner.get_playlists().await
+    }
+
+    async fn get_playlist_summaries(&self) -> crate::Result<Vec<Summary>> {
+        self.inner.get_playlist_summaries().await
+    }
+
+    async fn get_playlists_page(&self, page: PageRequest) -> crate::Result<Page<Playlist>> {
+        self.inner.get_playlists_page(page).await
+    }
+
+    async fn get_playlist_summaries_page(&self, page: PageRequest) -> crate::Result<Page<Summary>> {
+        self.inner.get_playlist_summaries_page(page).await
+    }
+
+    async fn get_playlist(&self, id: PlaylistId) -> crate::Result<Playlist> {
+        self.inner.get_playlist(id).await
+    }
+
+    async fn upsert_playlist(&self, playlist: Playlist) -> crate::Result<Playlist> {
+        self.change(self.inner.upsert_playlist(playlist), |playlist| {
+            Transaction::PlaylistUpsert(playlist.clone())
+        })
+        .await
+    }
+
+    async fn delete_playlist(&self, id: PlaylistId) -> crate::Result<()> {
+        self.change(self.inner.delete_playlist(id), |()| {
+            Transaction::PlaylistDelete(id)
+        })
+        .await
+    }
+
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        if_match: String,
+    ) -> crate::Result<Playlist> {
+        self.change(
+            self.inner.upsert_playlist_if_match(playlist, if_match),
+            |playlist| Transaction::PlaylistUpsert(playlist.clone()),
+        )
+        .await
+    }
+
+    async fn delete_playlist_if_match(
+        &self,
+        id: PlaylistId,
+        if_match: String,
+    ) -> crate::Result<()> {
+        self.change(self.inner.delete_playlist_if_match(id, if_match), |()| {
+            Transaction::PlaylistDelete(id)
+        })
+        .await
+    }
+
+    async fn get_playlist_revisions(&self, id: PlaylistId) -> crate::Result<Vec<RevisionInfo>> {
+        self.inner.get_playlist_revisions(id).await
+    }
+
+    async fn get_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> crate::Result<Revision<Playlist>> {
+        self.inner.get_playlist_revision(id, revision).await
+    }
+
+    async fn restore_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> crate::Result<Playlist> {
+        self.change(
+            self.inner.restore_playlist_revision(id, revision),
+            |playlist| Transaction::PlaylistUpsert(playlist.clone()),
+        )
+        .await
+    }
+
+    async fn subscribe(&self) -> crate::Result<ChangeStream> {
+        self.inner.subscribe().await
+    }
+
+    async fn stop(&self) -> crate::Result<()> {
+        self.inner.stop().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogContents, Record, Transaction, read_log};
+    use crate::{Error, Lyric, LyricPost};
+    use chrono::Utc;
+
+    fn record(sequence: Option<u64>) -> Record {
+        Record {
+            at: Utc::now(),
+            sequence,
+            transaction: Transaction::LyricUpsert(Lyric::from((
+                None,
+                LyricPost::from(("Roodkapje", "Zeg roodkapje")),
+            ))),
+        }
+    }
+
+    fn log(records: &[Record]) -> String {
+        records
+            .iter()
+            .map(|record| record.to_string() + "\n")
+            .collect()
+    }
+
+    fn read(log: &str) -> crate::Result<LogContents> {
+        read_log(log.as_bytes())
+    }
+
+    #[test]
+    fn versioned_record() {
+        let line = record(Some(7)).to_string();
+        assert!(line.starts_with("v1 7 "));
+        let parsed = line.parse::<Record>().unwrap();
+        assert_eq!(parsed.sequence, Some(7));
+
+        let changed = line.replace("Roodkapje", "Roodkapj3");
+        assert!(changed.parse::<Record>().is_err());
+    }
+
+    #[test]
+    fn legacy_record() {
+        let line = record(None).to_string();
+        assert!(line.starts_with('['));
+        let parsed = line.parse::<Record>().unwrap();
+        assert_eq!(parsed.sequence, None);
+    }
+
+    #[test]
+    fn legacy_then_versioned() {
+        let contents = read(&log(&[record(None), record(Some(1)), record(Some(2))])).unwrap();
+        assert_eq!(contents.records.len(), 3);
+        assert_eq!(contents.last_sequence(), Some(2));
+        assert!(contents.torn.is_none());
+    }
+
+    #[test]
+    fn torn_tail() {
+        let mut text = log(&[record(Some(1)), record(Some(2))]);
+        let whole = text.len();
+        let third = record(Some(3)).to_string();
+        text.push_str(&third[..third.len() / 2]);
+
+        let contents = read(&text).unwrap();
+        assert_eq!(contents.records.len(), 2);
+        let torn = contents.torn.unwrap();
+        assert_eq!(torn.line, 3);
+        assert_eq!(torn.offset, whole as u64);
+        assert!(!contents.complete);
+    }
+
+    #[test]
+    fn corrupt_last_line() {
+        let last = log(&[record(Some(2))]).replace("Roodkapje", "Roodkapj3");
+        let text = log(&[record(Some(1))]) + &last;
+        assert!(matches!(read(&text), Err(Error::CorruptLog(2, _))));
+    }
+
+    #[test]
+    fn corrupt_middle() {
+        let second = log(&[record(Some(2))]).replace("Roodkapje", "Roodkapj3");
+        let text = log(&[record(Some(1))]) + &second + &log(&[record(Some(3))]);
+        assert!(matches!(read(&text), Err(Error::CorruptLog(2, _))));
+    }
+
+    #[test]
+    fn gap() {
+        let text = log(&[record(Some(1)), record(Some(3))]);
+        assert!(matches!(read(&text), Err(Error::CorruptLog(2, _))));
+    }
+}
@@ -0,0 +1,318 @@
+/*!
+Transposing the inline chords of a lyric.
+
+A [`Transposition`] shifts chords by a number of semitones. With a capo the chords are shifted down
+by the fret of the capo, so they are the shapes played, while the key of the lyric is the key that sounds.
+Accidentals follow the preference of the transposition, or the spelling of the chord if there is none.
+Chords that cannot be parsed, like `N.C.`, are left as they are.
+*/
+
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chords::{Segment, segments, to_line};
+use crate::{Error, Lyric, LyricId};
+
+const SHARPS: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+const FLATS: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+];
+
+/// Transpositions of playlist members, by lyric id
+pub type Transpositions = BTreeMap<LyricId, Transposition>;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Accidentals {
+    Sharp,
+    Flat,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Transposition {
+    pub semitones: i8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accidentals: Option<Accidentals>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub capo: u8,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_zero(capo: &u8) -> bool {
+    *capo == 0
+}
+
+/// Written as `+2`, `-3 flat` or `+2 sharp capo 2`
+impl Display for Transposition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:+}", self.semitones)?;
+        match self.accidentals {
+            Some(Accidentals::Sharp) => write!(f, " sharp")?,
+            Some(Accidentals::Flat) => write!(f, " flat")?,
+            None => {}
+        }
+        if self.capo > 0 {
+            write!(f, " capo {}", self.capo)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Transposition {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || Error::Parse(format!("transposition {s}"));
+        let mut words = s.split_whitespace();
+        let mut transposition = Transposition {
+            semitones: words
+                .next()
+                .map(|semitones| semitones.trim_start_matches('+'))
+                .and_then(|semitones| semitones.parse().ok())
+                .ok_or_else(error)?,
+            ..Transposition::default()
+        };
+        while let Some(word) = words.next() {
+            match word {
+                "sharp" => transposition.accidentals = Some(Accidentals::Sharp),
+                "flat" => transposition.accidentals = Some(Accidentals::Flat),
+                "capo" => {
+                    transposition.capo = words
+                        .next()
+                        .and_then(|capo| capo.parse().ok())
+                        .ok_or_else(error)?;
+                }
+                _ => return Err(error()),
+            }
+        }
+        Ok(transposition)
+    }
+}
+
+impl Transposition {
+    #[must_use]
+    pub fn new(semitones: i8) -> Self {
+        Self {
+            semitones,
+            ..Self::default()
+        }
+    }
+
+    /// The shift of the chords, which is the shift of the key less the capo
+    fn chord_shift(self) -> i32 {
+        i32::from(self.semitones) - i32::from(self.capo)
+    }
+}
+
+/// Pitch class of the note at the start of `text` and the length of its name
+fn note(text: &str) -> Option<(i32, usize)> {
+    let mut chars = text.chars();
+    let natural = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    match chars.next() {
+        Some(c @ ('#' | '♯')) => Some((natural + 1, 1 + c.len_utf8())),
+        Some(c @ ('b' | '♭')) => Some((natural - 1, 1 + c.len_utf8())),
+        _ => Some((natural, 1)),
+    }
+}
+
+fn spell(pitch: i32, shift: i32, accidentals: Accidentals) -> &'static str {
+    let index = usize::try_from((pitch + shift).rem_euclid(12)).unwrap_or_default();
+    match accidentals {
+        Accidentals::Sharp => SHARPS[index],
+        Accidentals::Flat => FLATS[index],
+    }
+}
+
+/// Transposes a chord like `F#m7/C#`
+#[must_use]
+pub fn transpose_chord(chord: &str, transposition: Transposition) -> String {
+    transpose_chord_by(
+        chord,
+        transposition.chord_shift(),
+        transposition.accidentals,
+    )
+}
+
+fn transpose_chord_by(chord: &str, shift: i32, accidentals: Option<Accidentals>) -> String {
+    let Some((root, length)) = note(chord) else {
+        return chord.to_owned();
+    };
+    let accidentals = accidentals.unwrap_or(if chord[..length].ends_with(['b', '♭']) {
+        Accidentals::Flat
+    } else {
+        Accidentals::Sharp
+    });
+    let rest = &chord[length..];
+    let (quality, bass) = match rest.rsplit_once('/') {
+        Some((quality, bass)) => match note(bass) {
+            Some((pitch, length)) if length == bass.len() => (quality, Some(pitch)),
+            _ => (rest, None),
+        },
+        None => (rest, None),
+    };
+    let mut result = format!("{}{quality}", spell(root, shift, accidentals));
+    if let Some(bass) = bass {
+        result.push('/');
+        result.push_str(spell(bass, shift, accidentals));
+    }
+    result
+}
+
+/// Transposes the inline chords of `line`
+#[must_use]
+pub fn transpose_line(line: &str, transposition: Transposition) -> String {
+    to_line(
+        &segments(line)
+            .into_iter()
+            .map(|segment| Segment {
+                chord: segment
+                    .chord
+                    .map(|chord| transpose_chord(&chord, transposition)),
+                text: segment.text,
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Transposes the chords and the key of `lyric`
+#[must_use]
+pub fn transpose_lyric(mut lyric: Lyric, transposition: Transposition) -> Lyric {
+    lyric.parts = lyric
+        .parts
+        .iter()
+        .map(|part| {
+            part.iter()
+                .map(|line| transpose_line(line, transposition))
+                .collect()
+        })
+        .collect();
+    lyric.metadata.key = lyric.metadata.key.map(|key| {
+        transpose_chord_by(
+            &key,
+            i32::from(transposition.semitones),
+            transposition.accidentals,
+        )
+    });
+    lyric
+}
+
+/// Transpositions stored as text, one member per line
+#[must_use]
+pub fn transpositions_to_text(transpositions: &Transpositions) -> String {
+    transpositions
+        .iter()
+        .map(|(id, transposition)| format!("{id} {transposition}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Transpositions from text, leaving out those of lyrics that are no longer a member
+///
+/// # Errors
+///
+/// Returns [`Error::Parse`] if a line is not an id followed by a transposition
+pub fn to_transpositions(
+    text: impl AsRef<str>,
+    members: &[LyricId],
+) -> Result<Transpositions, Error> {
+    text.as_ref()
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (id, transposition) = line
+                .trim()
+                .split_once(' ')
+                .ok_or_else(|| Error::Parse(format!("transposition {line}")))?;
+            Ok((
+                id.parse::<LyricId>()?,
+                transposition.parse::<Transposition>()?,
+            ))
+        })
+        .filter(|result| result.as_ref().map_or(true, |(id, _)| members.contains(id)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Accidentals, Transposition, to_transpositions, transpose_chord, transpose_line,
+        transpositions_to_text,
+    };
+    use crate::LyricId;
+
+    #[test]
+    fn chords() {
+        let up = Transposition::new(2);
+        assert_eq!(transpose_chord("G", up), "A");
+        assert_eq!(transpose_chord("Am7", up), "Bm7");
+        assert_eq!(transpose_chord("F#m/C#", up), "G#m/D#");
+        assert_eq!(transpose_chord("Bb", up), "C");
+        assert_eq!(transpose_chord("Eb", up), "F");
+        assert_eq!(transpose_chord("Ab/Eb", Transposition::new(-1)), "G/D");
+        assert_eq!(transpose_chord("B", Transposition::new(1)), "C");
+        assert_eq!(transpose_chord("C", Transposition::new(-13)), "B");
+        assert_eq!(transpose_chord("N.C.", up), "N.C.");
+        assert_eq!(transpose_chord("Dsus4/G", up), "Esus4/A");
+    }
+
+    #[test]
+    fn accidentals_and_capo() {
+        let flat = Transposition {
+            semitones: 1,
+            accidentals: Some(Accidentals::Flat),
+            capo: 0,
+        };
+        assert_eq!(transpose_chord("C", flat), "Db");
+        assert_eq!(transpose_chord("F#", flat), "G");
+        assert_eq!(transpose_chord("C", Transposition::new(1)), "C#");
+        let capo = Transposition {
+            semitones: 0,
+            accidentals: None,
+            capo: 2,
+        };
+        assert_eq!(transpose_chord("A", capo), "G");
+    }
+
+    #[test]
+    fn line() {
+        assert_eq!(
+            transpose_line("[G]Toen den hertog [D/F#]Jan", Transposition::new(-2)),
+            "[F]Toen den hertog [C/E]Jan"
+        );
+        assert_eq!(
+            transpose_line("Zonder akkoorden", Transposition::new(3)),
+            "Zonder akkoorden"
+        );
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let transposition: Transposition = "+2 flat capo 3".parse().unwrap();
+        assert_eq!(transposition.semitones, 2);
+        assert_eq!(transposition.accidentals, Some(Accidentals::Flat));
+        assert_eq!(transposition.capo, 3);
+        assert_eq!(transposition.to_string(), "+2 flat capo 3");
+        assert_eq!(" 2".parse::<Transposition>().unwrap().semitones, 2);
+        assert!("twee".parse::<Transposition>().is_err());
+
+        let member = LyricId::default();
+        let transpositions = [(member, transposition)].into_iter().collect();
+        let text = transpositions_to_text(&transpositions);
+        assert_eq!(to_transpositions(&text, &[member]).unwrap(), transpositions);
+        assert!(to_transpositions(&text, &[]).unwrap().is_empty());
+    }
+}
@@ -1,13 +1,112 @@
 use crate::error::Error;
 use bs58::{decode, encode};
+use chrono::{DateTime, Utc};
 use core::default::Default;
 use core::str::FromStr;
-use serde_with::{DeserializeFromStr, SerializeDisplay};
+use serde::{Serialize, Serializer};
+use serde_with::DeserializeFromStr;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-#[derive(
-    Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, DeserializeFromStr, SerializeDisplay,
-)]
+/// How ids are serialized
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, DeserializeFromStr)]
+pub enum IdFormat {
+    /// Base 58, like `T2NPjHifDf1E1UfZZA6TDB`
+    #[default]
+    Bs58,
+    /// Hyphenated, like `3bd4d5c5-1d4b-4bbf-a1de-6e6c5f5a1f3a`
+    Uuid,
+}
+
+impl FromStr for IdFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bs58" => Ok(Self::Bs58),
+            "uuid" => Ok(Self::Uuid),
+            _ => Err(Error::Parse(format!("id format {s}"))),
+        }
+    }
+}
+
+tokio::task_local! {
+    static ID_FORMAT: IdFormat;
+}
+
+/// Runs `f` with ids serialized in `format`. `Display` and the storage formats keep using base 58.
+pub async fn with_id_format<F>(format: IdFormat, f: F) -> F::Output
+where
+    F: Future,
+{
+    ID_FORMAT.scope(format, f).await
+}
+
+/// Runs `f` with ids serialized in `format`, for responses that are serialized later, outside [`with_id_format`]
+pub fn with_id_format_sync<T>(format: IdFormat, f: impl FnOnce() -> T) -> T {
+    ID_FORMAT.sync_scope(format, f)
+}
+
+/// Runs `f` with ids serialized in base 58, for data that is stored or hashed
+pub fn with_bs58<T>(f: impl FnOnce() -> T) -> T {
+    with_id_format_sync(IdFormat::Bs58, f)
+}
+
+/// The format that ids are serialized in, base 58 outside [`with_id_format`]
+#[must_use]
+pub fn id_format() -> IdFormat {
+    ID_FORMAT.try_with(|format| *format).unwrap_or_default()
+}
+
+/// How the ids of new items are generated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdGenerator {
+    /// Random, uuid version 4
+    #[default]
+    V4,
+    /// Ordered by creation time, uuid version 7. See [`Uuid::created`]
+    V7,
+}
+
+impl FromStr for IdGenerator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "v4" => Ok(Self::V4),
+            "v7" => Ok(Self::V7),
+            _ => Err(Error::Parse(format!("id generator {s}"))),
+        }
+    }
+}
+
+impl IdGenerator {
+    #[must_use]
+    pub fn generate(self) -> Uuid {
+        match self {
+            Self::V4 => uuid::Uuid::new_v4().into(),
+            Self::V7 => uuid::Uuid::now_v7().into(),
+        }
+    }
+}
+
+static TIME_ORDERED: AtomicBool = AtomicBool::new(false);
+
+/// Sets the generator used by `Uuid::default` and so for every new lyric and playlist, in the whole process
+pub fn set_id_generator(generator: IdGenerator) {
+    TIME_ORDERED.store(generator == IdGenerator::V7, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn id_generator() -> IdGenerator {
+    if TIME_ORDERED.load(Ordering::Relaxed) {
+        IdGenerator::V7
+    } else {
+        IdGenerator::V4
+    }
+}
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, DeserializeFromStr)]
 pub struct Uuid(uuid::Uuid);
 
 impl Uuid {
@@ -15,6 +114,16 @@ impl Uuid {
     pub fn inner(&self) -> uuid::Uuid {
         self.0
     }
+
+    /// Creation time of a version 7 id, none for other versions
+    #[must_use]
+    pub fn created(&self) -> Option<DateTime<Utc>> {
+        if self.0.get_version_num() != 7 {
+            return None;
+        }
+        let (seconds, nanoseconds) = self.0.get_timestamp()?.to_unix();
+        DateTime::from_timestamp(i64::try_from(seconds).ok()?, nanoseconds)
+    }
 }
 
 impl Display for Uuid {
@@ -35,10 +144,25 @@ fn bytes_to_uuid(bytes: Vec<u8>) -> Result<uuid::Uuid, Error> {
     uuid::Uuid::from_slice(&bytes).map_err(Error::from)
 }
 
+impl Serialize for Uuid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match id_format() {
+            IdFormat::Bs58 => serializer.collect_str(self),
+            IdFormat::Uuid => serializer.collect_str(&self.0.hyphenated()),
+        }
+    }
+}
+
+/// Accepts base 58 and the hyphenated form, base 58 has no hyphen
 impl FromStr for Uuid {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains('-') {
+            return uuid::Uuid::try_parse(s)
+                .map(Uuid::from)
+                .map_err(Error::from);
+        }
         decode(s)
             .into_vec()
             .map_err(Error::from)
@@ -49,7 +173,7 @@ impl FromStr for Uuid {
 
 impl Default for Uuid {
     fn default() -> Self {
-        uuid::Uuid::new_v4().into()
+        id_generator().generate()
     }
 }
 
@@ -59,11 +183,160 @@ impl From<uuid::Uuid> for Uuid {
     }
 }
 
+/// Id of one kind of item. Written like `Uuid`, so the wire format does not change
+macro_rules! typed_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Clone,
+            Copy,
+            Default,
+            Hash,
+            PartialEq,
+            Eq,
+            PartialOrd,
+            Ord,
+            DeserializeFromStr,
+        )]
+        pub struct $name(Uuid);
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.0.serialize(serializer)
+            }
+        }
+
+        impl $name {
+            #[must_use]
+            pub fn inner(&self) -> uuid::Uuid {
+                self.0.inner()
+            }
+
+            /// Creation time of a version 7 id, none for other versions
+            #[must_use]
+            pub fn created(&self) -> Option<DateTime<Utc>> {
+                self.0.created()
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+                write!(f, "{self}")
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse::<Uuid>().map(Self)
+            }
+        }
+
+        impl From<Uuid> for $name {
+            fn from(uuid: Uuid) -> Self {
+                Self(uuid)
+            }
+        }
+
+        impl From<uuid::Uuid> for $name {
+            fn from(uuid: uuid::Uuid) -> Self {
+                Self(uuid.into())
+            }
+        }
+
+        impl From<$name> for Uuid {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+    };
+}
+
+typed_id!(
+    /// Id of a lyric
+    LyricId
+);
+
+typed_id!(
+    /// Id of a playlist
+    PlaylistId
+);
+
 #[cfg(test)]
 mod test {
+    use super::{
+        IdFormat, IdGenerator, LyricId, PlaylistId, Uuid, id_format, with_bs58, with_id_format,
+        with_id_format_sync,
+    };
+
     #[test]
     fn new_id() {
         let id = super::Uuid::default();
         dbg!(id.to_string());
     }
+
+    #[test]
+    fn typed_id_wire_format() {
+        let uuid = "T2NPjHifDf1E1UfZZA6TDB".parse::<Uuid>().unwrap();
+        let lyric_id = "T2NPjHifDf1E1UfZZA6TDB".parse::<LyricId>().unwrap();
+        assert_eq!(Uuid::from(lyric_id), uuid);
+        assert_eq!(lyric_id.to_string(), uuid.to_string());
+        assert_eq!(
+            toml::Value::try_from(PlaylistId::from(uuid)).unwrap(),
+            toml::Value::try_from(uuid).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_hyphenated() {
+        let bs58 = "T2NPjHifDf1E1UfZZA6TDB".parse::<Uuid>().unwrap();
+        let hyphenated = bs58.inner().hyphenated().to_string();
+        assert_eq!(hyphenated.parse::<Uuid>().unwrap(), bs58);
+        assert_eq!(hyphenated.parse::<LyricId>().unwrap(), LyricId::from(bs58));
+        assert!("T2NPjHifDf1E1UfZZA6TDB-".parse::<Uuid>().is_err());
+    }
+
+    #[tokio::test]
+    async fn serialize_in_format() {
+        let id = "T2NPjHifDf1E1UfZZA6TDB".parse::<LyricId>().unwrap();
+        let value = |id: LyricId| {
+            toml::Value::try_from(id)
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .to_owned()
+        };
+        let (uuid, bs58) = with_id_format(IdFormat::Uuid, async {
+            (value(id), with_bs58(|| value(id)))
+        })
+        .await;
+        assert_eq!(uuid, id.inner().hyphenated().to_string());
+        assert_eq!(bs58, "T2NPjHifDf1E1UfZZA6TDB");
+        assert_eq!(value(id), bs58);
+        assert_eq!(id.to_string(), bs58);
+
+        let format = with_id_format(IdFormat::Uuid, async { id_format() }).await;
+        assert_eq!(with_id_format_sync(format, || value(id)), uuid);
+    }
+
+    #[test]
+    fn time_ordered() {
+        let before = chrono::Utc::now();
+        let first = IdGenerator::V7.generate();
+        let second = IdGenerator::V7.generate();
+        assert!(first < second);
+        let created = first.created().unwrap();
+        assert!(created >= before - chrono::TimeDelta::milliseconds(1));
+        assert!(created <= chrono::Utc::now());
+        assert_eq!(LyricId::from(first).created(), Some(created));
+        assert_eq!(IdGenerator::V4.generate().created(), None);
+        assert_eq!("V7".parse::<IdGenerator>().unwrap(), IdGenerator::V7);
+    }
 }
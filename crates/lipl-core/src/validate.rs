@@ -0,0 +1,393 @@
+/*!
+Validation of lyrics and playlists before they are stored.
+
+[`Rules`] holds the limits, [`Validate`] checks a [`LyricPost`] or [`PlaylistPost`] against them and
+[`check_title`] checks the title against the titles already stored. Every problem is reported as a
+[`FieldError`], so a client can show it next to the field. Fields are named as in json, like `parts[1][0]`.
+*/
+
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use core::str::FromStr;
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, LyricPost, PlaylistPost, Result, Summary, Uuid};
+
+/// How titles of the same kind of item must differ
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TitleRule {
+    /// Titles can be the same
+    Any,
+    /// Titles must differ
+    #[default]
+    Unique,
+    /// Titles must differ, ignoring case
+    UniqueIgnoreCase,
+}
+
+impl FromStr for TitleRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "any" => Ok(Self::Any),
+            "unique" => Ok(Self::Unique),
+            "unique-ignore-case" => Ok(Self::UniqueIgnoreCase),
+            _ => Err(Error::Parse(format!("title rule {s}"))),
+        }
+    }
+}
+
+impl TitleRule {
+    fn same(self, a: &str, b: &str) -> bool {
+        match self {
+            Self::Any => false,
+            Self::Unique => a.trim() == b.trim(),
+            Self::UniqueIgnoreCase => a.trim().to_lowercase() == b.trim().to_lowercase(),
+        }
+    }
+}
+
+/// Limits for lyrics and playlists. Lengths are in characters
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rules {
+    pub max_title_length: usize,
+    /// Limit for each metadata text and tag
+    pub max_metadata_length: usize,
+    pub max_parts: usize,
+    /// Limit for all lines of a part together
+    pub max_part_length: usize,
+    pub max_members: usize,
+    pub titles: TitleRule,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            max_title_length: 200,
+            max_metadata_length: 200,
+            max_parts: 100,
+            max_part_length: 5_000,
+            max_members: 1_000,
+            titles: TitleRule::default(),
+        }
+    }
+}
+
+/// A problem with the value of one field
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    /// Machine readable code, like `required` or `too-long`
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: impl Into<String>, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            code: code.to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Formats a list of field errors separated by semicolons
+#[must_use]
+pub fn to_text(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+pub trait Validate {
+    /// Returns the problems found, an empty list if there are none
+    fn field_errors(&self, rules: &Rules) -> Vec<FieldError>;
+
+    /// # Errors
+    ///
+    /// Returns `Error::Validation` with every problem found
+    fn validate(&self, rules: &Rules) -> Result<()> {
+        to_result(self.field_errors(rules))
+    }
+}
+
+/// # Errors
+///
+/// Returns `Error::Validation` if `errors` is not empty
+pub fn to_result(errors: Vec<FieldError>) -> Result<()> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Validation(errors))
+    }
+}
+
+/// Tabs are allowed, other control characters are not
+fn has_control(text: &str) -> bool {
+    text.chars().any(|c| c.is_control() && c != '\t')
+}
+
+fn check_text(errors: &mut Vec<FieldError>, field: &str, text: &str, max_length: usize) {
+    let length = text.chars().count();
+    if length > max_length {
+        errors.push(FieldError::new(
+            field,
+            "too-long",
+            format!("{length} characters, at most {max_length} allowed"),
+        ));
+    }
+    if has_control(text) {
+        errors.push(FieldError::new(
+            field,
+            "control-character",
+            "contains a control character",
+        ));
+    }
+}
+
+fn check_title_text(errors: &mut Vec<FieldError>, title: &str, rules: &Rules) {
+    if title.trim().is_empty() {
+        errors.push(FieldError::new("title", "required", "cannot be empty"));
+    }
+    check_text(errors, "title", title, rules.max_title_length);
+}
+
+impl Validate for LyricPost {
+    fn field_errors(&self, rules: &Rules) -> Vec<FieldError> {
+        let mut errors = vec![];
+        check_title_text(&mut errors, &self.title, rules);
+
+        let metadata = &self.metadata;
+        for (field, text) in [
+            ("subtitle", &metadata.subtitle),
+            ("lyricist", &metadata.lyricist),
+            ("composer", &metadata.composer),
+            ("key", &metadata.key),
+            ("copyright", &metadata.copyright),
+            ("language", &metadata.language),
+        ] {
+            if let Some(text) = text {
+                check_text(&mut errors, field, text, rules.max_metadata_length);
+            }
+        }
+        for (index, tag) in metadata.tags.iter().enumerate() {
+            check_text(
+                &mut errors,
+                &format!("tags[{index}]"),
+                tag,
+                rules.max_metadata_length,
+            );
+        }
+
+        if self.parts.len() > rules.max_parts {
+            errors.push(FieldError::new(
+                "parts",
+                "too-many",
+                format!(
+                    "{} parts, at most {} allowed",
+                    self.parts.len(),
+                    rules.max_parts
+                ),
+            ));
+        }
+        for (index, part) in self.parts.iter().enumerate() {
+            let length = part.iter().map(|line| line.chars().count()).sum::<usize>();
+            if length > rules.max_part_length {
+                errors.push(FieldError::new(
+                    format!("parts[{index}]"),
+                    "too-long",
+                    format!(
+                        "{length} characters, at most {} allowed",
+                        rules.max_part_length
+                    ),
+                ));
+            }
+            for (line_index, line) in part.iter().enumerate() {
+                if has_control(line) {
+                    errors.push(FieldError::new(
+                        format!("parts[{index}][{line_index}]"),
+                        "control-character",
+                        "contains a control character",
+                    ));
+                }
+            }
+        }
+        errors
+    }
+}
+
+impl Validate for PlaylistPost {
+    fn field_errors(&self, rules: &Rules) -> Vec<FieldError> {
+        let mut errors = vec![];
+        check_title_text(&mut errors, &self.title, rules);
+
+        if self.members.len() > rules.max_members {
+            errors.push(FieldError::new(
+                "members",
+                "too-many",
+                format!(
+                    "{} members, at most {} allowed",
+                    self.members.len(),
+                    rules.max_members
+                ),
+            ));
+        }
+        let mut seen = HashSet::new();
+        for (index, member) in self.members.iter().enumerate() {
+            if !seen.insert(member) {
+                errors.push(FieldError::new(
+                    format!("members[{index}]"),
+                    "duplicate",
+                    format!("{member} is already a member"),
+                ));
+            }
+        }
+        errors
+    }
+}
+
+/// Returns a `not-unique` error if another item in `summaries` has the same title as `title`, according to the title rule.
+/// The item with id `id` is the item that is changed, it is not compared.
+#[must_use]
+pub fn title_errors(
+    summaries: &[Summary],
+    id: Option<Uuid>,
+    title: &str,
+    rules: &Rules,
+) -> Vec<FieldError> {
+    let occupied = summaries
+        .iter()
+        .filter(|summary| Some(summary.id) != id)
+        .any(|summary| rules.titles.same(&summary.title, title));
+    if occupied {
+        vec![not_unique(title)]
+    } else {
+        vec![]
+    }
+}
+
+/// The `not-unique` error for a title that is already used
+#[must_use]
+pub fn not_unique(title: &str) -> FieldError {
+    FieldError::new(
+        "title",
+        "not-unique",
+        format!("{} is already used", title.trim()),
+    )
+}
+
+/// Checks that the title is not used by another item in `summaries`, see [`title_errors`]
+///
+/// # Errors
+///
+/// Returns `Error::Validation` with a `not-unique` error for the title
+pub fn check_title(
+    summaries: &[Summary],
+    id: Option<Uuid>,
+    title: &str,
+    rules: &Rules,
+) -> Result<()> {
+    to_result(title_errors(summaries, id, title, rules))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Rules, TitleRule, Validate, check_title};
+    use crate::{Error, LyricId, LyricPost, Metadata, PlaylistPost, Summary, Uuid};
+
+    fn codes(errors: &[super::FieldError]) -> Vec<(&str, &str)> {
+        errors
+            .iter()
+            .map(|error| (error.field.as_str(), error.code.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn lyric_post_valid() {
+        let lyric_post = LyricPost::from(("Roodkapje", "Zeg roodkapje\twaar ga je hene"));
+        assert!(lyric_post.validate(&Rules::default()).is_ok());
+    }
+
+    #[test]
+    fn lyric_post_invalid() {
+        let rules = Rules {
+            max_parts: 1,
+            max_part_length: 10,
+            ..Rules::default()
+        };
+        let lyric_post = LyricPost {
+            title: " ".to_owned(),
+            metadata: Metadata {
+                tags: vec!["kerst\u{7}".to_owned()],
+                ..Metadata::default()
+            },
+            parts: vec![
+                vec!["Kort".to_owned()],
+                vec!["Veel te lang".to_owned(), "met\u{0}".to_owned()],
+            ],
+        };
+        assert_eq!(
+            codes(&lyric_post.field_errors(&rules)),
+            vec![
+                ("title", "required"),
+                ("tags[0]", "control-character"),
+                ("parts", "too-many"),
+                ("parts[1]", "too-long"),
+                ("parts[1][1]", "control-character"),
+            ]
+        );
+        assert!(matches!(
+            lyric_post.validate(&rules),
+            Err(Error::Validation(errors)) if errors.len() == 5
+        ));
+    }
+
+    #[test]
+    fn playlist_post_duplicate_members() {
+        let id = LyricId::default();
+        let playlist_post = PlaylistPost {
+            title: "x".repeat(201),
+            members: vec![id, LyricId::default(), id],
+            ..PlaylistPost::default()
+        };
+        assert_eq!(
+            codes(&playlist_post.field_errors(&Rules::default())),
+            vec![("title", "too-long"), ("members[2]", "duplicate")]
+        );
+    }
+
+    #[test]
+    fn title_rules() {
+        let id = Uuid::default();
+        let summaries = vec![Summary {
+            id,
+            title: "Roodkapje".to_owned(),
+        }];
+        let rules = |titles| Rules {
+            titles,
+            ..Rules::default()
+        };
+        let unique = rules(TitleRule::Unique);
+        assert!(check_title(&summaries, None, "Roodkapje ", &unique).is_err());
+        assert!(check_title(&summaries, Some(id), "Roodkapje", &unique).is_ok());
+        assert!(check_title(&summaries, None, "roodkapje", &unique).is_ok());
+        let ignore_case = rules(TitleRule::UniqueIgnoreCase);
+        assert!(check_title(&summaries, None, "roodkapje", &ignore_case).is_err());
+        assert!(check_title(&summaries, None, "Roodkapje", &rules(TitleRule::Any)).is_ok());
+        assert_eq!(
+            "unique-ignore-case".parse::<TitleRule>().unwrap(),
+            TitleRule::UniqueIgnoreCase
+        );
+    }
+}
@@ -44,6 +44,7 @@ fn create_lyrics(lyric_files: &[PathBuf], playlists: Vec<(String, Vec<String>)>)
                             .map(|title| lyrics.iter().find(|lyric| lyric.title == *title).unwrap())
                             .map(|lyric| lyric.id)
                             .collect::<Vec<_>>(),
+                        ..PlaylistPost::default()
                     }
                 )
             )
@@ -60,6 +61,7 @@ fn create_lyrics(lyric_files: &[PathBuf], playlists: Vec<(String, Vec<String>)>)
                     LyricPost {
                         title: #title.to_owned(),
                         parts: to_parts(include_str!(#file_path)),
+                        ..LyricPost::default()
                     }
                 )
             )
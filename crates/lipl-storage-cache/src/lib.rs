@@ -0,0 +1,562 @@
+/*!
+A [`Repo`] decorator that caches the summaries and the items of another repo.
+
+Writes through the decorator update the cache. A cache made with [`CachedRepo::subscribed`] follows the
+changes of the inner repo and forgets the entries they make stale, which includes the changes made by others
+when the backend reports them, like postgres and redis do. If the changes end it forgets every entry and
+subscribes again, waiting longer after each failed attempt. Otherwise changes made by others are seen when the
+cached entries expire after the time to live, if one is set.
+Lists of items, search and revisions are read from the inner repo. Pages of summaries are taken from
+the cached summaries.
+*/
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use lipl_core::{
+    Etag, Lyric, LyricId, Page, PageRequest, Playlist, PlaylistId, Repo, Result, Revision,
+    RevisionInfo, Summary,
+    change::{Change, ChangeStream},
+    page::paginate,
+    search::Hit,
+};
+
+/// Number of reads answered from the cache and from the inner repo
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct Entry<T> {
+    value: T,
+    stored: Instant,
+}
+
+impl<T> Entry<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            stored: Instant::now(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Cache {
+    /// Changes on every write, a read that started before a write is not stored
+    generation: u64,
+    lyric_summaries: Option<Entry<Vec<Summary>>>,
+    playlist_summaries: Option<Entry<Vec<Summary>>>,
+    lyrics: HashMap<LyricId, Entry<Lyric>>,
+    playlists: HashMap<PlaylistId, Entry<Playlist>>,
+}
+
+/// True if `entry` holds the item with entity tag `etag`
+fn has_etag<T: Etag>(entry: Option<&Entry<T>>, etag: &str) -> bool {
+    entry.is_some_and(|entry| entry.value.etag().as_deref() == Some(etag))
+}
+
+impl Cache {
+    fn clear(&mut self) {
+        self.lyric_summaries = None;
+        self.playlist_summaries = None;
+        self.lyrics.clear();
+        self.playlists.clear();
+    }
+
+    /// Forgets the entries that `change` makes stale, or every entry if changes are missed.
+    /// An upsert of an item that is cached with the same entity tag, like a write through the cache, is known already.
+    fn evict(&mut self, change: &Change) {
+        match change {
+            Change::LyricUpserted { id, etag } if has_etag(self.lyrics.get(id), etag) => return,
+            Change::PlaylistUpserted { id, etag } if has_etag(self.playlists.get(id), etag) => {
+                return;
+            }
+            Change::LyricUpserted { id, .. } | Change::LyricDeleted { id } => {
+                self.lyrics.remove(id);
+                self.lyric_summaries = None;
+            }
+            Change::PlaylistUpserted { id, .. } | Change::PlaylistDeleted { id } => {
+                self.playlists.remove(id);
+                self.playlist_summaries = None;
+            }
+            Change::Lagged { .. } => {
+                self.clear();
+            }
+        }
+        self.generation += 1;
+    }
+}
+
+/// Wait before subscribing again after the changes of the inner repo ended, doubled after each failure
+const RESUBSCRIBE: Duration = Duration::from_secs(1);
+const MAX_RESUBSCRIBE: Duration = Duration::from_secs(60);
+
+/// Forgets every entry, returns false if the cache is dropped
+fn clear(cache: &Weak<Mutex<Cache>>) -> bool {
+    cache.upgrade().is_some_and(|cache| {
+        let mut cache = cache.lock().unwrap();
+        cache.generation += 1;
+        cache.clear();
+        true
+    })
+}
+
+/// Evicts the entries that the changes of `inner` make stale, until the cache is dropped.
+/// The cache is cleared when the changes end and again when they are subscribed to again,
+/// because the changes in between are missed.
+async fn follow<R: Repo>(inner: R, cache: Weak<Mutex<Cache>>, mut changes: ChangeStream) {
+    loop {
+        while let Some(change) = changes.next().await {
+            let Some(cache) = cache.upgrade() else {
+                return;
+            };
+            cache.lock().unwrap().evict(&change);
+        }
+        let mut wait = RESUBSCRIBE;
+        changes = loop {
+            if !clear(&cache) {
+                return;
+            }
+            tokio::time::sleep(wait).await;
+            match inner.subscribe().await {
+                Ok(changes) => break changes,
+                Err(_) => wait = (wait * 2).min(MAX_RESUBSCRIBE),
+            }
+        };
+        if !clear(&cache) {
+            return;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CachedRepo<R> {
+    inner: R,
+    ttl: Option<Duration>,
+    cache: Arc<Mutex<Cache>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl<R> CachedRepo<R> {
+    /// Caches `inner`. Without `ttl` entries are kept until they are written through this repo
+    pub fn new(inner: R, ttl: Option<Duration>) -> Self {
+        Self {
+            inner,
+            ttl,
+            cache: Arc::default(),
+            hits: Arc::default(),
+            misses: Arc::default(),
+        }
+    }
+
+    /// Caches `inner` and subscribes to its changes, each change evicts the entries it makes stale.
+    /// When the changes end it subscribes again, see the crate documentation.
+    /// Must be called within a tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the first subscription fails.
+    pub async fn subscribed(inner: R, ttl: Option<Duration>) -> Result<Self>
+    where
+        R: Repo + Clone + Sync + 'static,
+    {
+        let changes = inner.subscribe().await?;
+        let repo = Self::new(inner.clone(), ttl);
+        tokio::spawn(follow(inner, Arc::downgrade(&repo.cache), changes));
+        Ok(repo)
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn is_fresh<T>(&self, entry: &Entry<T>) -> bool {
+        self.ttl.is_none_or(|ttl| entry.stored.elapsed() < ttl)
+    }
+
+    /// Returns the entry selected by `read` if it is fresh, otherwise fetches the value and stores it with `write`
+    async fn cached<T, F>(
+        &self,
+        read: impl Fn(&Cache) -> Option<&Entry<T>>,
+        write: impl FnOnce(&mut Cache, Entry<T>),
+        fetch: F,
+    ) -> Result<T>
+    where
+        T: Clone,
+        F: Future<Output = Result<T>>,
+    {
+        let generation = {
+            let cache = self.cache.lock().unwrap();
+            if let Some(entry) = read(&cache).filter(|entry| self.is_fresh(entry)) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(entry.value.clone());
+            }
+            cache.generation
+        };
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let value = fetch.await?;
+        let mut cache = self.cache.lock().unwrap();
+        if cache.generation == generation {
+            write(&mut cache, Entry::new(value.clone()));
+        }
+        Ok(value)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Cache)) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.generation += 1;
+        f(&mut cache);
+    }
+
+    /// Stores the lyric as written, or forgets it if the write failed
+    fn lyric_written(&self, id: LyricId, result: Result<Lyric>) -> Result<Lyric> {
+        self.update(|cache| {
+            cache.lyric_summaries = None;
+            match &result {
+                Ok(lyric) => {
+                    cache.lyrics.insert(id, Entry::new(lyric.clone()));
+                }
+                Err(_) => {
+                    cache.lyrics.remove(&id);
+                }
+            }
+        });
+        result
+    }
+
+    /// Deleting a lyric changes the playlists it was a member of
+    fn lyric_deleted(&self, id: LyricId, result: Result<()>) -> Result<()> {
+        self.update(|cache| {
+            cache.lyric_summaries = None;
+            cache.lyrics.remove(&id);
+            cache.playlists.clear();
+        });
+        result
+    }
+
+    /// Stores the playlist as written, or forgets it if the write failed
+    fn playlist_written(&self, id: PlaylistId, result: Result<Playlist>) -> Result<Playlist> {
+        self.update(|cache| {
+            cache.playlist_summaries = None;
+            match &result {
+                Ok(playlist) => {
+                    cache.playlists.insert(id, Entry::new(playlist.clone()));
+                }
+                Err(_) => {
+                    cache.playlists.remove(&id);
+                }
+            }
+        });
+        result
+    }
+
+    fn playlist_deleted(&self, id: PlaylistId, result: Result<()>) -> Result<()> {
+        self.update(|cache| {
+            cache.playlist_summaries = None;
+            cache.playlists.remove(&id);
+        });
+        result
+    }
+}
+
+impl<R: Repo + Sync> Repo for CachedRepo<R> {
+    async fn get_lyrics(&self) -> Result<Vec<Lyric>> {
+        self.inner.get_lyrics().await
+    }
+
+    async fn get_lyric_summaries(&self) -> Result<Vec<Summary>> {
+        self.cached(
+            |cache| cache.lyric_summaries.as_ref(),
+            |cache, entry| cache.lyric_summaries = Some(entry),
+            self.inner.get_lyric_summaries(),
+        )
+        .await
+    }
+
+    async fn get_lyrics_page(&self, page: PageRequest) -> Result<Page<Lyric>> {
+        self.inner.get_lyrics_page(page).await
+    }
+
+    async fn get_lyric_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.get_lyric_summaries()
+            .await
+            .map(|summaries| paginate(summaries, &page))
+    }
+
+    async fn get_lyric(&self, id: LyricId) -> Result<Lyric> {
+        self.cached(
+            |cache| cache.lyrics.get(&id),
+            |cache, entry| {
+                cache.lyrics.insert(id, entry);
+            },
+            self.inner.get_lyric(id),
+        )
+        .await
+    }
+
+    async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
+        let id = lyric.id;
+        let result = self.inner.upsert_lyric(lyric).await;
+        self.lyric_written(id, result)
+    }
+
+    async fn delete_lyric(&self, id: LyricId) -> Result<()> {
+        let result = self.inner.delete_lyric(id).await;
+        self.lyric_deleted(id, result)
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, if_match: String) -> Result<Lyric> {
+        let id = lyric.id;
+        let result = self.inner.upsert_lyric_if_match(lyric, if_match).await;
+        self.lyric_written(id, result)
+    }
+
+    async fn delete_lyric_if_match(&self, id: LyricId, if_match: String) -> Result<()> {
+        let result = self.inner.delete_lyric_if_match(id, if_match).await;
+        self.lyric_deleted(id, result)
+    }
+
+    async fn get_lyric_revisions(&self, id: LyricId) -> Result<Vec<RevisionInfo>> {
+        self.inner.get_lyric_revisions(id).await
+    }
+
+    async fn get_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Revision<Lyric>> {
+        self.inner.get_lyric_revision(id, revision).await
+    }
+
+    async fn restore_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Lyric> {
+        let result = self.inner.restore_lyric_revision(id, revision).await;
+        self.lyric_written(id, result)
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        self.inner.search(query).await
+    }
+
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        self.inner.get_playlists().await
+    }
+
+    async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
+        self.cached(
+            |cache| cache.playlist_summaries.as_ref(),
+            |cache, entry| cache.playlist_summaries = Some(entry),
+            self.inner.get_playlist_summaries(),
+        )
+        .await
+    }
+
+    async fn get_playlists_page(&self, page: PageRequest) -> Result<Page<Playlist>> {
+        self.inner.get_playlists_page(page).await
+    }
+
+    async fn get_playlist_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.get_playlist_summaries()
+            .await
+            .map(|summaries| paginate(summaries, &page))
+    }
+
+    async fn get_playlist(&self, id: PlaylistId) -> Result<Playlist> {
+        self.cached(
+            |cache| cache.playlists.get(&id),
+            |cache, entry| {
+                cache.playlists.insert(id, entry);
+            },
+            self.inner.get_playlist(id),
+        )
+        .await
+    }
+
+    async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
+        let id = playlist.id;
+        let result = self.inner.upsert_playlist(playlist).await;
+        self.playlist_written(id, result)
+    }
+
+    async fn delete_playlist(&self, id: PlaylistId) -> Result<()> {
+        let result = self.inner.delete_playlist(id).await;
+        self.playlist_deleted(id, result)
+    }
+
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        if_match: String,
+    ) -> Result<Playlist> {
+        let id = playlist.id;
+        let result = self
+            .inner
+            .upsert_playlist_if_match(playlist, if_match)
+            .await;
+        self.playlist_written(id, result)
+    }
+
+    async fn delete_playlist_if_match(&self, id: PlaylistId, if_match: String) -> Result<()> {
+        let result = self.inner.delete_playlist_if_match(id, if_match).await;
+        self.playlist_deleted(id, result)
+    }
+
+    async fn get_playlist_revisions(&self, id: PlaylistId) -> Result<Vec<RevisionInfo>> {
+        self.inner.get_playlist_revisions(id).await
+    }
+
+    async fn get_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> Result<Revision<Playlist>> {
+        self.inner.get_playlist_revision(id, revision).await
+    }
+
+    async fn restore_playlist_revision(&self, id: PlaylistId, revision: u32) -> Result<Playlist> {
+        let result = self.inner.restore_playlist_revision(id, revision).await;
+        self.playlist_written(id, result)
+    }
+
+    async fn subscribe(&self) -> Result<ChangeStream> {
+        self.inner.subscribe().await
+    }
+
+    async fn stop(&self) -> Result<()> {
+        self.inner.stop().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use std::sync::Arc;
+
+    use futures_util::stream;
+    use lipl_core::{HasSummary, Lyric, LyricId, LyricPost, Repo, RepoConfig, change::Change};
+    use lipl_storage_memory::{MemoryRepo, MemoryRepoConfig};
+
+    use super::{Cache, CacheStats, CachedRepo, Entry, RESUBSCRIBE, follow};
+
+    async fn repo(ttl: Option<Duration>) -> (MemoryRepo, CachedRepo<MemoryRepo>) {
+        let inner = MemoryRepoConfig::default().to_repo().await.unwrap();
+        (inner.clone(), CachedRepo::new(inner, ttl))
+    }
+
+    fn lyric(title: &str) -> Lyric {
+        LyricPost::from((title, "Zeg roodkapje waar ga je hene")).into()
+    }
+
+    #[tokio::test]
+    async fn hits_and_misses() {
+        let (_, repo) = repo(None).await;
+        let lyric = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+
+        assert_eq!(repo.get_lyric(lyric.id).await.unwrap(), lyric);
+        assert_eq!(repo.get_lyric_summaries().await.unwrap().len(), 1);
+        assert_eq!(repo.get_lyric_summaries().await.unwrap().len(), 1);
+        assert_eq!(repo.stats(), CacheStats { hits: 2, misses: 1 });
+
+        let changed = Lyric {
+            title: "Daar bij die molen".to_owned(),
+            ..lyric
+        };
+        repo.upsert_lyric(changed.clone()).await.unwrap();
+        assert_eq!(repo.get_lyric(changed.id).await.unwrap(), changed);
+        assert_eq!(
+            repo.get_lyric_summaries().await.unwrap()[0].title,
+            changed.title
+        );
+        assert_eq!(repo.stats(), CacheStats { hits: 3, misses: 2 });
+    }
+
+    /// Waits until the lyric is no longer cached, the inner repo does not have it
+    async fn evicted(repo: &CachedRepo<MemoryRepo>, id: LyricId) -> bool {
+        for _ in 0..100 {
+            if repo.get_lyric(id).await.is_err() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn subscribes_again() {
+        let (inner, repo) = repo(None).await;
+        let roodkapje = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+        inner.delete_lyric(roodkapje.id).await.unwrap();
+        assert_eq!(repo.get_lyric(roodkapje.id).await.unwrap(), roodkapje);
+
+        // The changes end at once, which clears the cache
+        tokio::spawn(follow(
+            inner.clone(),
+            Arc::downgrade(&repo.cache),
+            Box::pin(stream::empty()),
+        ));
+        assert!(evicted(&repo, roodkapje.id).await);
+
+        tokio::time::sleep(RESUBSCRIBE + Duration::from_millis(100)).await;
+        let molen = repo
+            .upsert_lyric(lyric("Daar bij die molen"))
+            .await
+            .unwrap();
+        assert_eq!(repo.get_lyric(molen.id).await.unwrap(), molen);
+        inner.delete_lyric(molen.id).await.unwrap();
+        assert!(evicted(&repo, molen.id).await);
+    }
+
+    #[tokio::test]
+    async fn evicts_on_change() {
+        let inner = MemoryRepoConfig::default().to_repo().await.unwrap();
+        let repo = CachedRepo::subscribed(inner.clone(), None).await.unwrap();
+        let lyric = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+        assert_eq!(repo.get_lyric(lyric.id).await.unwrap(), lyric);
+        assert_eq!(repo.get_lyric_summaries().await.unwrap().len(), 1);
+        assert_eq!(repo.stats(), CacheStats { hits: 1, misses: 1 });
+
+        inner.delete_lyric(lyric.id).await.unwrap();
+        for _ in 0..100 {
+            if repo.get_lyric(lyric.id).await.is_err() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(repo.get_lyric(lyric.id).await.is_err());
+        assert!(repo.get_lyric_summaries().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn clears_when_lagged() {
+        let mut cache = Cache::default();
+        let lyric = lyric("Roodkapje");
+        cache.lyric_summaries = Some(Entry::new(vec![lyric.summary()]));
+        cache.lyrics.insert(lyric.id, Entry::new(lyric));
+
+        cache.evict(&Change::Lagged { missed: 1 });
+        assert!(cache.lyric_summaries.is_none());
+        assert!(cache.lyrics.is_empty());
+        assert_eq!(cache.generation, 1);
+    }
+
+    #[tokio::test]
+    async fn expires_after_ttl() {
+        let (inner, repo) = repo(Some(Duration::from_millis(50))).await;
+        let lyric = repo.upsert_lyric(lyric("Roodkapje")).await.unwrap();
+        assert_eq!(repo.get_lyric_summaries().await.unwrap().len(), 1);
+
+        inner.delete_lyric(lyric.id).await.unwrap();
+        assert_eq!(repo.get_lyric(lyric.id).await.unwrap(), lyric);
+        assert_eq!(repo.get_lyric_summaries().await.unwrap().len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(repo.get_lyric(lyric.id).await.is_err());
+        assert!(repo.get_lyric_summaries().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,612 @@
+/*!
+Conformance checks for implementations of [`lipl_core::Repo`].
+
+Every check takes a repo that may already hold other items and panics if the repo does not keep the
+contract. Titles in a check end with a random word, so checks can share a database and run in parallel.
+[`conformance_tests!`] turns the checks into tests for a backend.
+
+The contract:
+- upserts keep the id of the item and return the item as stored
+- lists are ordered by title, pages walk the same list in order of title and id
+- reading or deleting an unknown id fails with [`Error::NotFound`], also when the id belongs to an item of the other kind
+- a playlist may be empty, members must be lyrics or the upsert fails with [`Error::PlaylistInvalidMember`]
+- deleting a lyric removes it from the playlists it is a member of
+- conditional writes with a stale or unknown entity tag fail with [`Error::PreconditionFailed`]
+- every write records a revision, revisions outlive the item and restoring creates a new revision
+- subscribers receive every write that succeeds, in order, with the entity tag of the stored item
+*/
+
+use std::future::poll_fn;
+
+use lipl_core::{
+    Error, Etag, HasSummary, Lyric, LyricId, LyricPost, Metadata, Page, PageRequest, Playlist,
+    PlaylistId, PlaylistPost, Repo, Result, Summary, Transposition, Transpositions, Uuid,
+    change::{Change, ChangeStream},
+    parts::to_parts,
+};
+
+/// Generates a test for every check.
+///
+/// `$fixture` is an expression that evaluates to a future of `Option<(guard, repo)>`.
+/// The guard is kept until the test ends, `None` skips the checks, for instance if there is no server to test against.
+#[macro_export]
+macro_rules! conformance_tests {
+    ($fixture:expr) => {
+        $crate::conformance_tests!(
+            $fixture =>
+            lyric_upsert,
+            lyric_not_found,
+            lyric_list,
+            lyric_pages,
+            lyric_delete_removes_member,
+            lyric_if_match,
+            lyric_revisions,
+            lyric_search,
+            playlist_upsert,
+            playlist_empty,
+            playlist_invalid_member,
+            playlist_not_found,
+            playlist_list,
+            playlist_pages,
+            playlist_if_match,
+            playlist_revisions,
+            changes,
+        );
+    };
+    ($fixture:expr => $($check:ident),* $(,)?) => {
+        $(
+            #[tokio::test(flavor = "multi_thread")]
+            async fn $check() {
+                if let Some((_guard, repo)) = $fixture.await {
+                    $crate::$check(&repo).await;
+                }
+            }
+        )*
+    };
+}
+
+fn random_word() -> String {
+    Uuid::default().to_string()
+}
+
+fn lyric(title: &str, text: &str) -> Lyric {
+    (
+        None,
+        LyricPost {
+            title: title.to_owned(),
+            metadata: Metadata::default(),
+            parts: to_parts(text),
+        },
+    )
+        .into()
+}
+
+fn playlist(title: &str, members: &[LyricId]) -> Playlist {
+    (
+        None,
+        PlaylistPost {
+            title: title.to_owned(),
+            members: members.to_vec(),
+            ..PlaylistPost::default()
+        },
+    )
+        .into()
+}
+
+async fn lyrics<R: Repo>(repo: &R, titles: &[&str]) -> Vec<Lyric> {
+    let word = random_word();
+    let mut lyrics = vec![];
+    for title in titles {
+        let lyric = lyric(&format!("{title} {word}"), "Zeg roodkapje waar ga je hene");
+        lyrics.push(repo.upsert_lyric(lyric).await.unwrap());
+    }
+    lyrics
+}
+
+async fn playlists<R: Repo>(repo: &R, titles: &[&str]) -> Vec<Playlist> {
+    let word = random_word();
+    let mut playlists = vec![];
+    for title in titles {
+        let playlist = playlist(&format!("{title} {word}"), &[]);
+        playlists.push(repo.upsert_playlist(playlist).await.unwrap());
+    }
+    playlists
+}
+
+/// The items of `list` with an id in `mine`, in the order of `list`
+fn only<T: HasSummary>(list: Vec<T>, mine: &[T]) -> Vec<T> {
+    let ids = mine
+        .iter()
+        .map(|item| item.summary().id)
+        .collect::<Vec<_>>();
+    list.into_iter()
+        .filter(|item| ids.contains(&item.summary().id))
+        .collect()
+}
+
+fn sorted<T: HasSummary + Clone>(items: &[T]) -> Vec<T> {
+    let mut items = items.to_vec();
+    items.sort_by(lipl_core::by_title);
+    items
+}
+
+/// Walks all pages with a small page size
+async fn walk<T, F, Fut>(get_page: F) -> Vec<T>
+where
+    F: Fn(PageRequest) -> Fut,
+    Fut: Future<Output = Result<Page<T>>>,
+{
+    let mut items = vec![];
+    let mut cursor = None;
+    loop {
+        let page = get_page(PageRequest::new(2, cursor)).await.unwrap();
+        assert!(page.items.len() <= 2, "page larger than its limit");
+        items.extend(page.items);
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => return items,
+        }
+    }
+}
+
+fn assert_not_found<T: core::fmt::Debug>(result: Result<T>, id: impl Into<Uuid>) {
+    let id = id.into();
+    assert!(
+        matches!(result, Err(Error::NotFound(found)) if found == id),
+        "expected not found for {id}, got {result:?}"
+    );
+}
+
+fn assert_precondition_failed<T: core::fmt::Debug>(result: Result<T>, id: impl Into<Uuid>) {
+    let id = id.into();
+    assert!(
+        matches!(result, Err(Error::PreconditionFailed(found)) if found == id),
+        "expected precondition failed for {id}, got {result:?}"
+    );
+}
+
+fn etag<T: Etag>(item: &T) -> String {
+    item.etag().unwrap()
+}
+
+/// The next change to one of the items with an id in `ids`, changes to other items are skipped
+async fn next_change(changes: &mut ChangeStream, ids: &[Uuid]) -> Change {
+    loop {
+        let change = poll_fn(|cx| changes.as_mut().poll_next(cx))
+            .await
+            .expect("change stream ended");
+        if change.id().is_some_and(|id| ids.contains(&id)) {
+            return change;
+        }
+    }
+}
+
+pub async fn lyric_upsert<R: Repo>(repo: &R) {
+    let mut lyric = lyric(
+        &format!("Roodkapje {}", random_word()),
+        "# refrein\n[G]Zeg roodkapje waar ga je hene\n\nZo alleen\n\n# refrein",
+    );
+    lyric.metadata = Metadata {
+        key: Some("G".to_owned()),
+        tempo: Some(96),
+        ccli: Some(4_000_000_000),
+        tags: vec!["kinderlied".to_owned(), "sprookje".to_owned()],
+        ..Metadata::default()
+    };
+    assert_eq!(repo.upsert_lyric(lyric.clone()).await.unwrap(), lyric);
+    assert_eq!(repo.get_lyric(lyric.id).await.unwrap(), lyric);
+
+    let changed = Lyric {
+        title: format!("Roodkapje {}", random_word()),
+        metadata: Metadata::default(),
+        parts: to_parts("Zo alleen"),
+        ..lyric
+    };
+    assert_eq!(repo.upsert_lyric(changed.clone()).await.unwrap(), changed);
+    assert_eq!(repo.get_lyric(changed.id).await.unwrap(), changed);
+}
+
+pub async fn lyric_not_found<R: Repo>(repo: &R) {
+    let id = LyricId::default();
+    assert_not_found(repo.get_lyric(id).await, id);
+    assert_not_found(repo.delete_lyric(id).await, id);
+
+    let playlist = playlists(repo, &["Leeg"]).await.remove(0);
+    let id = LyricId::from(Uuid::from(playlist.id));
+    assert_not_found(repo.get_lyric(id).await, id);
+    assert_not_found(repo.delete_lyric(id).await, id);
+    assert_eq!(repo.get_playlist(playlist.id).await.unwrap(), playlist);
+}
+
+pub async fn lyric_list<R: Repo>(repo: &R) {
+    let lyrics = lyrics(repo, &["B", "A", "C"]).await;
+    assert_eq!(
+        only(repo.get_lyrics().await.unwrap(), &lyrics),
+        sorted(&lyrics)
+    );
+    assert_eq!(
+        only(
+            repo.get_lyric_summaries().await.unwrap(),
+            &lipl_core::to_summaries(lyrics.clone())
+        ),
+        lipl_core::to_summaries(sorted(&lyrics))
+    );
+}
+
+pub async fn lyric_pages<R: Repo>(repo: &R) {
+    let lyrics = lyrics(repo, &["B", "A", "C"]).await;
+    let summaries = lipl_core::to_summaries(lyrics.clone());
+    assert_eq!(
+        only(
+            walk(|page| repo.get_lyric_summaries_page(page)).await,
+            &summaries
+        ),
+        lipl_core::to_summaries(sorted(&lyrics))
+    );
+    assert_eq!(
+        only(walk(|page| repo.get_lyrics_page(page)).await, &lyrics),
+        sorted(&lyrics)
+    );
+}
+
+pub async fn lyric_delete_removes_member<R: Repo>(repo: &R) {
+    let lyrics = lyrics(repo, &["A", "B"]).await;
+    let mut playlist = playlist(
+        &format!("Alle {}", random_word()),
+        &[lyrics[0].id, lyrics[1].id],
+    );
+    playlist
+        .transpositions
+        .insert(lyrics[0].id, Transposition::new(2));
+    repo.upsert_playlist(playlist.clone()).await.unwrap();
+
+    repo.delete_lyric(lyrics[0].id).await.unwrap();
+    assert_not_found(repo.get_lyric(lyrics[0].id).await, lyrics[0].id);
+    let playlist = repo.get_playlist(playlist.id).await.unwrap();
+    assert_eq!(playlist.members, vec![lyrics[1].id]);
+    assert!(playlist.transpositions.is_empty());
+}
+
+pub async fn lyric_if_match<R: Repo>(repo: &R) {
+    let lyric = lyrics(repo, &["Roodkapje"]).await.remove(0);
+    let changed = Lyric {
+        parts: to_parts("Zo alleen"),
+        ..lyric.clone()
+    };
+    assert_precondition_failed(
+        repo.upsert_lyric_if_match(changed.clone(), "\"stale\"".to_owned())
+            .await,
+        lyric.id,
+    );
+    assert_eq!(repo.get_lyric(lyric.id).await.unwrap(), lyric);
+    assert_eq!(
+        repo.upsert_lyric_if_match(changed.clone(), etag(&lyric))
+            .await
+            .unwrap(),
+        changed
+    );
+    assert_precondition_failed(
+        repo.delete_lyric_if_match(lyric.id, etag(&lyric)).await,
+        lyric.id,
+    );
+    repo.delete_lyric_if_match(lyric.id, etag(&changed))
+        .await
+        .unwrap();
+    assert_not_found(repo.get_lyric(lyric.id).await, lyric.id);
+    assert_precondition_failed(
+        repo.upsert_lyric_if_match(lyric.clone(), etag(&lyric))
+            .await,
+        lyric.id,
+    );
+}
+
+pub async fn lyric_revisions<R: Repo>(repo: &R) {
+    let lyric = lyrics(repo, &["Roodkapje"]).await.remove(0);
+    let changed = Lyric {
+        parts: to_parts("Zo alleen"),
+        ..lyric.clone()
+    };
+    repo.upsert_lyric(changed.clone()).await.unwrap();
+
+    let revisions = repo.get_lyric_revisions(lyric.id).await.unwrap();
+    assert_eq!(
+        revisions
+            .iter()
+            .map(|info| info.revision)
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(
+        repo.get_lyric_revision(lyric.id, 1).await.unwrap().item,
+        lyric
+    );
+    assert_eq!(
+        repo.get_lyric_revision(lyric.id, 2).await.unwrap().item,
+        changed
+    );
+    assert!(matches!(
+        repo.get_lyric_revision(lyric.id, 3).await,
+        Err(Error::RevisionNotFound(id, 3)) if id == Uuid::from(lyric.id)
+    ));
+
+    assert_eq!(
+        repo.restore_lyric_revision(lyric.id, 1).await.unwrap(),
+        lyric
+    );
+    assert_eq!(repo.get_lyric(lyric.id).await.unwrap(), lyric);
+    assert_eq!(repo.get_lyric_revisions(lyric.id).await.unwrap().len(), 3);
+
+    repo.delete_lyric(lyric.id).await.unwrap();
+    assert_eq!(repo.get_lyric_revisions(lyric.id).await.unwrap().len(), 3);
+    assert_eq!(
+        repo.restore_lyric_revision(lyric.id, 2).await.unwrap(),
+        changed
+    );
+    assert_eq!(repo.get_lyric(lyric.id).await.unwrap(), changed);
+}
+
+pub async fn lyric_search<R: Repo>(repo: &R) {
+    let (in_title, in_line) = (random_word(), random_word());
+    let lyric = repo
+        .upsert_lyric(lyric(
+            &format!("Hertog Jan {in_title}"),
+            &format!("Toen den hertog [G]Jan kwam [D]{in_line} naar de ﬁnale in Győr"),
+        ))
+        .await
+        .unwrap();
+    let found = |hits: Vec<lipl_core::search::Hit>| hits.iter().any(|hit| hit.id == lyric.id);
+    assert!(found(repo.search(&in_title).await.unwrap()));
+    assert!(found(repo.search(&in_line.to_lowercase()).await.unwrap()));
+    assert!(found(
+        repo.search(&format!("hertog {in_line}")).await.unwrap()
+    ));
+    // Compatibility characters and every accent are normalized the same on every backend
+    assert!(found(
+        repo.search(&format!("finale gyor {in_title}"))
+            .await
+            .unwrap()
+    ));
+    assert!(repo.search(&random_word()).await.unwrap().is_empty());
+}
+
+pub async fn playlist_upsert<R: Repo>(repo: &R) {
+    let lyrics = lyrics(repo, &["A", "B"]).await;
+    let mut playlist = playlist(
+        &format!("Alle {}", random_word()),
+        &[lyrics[1].id, lyrics[0].id],
+    );
+    playlist.transpositions.insert(
+        lyrics[1].id,
+        "-2 flat capo 1".parse::<Transposition>().unwrap(),
+    );
+    assert_eq!(
+        repo.upsert_playlist(playlist.clone()).await.unwrap(),
+        playlist
+    );
+    assert_eq!(repo.get_playlist(playlist.id).await.unwrap(), playlist);
+
+    let changed = Playlist {
+        title: format!("Alle {}", random_word()),
+        members: vec![lyrics[0].id],
+        transpositions: Transpositions::default(),
+        ..playlist
+    };
+    assert_eq!(
+        repo.upsert_playlist(changed.clone()).await.unwrap(),
+        changed
+    );
+    assert_eq!(repo.get_playlist(changed.id).await.unwrap(), changed);
+}
+
+pub async fn playlist_empty<R: Repo>(repo: &R) {
+    let playlists = playlists(repo, &["Leeg"]).await;
+    assert_eq!(
+        repo.get_playlist(playlists[0].id).await.unwrap(),
+        playlists[0]
+    );
+    assert_eq!(
+        only(repo.get_playlists().await.unwrap(), &playlists),
+        playlists
+    );
+    assert_eq!(
+        only(walk(|page| repo.get_playlists_page(page)).await, &playlists),
+        playlists
+    );
+}
+
+pub async fn playlist_invalid_member<R: Repo>(repo: &R) {
+    let lyric = lyrics(repo, &["Roodkapje"]).await.remove(0);
+    let unknown = LyricId::default();
+    let playlist = playlist(&format!("Ongeldig {}", random_word()), &[lyric.id, unknown]);
+    let result = repo.upsert_playlist(playlist.clone()).await;
+    assert!(
+        matches!(&result, Err(Error::PlaylistInvalidMember(_, member)) if *member == unknown.to_string()),
+        "expected invalid member {unknown}, got {result:?}"
+    );
+    assert_not_found(repo.get_playlist(playlist.id).await, playlist.id);
+}
+
+pub async fn playlist_not_found<R: Repo>(repo: &R) {
+    let id = PlaylistId::default();
+    assert_not_found(repo.get_playlist(id).await, id);
+    assert_not_found(repo.delete_playlist(id).await, id);
+
+    let lyric = lyrics(repo, &["Roodkapje"]).await.remove(0);
+    let id = PlaylistId::from(Uuid::from(lyric.id));
+    assert_not_found(repo.get_playlist(id).await, id);
+    assert_not_found(repo.delete_playlist(id).await, id);
+    assert_eq!(repo.get_lyric(lyric.id).await.unwrap(), lyric);
+}
+
+pub async fn playlist_list<R: Repo>(repo: &R) {
+    let playlists = playlists(repo, &["B", "A", "C"]).await;
+    assert_eq!(
+        only(repo.get_playlists().await.unwrap(), &playlists),
+        sorted(&playlists)
+    );
+    let summaries: Vec<Summary> = lipl_core::to_summaries(playlists.clone());
+    assert_eq!(
+        only(repo.get_playlist_summaries().await.unwrap(), &summaries),
+        lipl_core::to_summaries(sorted(&playlists))
+    );
+}
+
+pub async fn playlist_pages<R: Repo>(repo: &R) {
+    let playlists = playlists(repo, &["B", "A", "C"]).await;
+    let summaries = lipl_core::to_summaries(playlists.clone());
+    assert_eq!(
+        only(
+            walk(|page| repo.get_playlist_summaries_page(page)).await,
+            &summaries
+        ),
+        lipl_core::to_summaries(sorted(&playlists))
+    );
+    assert_eq!(
+        only(walk(|page| repo.get_playlists_page(page)).await, &playlists),
+        sorted(&playlists)
+    );
+}
+
+pub async fn playlist_if_match<R: Repo>(repo: &R) {
+    let lyric = lyrics(repo, &["Roodkapje"]).await.remove(0);
+    let playlist = playlists(repo, &["Alle"]).await.remove(0);
+    let changed = Playlist {
+        members: vec![lyric.id],
+        ..playlist.clone()
+    };
+    assert_precondition_failed(
+        repo.upsert_playlist_if_match(changed.clone(), "\"stale\"".to_owned())
+            .await,
+        playlist.id,
+    );
+    assert_eq!(
+        repo.upsert_playlist_if_match(changed.clone(), etag(&playlist))
+            .await
+            .unwrap(),
+        changed
+    );
+    assert_precondition_failed(
+        repo.delete_playlist_if_match(playlist.id, etag(&playlist))
+            .await,
+        playlist.id,
+    );
+    repo.delete_playlist_if_match(playlist.id, etag(&changed))
+        .await
+        .unwrap();
+    assert_not_found(repo.get_playlist(playlist.id).await, playlist.id);
+}
+
+pub async fn playlist_revisions<R: Repo>(repo: &R) {
+    let lyric = lyrics(repo, &["Roodkapje"]).await.remove(0);
+    let playlist = playlists(repo, &["Alle"]).await.remove(0);
+    let changed = Playlist {
+        members: vec![lyric.id],
+        ..playlist.clone()
+    };
+    repo.upsert_playlist(changed.clone()).await.unwrap();
+
+    let revisions = repo.get_playlist_revisions(playlist.id).await.unwrap();
+    assert_eq!(
+        revisions
+            .iter()
+            .map(|info| info.revision)
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(
+        repo.get_playlist_revision(playlist.id, 1)
+            .await
+            .unwrap()
+            .item,
+        playlist
+    );
+    assert!(matches!(
+        repo.get_playlist_revision(playlist.id, 3).await,
+        Err(Error::RevisionNotFound(id, 3)) if id == Uuid::from(playlist.id)
+    ));
+    assert_eq!(
+        repo.restore_playlist_revision(playlist.id, 1)
+            .await
+            .unwrap(),
+        playlist
+    );
+    assert_eq!(
+        repo.get_playlist_revisions(playlist.id)
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+
+    repo.delete_playlist(playlist.id).await.unwrap();
+    assert_eq!(
+        repo.restore_playlist_revision(playlist.id, 2)
+            .await
+            .unwrap(),
+        changed
+    );
+    assert_eq!(repo.get_playlist(playlist.id).await.unwrap(), changed);
+}
+
+pub async fn changes<R: Repo>(repo: &R) {
+    let mut changes = repo.subscribe().await.unwrap();
+    let lyric = lyrics(repo, &["Roodkapje"]).await.remove(0);
+    let playlist = repo
+        .upsert_playlist(playlist(&format!("Alle {}", random_word()), &[lyric.id]))
+        .await
+        .unwrap();
+    assert_precondition_failed(
+        repo.delete_lyric_if_match(lyric.id, etag(&playlist)).await,
+        lyric.id,
+    );
+    repo.delete_playlist(playlist.id).await.unwrap();
+    repo.delete_lyric(lyric.id).await.unwrap();
+
+    let ids = [Uuid::from(lyric.id), Uuid::from(playlist.id)];
+    assert_eq!(
+        next_change(&mut changes, &ids).await,
+        Change::lyric_upserted(&lyric)
+    );
+    assert_eq!(
+        next_change(&mut changes, &ids).await,
+        Change::playlist_upserted(&playlist)
+    );
+    assert_eq!(
+        next_change(&mut changes, &ids).await,
+        Change::PlaylistDeleted { id: playlist.id }
+    );
+    assert_eq!(
+        next_change(&mut changes, &ids).await,
+        Change::LyricDeleted { id: lyric.id }
+    );
+
+    let lyric = lyrics(repo, &["Klokjes"]).await.remove(0);
+    let holder = repo
+        .upsert_playlist(self::playlist(
+            &format!("Alle {}", random_word()),
+            &[lyric.id],
+        ))
+        .await
+        .unwrap();
+    repo.delete_lyric(lyric.id).await.unwrap();
+    let emptied = repo.get_playlist(holder.id).await.unwrap();
+    assert!(emptied.members.is_empty());
+
+    let ids = [Uuid::from(lyric.id), Uuid::from(holder.id)];
+    assert_eq!(
+        next_change(&mut changes, &ids).await,
+        Change::lyric_upserted(&lyric)
+    );
+    assert_eq!(
+        next_change(&mut changes, &ids).await,
+        Change::playlist_upserted(&holder)
+    );
+    assert_eq!(
+        next_change(&mut changes, &ids).await,
+        Change::LyricDeleted { id: lyric.id }
+    );
+    assert_eq!(
+        next_change(&mut changes, &ids).await,
+        Change::playlist_upserted(&emptied)
+    );
+}
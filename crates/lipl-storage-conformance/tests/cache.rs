@@ -0,0 +1,16 @@
+use std::time::Duration;
+
+use lipl_core::RepoConfig;
+use lipl_storage_cache::CachedRepo;
+use lipl_storage_conformance::conformance_tests;
+use lipl_storage_memory::{MemoryRepo, MemoryRepoConfig};
+
+async fn repo() -> Option<((), CachedRepo<MemoryRepo>)> {
+    let inner = MemoryRepoConfig::default().to_repo().await.unwrap();
+    let repo = CachedRepo::subscribed(inner, Some(Duration::from_secs(60)))
+        .await
+        .unwrap();
+    Some(((), repo))
+}
+
+conformance_tests!(repo());
@@ -0,0 +1,42 @@
+use lipl_core::{Lyric, LyricPost, PlaylistId, Repo, RepoConfig};
+use lipl_storage_conformance::conformance_tests;
+use lipl_storage_fs::{FileRepo, FileRepoConfig};
+use tempfile::TempDir;
+
+async fn repo() -> Option<(TempDir, FileRepo)> {
+    let dir = TempDir::new().unwrap();
+    let config = FileRepoConfig {
+        path: dir.path().to_string_lossy().into_owned(),
+    };
+    let repo = config.to_repo().await.unwrap();
+    Some((dir, repo))
+}
+
+conformance_tests!(repo());
+
+/// Files written by other tools can be named with the hyphenated id and list members hyphenated
+#[tokio::test]
+async fn hyphenated_ids() {
+    let (dir, repo) = repo().await.unwrap();
+    let lyric = Lyric::from((None, LyricPost::from(("Roodkapje", "Zeg roodkapje"))));
+    let hyphenated = lyric.id.inner().hyphenated();
+    std::fs::write(
+        dir.path().join(format!("{hyphenated}.md")),
+        lyric.to_string(),
+    )
+    .unwrap();
+    let playlist_id = PlaylistId::default();
+    std::fs::write(
+        dir.path().join(format!("{playlist_id}.toml")),
+        format!("title = \"Alle 13 goed\"\nmembers = [\"{hyphenated}\"]\n"),
+    )
+    .unwrap();
+
+    assert_eq!(repo.get_lyric(lyric.id).await.unwrap(), lyric);
+    assert_eq!(repo.get_lyrics().await.unwrap(), vec![lyric.clone()]);
+    let playlist = repo.get_playlist(playlist_id).await.unwrap();
+    assert_eq!(playlist.members, vec![lyric.id]);
+
+    repo.delete_lyric(lyric.id).await.unwrap();
+    assert!(repo.get_lyrics().await.unwrap().is_empty());
+}
@@ -0,0 +1,250 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::time::Duration;
+
+use lipl_core::{
+    Error, Lyric, LyricPost, Playlist, PlaylistPost, Repo, RepoConfig,
+    reexport::chrono::Utc,
+    transaction::{LoggedRepo, build_from_log_until, recover, recover_until, snapshot_path},
+};
+use lipl_storage_conformance::conformance_tests;
+use lipl_storage_memory::{MemoryRepo, MemoryRepoConfig};
+use tempfile::TempDir;
+
+const LOG: &str = "transaction.log";
+
+async fn repo() -> Option<(TempDir, LoggedRepo<MemoryRepo>)> {
+    let dir = TempDir::new().unwrap();
+    let inner = MemoryRepoConfig::default().to_repo().await.unwrap();
+    let log = File::create(dir.path().join(LOG)).unwrap();
+    Some((dir, LoggedRepo::new(inner, log)))
+}
+
+conformance_tests!(repo());
+
+/// A memory repo that replays the log on startup has the items of the logged repo
+#[tokio::test]
+async fn replay() {
+    let (dir, repo) = repo().await.unwrap();
+    let roodkapje = repo
+        .upsert_lyric(Lyric::from((
+            None,
+            LyricPost::from(("Roodkapje", "Zeg roodkapje")),
+        )))
+        .await
+        .unwrap();
+    let molen = repo
+        .upsert_lyric(Lyric::from((
+            None,
+            LyricPost::from(("Molen", "Daar bij die molen")),
+        )))
+        .await
+        .unwrap();
+    let playlist = repo
+        .upsert_playlist(Playlist::from((
+            None,
+            PlaylistPost {
+                title: "Alle 13 goed".to_owned(),
+                members: vec![roodkapje.id, molen.id],
+                ..PlaylistPost::default()
+            },
+        )))
+        .await
+        .unwrap();
+    repo.delete_lyric(molen.id).await.unwrap();
+    assert!(repo.delete_lyric(molen.id).await.is_err());
+
+    let replayed = MemoryRepoConfig {
+        sample_data: false,
+        transaction_log: Some(dir.path().join(LOG)),
+    }
+    .to_repo()
+    .await
+    .unwrap();
+    assert_eq!(
+        replayed.get_lyrics().await.unwrap(),
+        vec![roodkapje.clone()]
+    );
+    assert_eq!(
+        replayed.get_playlist(playlist.id).await.unwrap().members,
+        vec![roodkapje.id]
+    );
+}
+
+/// After compaction the log is empty and recovery loads the snapshot and replays the changes made since
+#[tokio::test]
+async fn compaction() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(LOG);
+    let inner = MemoryRepoConfig::default().to_repo().await.unwrap();
+    let repo = LoggedRepo::open(inner, &path).unwrap();
+    let roodkapje = repo
+        .upsert_lyric(Lyric::from((
+            None,
+            LyricPost::from(("Roodkapje", "Zeg roodkapje")),
+        )))
+        .await
+        .unwrap();
+    let molen = repo
+        .upsert_lyric(Lyric::from((
+            None,
+            LyricPost::from(("Molen", "Daar bij die molen")),
+        )))
+        .await
+        .unwrap();
+
+    repo.compact().await.unwrap();
+    assert!(std::fs::read_to_string(&path).unwrap().is_empty());
+    assert!(snapshot_path(&path).exists());
+
+    repo.delete_lyric(roodkapje.id).await.unwrap();
+    let replayed = MemoryRepoConfig {
+        sample_data: false,
+        transaction_log: Some(path),
+    }
+    .to_repo()
+    .await
+    .unwrap();
+    assert_eq!(replayed.get_lyrics().await.unwrap(), vec![molen]);
+}
+
+/// Replaying until a moment leaves out the later changes, a compaction after that moment makes it unreachable
+#[tokio::test]
+async fn point_in_time() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(LOG);
+    let inner = MemoryRepoConfig::default().to_repo().await.unwrap();
+    let repo = LoggedRepo::open(inner, &path).unwrap();
+    let roodkapje = repo
+        .upsert_lyric(Lyric::from((
+            None,
+            LyricPost::from(("Roodkapje", "Zeg roodkapje")),
+        )))
+        .await
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(10));
+    let sunday = Utc::now();
+    std::thread::sleep(Duration::from_millis(10));
+    repo.delete_lyric(roodkapje.id).await.unwrap();
+
+    let then = MemoryRepo::default();
+    build_from_log_until(File::open(&path).unwrap(), then.clone(), sunday)
+        .await
+        .unwrap();
+    assert_eq!(then.get_lyrics().await.unwrap(), vec![roodkapje]);
+
+    repo.compact().await.unwrap();
+    let result = recover_until(&path, MemoryRepo::default(), sunday).await;
+    assert!(matches!(result, Err(Error::Argument(_))));
+}
+
+/// A record cut off by a crash is reported and left out, opening the log again removes it
+#[tokio::test]
+async fn torn_tail() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(LOG);
+    let inner = MemoryRepoConfig::default().to_repo().await.unwrap();
+    let repo = LoggedRepo::open(inner, &path).unwrap();
+    let roodkapje = repo
+        .upsert_lyric(Lyric::from((
+            None,
+            LyricPost::from(("Roodkapje", "Zeg roodkapje")),
+        )))
+        .await
+        .unwrap();
+    repo.compact().await.unwrap();
+    let molen = repo
+        .upsert_lyric(Lyric::from((
+            None,
+            LyricPost::from(("Molen", "Daar bij die molen")),
+        )))
+        .await
+        .unwrap();
+    drop(repo);
+    let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+    log.write_all(b"v1 3 0123").unwrap();
+
+    let report = recover(&path, MemoryRepo::default()).await.unwrap();
+    assert_eq!(report.replayed, 1);
+    assert_eq!(report.last_sequence, Some(2));
+    assert_eq!(report.torn_tail, Some(2));
+
+    let inner = MemoryRepoConfig {
+        sample_data: false,
+        transaction_log: Some(path.clone()),
+    }
+    .to_repo()
+    .await
+    .unwrap();
+    let repo = LoggedRepo::open(inner, &path).unwrap();
+    repo.delete_lyric(roodkapje.id).await.unwrap();
+
+    let recovered = MemoryRepo::default();
+    let report = recover(&path, recovered.clone()).await.unwrap();
+    assert_eq!(report.last_sequence, Some(3));
+    assert_eq!(report.torn_tail, None);
+    assert_eq!(recovered.get_lyrics().await.unwrap(), vec![molen]);
+}
+
+/// Concurrent changes are logged in the order they were applied, so replaying the log gives the same lyric
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_order() {
+    let (dir, repo) = repo().await.unwrap();
+    let lyric = Lyric::from((None, LyricPost::from(("Roodkapje", "Zeg roodkapje"))));
+    let writes = (0..20)
+        .map(|index| {
+            let repo = repo.clone();
+            let lyric = Lyric {
+                title: format!("Roodkapje {index}"),
+                ..lyric.clone()
+            };
+            tokio::spawn(async move { repo.upsert_lyric(lyric).await.unwrap() })
+        })
+        .collect::<Vec<_>>();
+    for write in writes {
+        write.await.unwrap();
+    }
+
+    let replayed = MemoryRepoConfig {
+        sample_data: false,
+        transaction_log: Some(dir.path().join(LOG)),
+    }
+    .to_repo()
+    .await
+    .unwrap();
+    assert_eq!(
+        replayed.get_lyric(lyric.id).await.unwrap(),
+        repo.get_lyric(lyric.id).await.unwrap()
+    );
+}
+
+struct Broken;
+
+impl Write for Broken {
+    fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("disk full"))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A change that is applied succeeds even if it cannot be logged, the changes after it are refused
+#[tokio::test]
+async fn failed_append() {
+    let inner = MemoryRepoConfig::default().to_repo().await.unwrap();
+    let repo = LoggedRepo::new(inner, Broken);
+    let roodkapje = repo
+        .upsert_lyric(Lyric::from((
+            None,
+            LyricPost::from(("Roodkapje", "Zeg roodkapje")),
+        )))
+        .await
+        .unwrap();
+    assert_eq!(repo.get_lyric(roodkapje.id).await.unwrap(), roodkapje);
+
+    let result = repo.delete_lyric(roodkapje.id).await;
+    assert!(matches!(result, Err(Error::LogFailed(_))));
+    assert_eq!(repo.get_lyric(roodkapje.id).await.unwrap(), roodkapje);
+}
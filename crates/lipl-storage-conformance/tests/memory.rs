@@ -0,0 +1,9 @@
+use lipl_core::RepoConfig;
+use lipl_storage_conformance::conformance_tests;
+use lipl_storage_memory::{MemoryRepo, MemoryRepoConfig};
+
+async fn repo() -> Option<((), MemoryRepo)> {
+    Some(((), MemoryRepoConfig::default().to_repo().await.unwrap()))
+}
+
+conformance_tests!(repo());
@@ -0,0 +1,42 @@
+//! Runs if `LIPL_STORAGE_POSTGRES_CONNECTION` holds a connection string for a local test database
+
+use lipl_core::{Error, Lyric, LyricPost, Metadata, Repo, RepoConfig, Uuid};
+use lipl_storage_conformance::conformance_tests;
+use lipl_storage_postgres::{PostgresConfig, PostgresConnectionPool};
+
+async fn repo() -> Option<((), PostgresConnectionPool)> {
+    let connection = std::env::var("LIPL_STORAGE_POSTGRES_CONNECTION").ok()?;
+    let repo = PostgresConfig::from(connection).to_repo().await.unwrap();
+    Some(((), repo))
+}
+
+conformance_tests!(repo());
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_titles_are_unique() {
+    let Some(((), repo)) = repo().await else {
+        return;
+    };
+    let title = Uuid::default().to_string();
+    let lyric = || {
+        Lyric::from((
+            None,
+            LyricPost {
+                title: title.clone(),
+                metadata: Metadata::default(),
+                parts: vec![],
+            },
+        ))
+    };
+
+    let (first, second) = tokio::join!(repo.upsert_lyric(lyric()), repo.upsert_lyric(lyric()));
+
+    let errors = [first, second]
+        .into_iter()
+        .filter_map(Result::err)
+        .collect::<Vec<_>>();
+    assert!(
+        matches!(errors.as_slice(), [Error::Validation(errors)] if errors[0].code == "not-unique"),
+        "expected one not-unique error, got {errors:?}"
+    );
+}
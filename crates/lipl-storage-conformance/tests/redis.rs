@@ -0,0 +1,18 @@
+//! Runs if `LIPL_STORAGE_REDIS_CONNECTION` holds the url of a local test server
+
+use lipl_core::RepoConfig;
+use lipl_storage_conformance::conformance_tests;
+use lipl_storage_redis::{RedisRepoConfig, redis_repo::RedisRepo};
+
+async fn repo() -> Option<((), RedisRepo)> {
+    let url = std::env::var("LIPL_STORAGE_REDIS_CONNECTION").ok()?;
+    let repo = url
+        .parse::<RedisRepoConfig<String>>()
+        .unwrap()
+        .to_repo()
+        .await
+        .unwrap();
+    Some(((), repo))
+}
+
+conformance_tests!(repo());
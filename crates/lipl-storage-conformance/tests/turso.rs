@@ -0,0 +1,14 @@
+use lipl_core::RepoConfig;
+use lipl_storage_conformance::conformance_tests;
+use lipl_storage_turso::{TursoConfig, TursoDatabase};
+
+async fn repo() -> Option<((), TursoDatabase)> {
+    let repo = TursoConfig::from(":memory:".to_owned())
+        .to_repo()
+        .await
+        .unwrap();
+    repo.schema().await.unwrap();
+    Some(((), repo))
+}
+
+conformance_tests!(repo());
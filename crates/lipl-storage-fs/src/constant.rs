@@ -1,2 +1,4 @@
 pub const TOML_EXTENSION: &str = "toml";
 pub const LYRIC_EXTENSION: &str = "md";
+pub const REVISION_DIR: &str = ".revisions";
+pub const REVISION_EXTENSION: &str = "jsonl";
@@ -1,10 +1,17 @@
 use futures_util::{Stream, StreamExt, TryFuture, TryStreamExt};
+use serde::{Serialize, de::DeserializeOwned};
+use std::fmt::Display;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::str::FromStr;
+use tokio::io::AsyncWriteExt;
 
 use crate::fs::IO;
-use lipl_core::{Error, Lyric, LyricMeta, LyricPost, Playlist, PlaylistPost, Summary, Uuid};
+use lipl_core::{
+    Error, Lyric, LyricId, LyricMeta, LyricPost, Playlist, PlaylistId, PlaylistPost, Revision,
+    Summary, Uuid, revision::to_record,
+};
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -12,13 +19,14 @@ pub async fn get_lyric_summary<P>(path: P) -> Result<Summary>
 where
     P: AsRef<Path> + Send + Sync,
 {
-    get_item::<LyricMeta, Summary>(path.read_frontmatter().await?, path.id()?)
+    get_item::<LyricMeta, Summary, Uuid>(path.read_frontmatter().await?, path.id()?)
 }
 
-pub fn get_item<F, G>(s: String, id: Uuid) -> Result<G>
+pub fn get_item<F, G, I>(s: String, id: I) -> Result<G>
 where
     F: FromStr<Err = lipl_core::Error>,
-    G: From<(Option<Uuid>, F)>,
+    G: From<(Option<I>, F)>,
+    I: Display,
 {
     s.parse::<F>()
         .map_err(|_| Error::Parse(format!("{id}")))
@@ -29,7 +37,7 @@ pub async fn get_playlist<P>(path: P) -> Result<Playlist>
 where
     P: AsRef<Path> + Send + Sync,
 {
-    get_item::<PlaylistPost, Playlist>(path.read_string().await?, path.id()?)
+    get_item::<PlaylistPost, Playlist, PlaylistId>(path.read_string().await?, path.id()?.into())
 }
 
 pub async fn get_list<P, T, F, Fut>(path: P, ext: &str, f: F) -> Result<Vec<T>>
@@ -75,7 +83,57 @@ pub async fn get_lyric<P>(path: P) -> Result<Lyric>
 where
     P: AsRef<Path> + Send + Sync,
 {
-    get_item::<LyricPost, Lyric>(path.read_string().await?, path.id()?)
+    get_item::<LyricPost, Lyric, LyricId>(path.read_string().await?, path.id()?.into())
+}
+
+fn json_error(error: serde_json::Error) -> Error {
+    Error::Json(Box::new(error))
+}
+
+/// Lines of the file at `path`, none if the file does not exist
+async fn read_lines<P>(path: P) -> Result<Vec<String>>
+where
+    P: AsRef<Path> + Send + Sync,
+{
+    match tokio::fs::read_to_string(path).await {
+        Ok(s) => Ok(s.lines().map(String::from).collect()),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(error) => Err(error.into()),
+    }
+}
+
+pub async fn get_revisions<T, P>(path: P) -> Result<Vec<Revision<T>>>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path> + Send + Sync,
+{
+    read_lines(path)
+        .await?
+        .iter()
+        .map(|line| serde_json::from_str(line).map_err(json_error))
+        .collect()
+}
+
+/// Appends the revisions for `item` replacing `previous` to the history at `path`
+pub async fn post_revisions<T, P>(path: P, previous: Option<T>, item: T) -> Result<()>
+where
+    T: Serialize,
+    P: AsRef<Path> + Send + Sync,
+{
+    let count = read_lines(&path).await?.len();
+    let mut json = String::new();
+    for revision in to_record(count, previous, item) {
+        json.push_str(&serde_json::to_string(&revision).map_err(json_error)?);
+        json.push('\n');
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .await?;
+    file.write_all(json.as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -93,8 +151,7 @@ mod test {
 
     fn data_dir() -> PathBuf {
         if let Ok(workspace) = std::env::var("WORKSPACE") {
-            let test_path = PathBuf::from(workspace).join("test");
-            test_path
+            PathBuf::from(workspace).join("test")
         } else {
             std::env::var("DATA_DIR").unwrap().into()
         }
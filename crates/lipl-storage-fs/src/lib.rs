@@ -1,21 +1,28 @@
 use lipl_core::Repo;
-use lipl_core::transaction::{OptionalTransaction, start_log_thread};
 use std::fmt::{Debug, Display};
-use std::fs::OpenOptions;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::task::JoinHandle;
 
-use constant::{LYRIC_EXTENSION, TOML_EXTENSION};
+use constant::{LYRIC_EXTENSION, REVISION_DIR, REVISION_EXTENSION, TOML_EXTENSION};
 use fs::IO;
 use futures_channel::mpsc;
 use futures_util::{FutureExt, Stream, StreamExt, TryFutureExt, TryStreamExt};
 pub use lipl_core::error::{Error, ErrorExtension};
+use lipl_core::page::paginate;
+use lipl_core::search::{Hit, Index};
 use lipl_core::vec_ext::VecExt;
-use lipl_core::{Lyric, Playlist, RepoConfig, Summary, Uuid, transaction::Request};
-use request::{delete_by_id, post, select, select_by_id};
+use lipl_core::{
+    Etag, HasSummary, Lyric, LyricId, Page, PageRequest, Playlist, PlaylistId, RepoConfig,
+    Revision, RevisionInfo, Summary, Uuid,
+    change::{Change, ChangeStream, Changes},
+    check_etag,
+    revision::{find, with_author},
+    transaction::{Request, ResultSender},
+};
+use request::{Envelope, delete_by_id, if_match, post, select, select_by_id, select_with};
 
 pub mod constant;
 mod fs;
@@ -43,8 +50,9 @@ impl RepoConfig for FileRepoConfig {
 
 #[derive(Clone)]
 pub struct FileRepo {
-    tx: mpsc::Sender<Request>,
+    tx: mpsc::Sender<Envelope>,
     path: String,
+    changes: Changes,
     _join_handle: Arc<JoinHandle<bool>>,
 }
 
@@ -56,7 +64,7 @@ impl Debug for FileRepo {
 
 fn check_members(
     playlist: &Playlist,
-    lyric_ids: &[Uuid],
+    lyric_ids: &[LyricId],
 ) -> impl futures_util::Future<Output = Result<(), Error>> + use<> {
     if let Some(member) = playlist
         .members
@@ -82,15 +90,130 @@ fn send<T, D: Display>(
     }
 }
 
+/// The error for a conditional request that reaches the handler without its precondition checked
+const UNRESOLVED: Error = Error::Argument("conditional request with unresolved precondition");
+
+/// Files are listed in directory order, lists are ordered by title
+fn sorted<T: HasSummary>(mut list: Vec<T>) -> Vec<T> {
+    list.sort_by(lipl_core::by_title);
+    list
+}
+
+/// Reading or removing the file of a missing item fails with not found
+fn not_found(uuid: impl Into<Uuid>) -> impl FnOnce(Error) -> Error {
+    let uuid = uuid.into();
+    move |error| match error {
+        Error::IOError(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            Error::NotFound(uuid)
+        }
+        error => error,
+    }
+}
+
+const LYRIC_REVISIONS: &str = "lyric";
+const PLAYLIST_REVISIONS: &str = "playlist";
+
+/// Path of the file with the revisions of an item, one json object per line
+fn revision_path(source_dir: &str, kind: &str, uuid: &impl Display) -> PathBuf {
+    Path::new(source_dir)
+        .join(REVISION_DIR)
+        .join(kind)
+        .full_path(&uuid.to_string(), REVISION_EXTENSION)
+}
+
+fn to_infos<T>(revisions: Vec<Revision<T>>) -> Vec<RevisionInfo> {
+    revisions
+        .into_iter()
+        .map(|revision| revision.info)
+        .collect()
+}
+
+/// Search index that is built on the first search and kept up to date on writes
+type SearchIndex = Arc<Mutex<Option<Index>>>;
+
+fn update_index<F>(index: &SearchIndex, f: F)
+where
+    F: FnOnce(&mut Index),
+{
+    if let Some(index) = index.lock().unwrap().as_mut() {
+        f(index);
+    }
+}
+
+/// Checks the precondition of a conditional request against the current item.
+/// Returns the unconditional request to execute or `None` if the precondition failed.
+fn on_precondition<T, U>(
+    current: Option<T>,
+    if_match: &str,
+    id: impl Into<Uuid>,
+    sender: ResultSender<U>,
+    request: impl FnOnce(ResultSender<U>) -> Request,
+) -> Option<Request>
+where
+    T: Etag,
+{
+    let id = id.into();
+    match check_etag(current.as_ref(), if_match, id) {
+        Ok(()) => Some(request(sender)),
+        Err(error) => {
+            if sender.send(Err(error)).is_err() {
+                tracing::error!("Error sending precondition failure for {id}");
+            }
+            None
+        }
+    }
+}
+
+/// Resolves conditional requests into unconditional requests.
+/// Requests are handled one at a time, so the item cannot change between check and write.
+async fn resolve_precondition<P, Q>(
+    request: Request,
+    lyric_path: P,
+    playlist_path: Q,
+) -> Option<Request>
+where
+    P: Fn(&LyricId) -> PathBuf,
+    Q: Fn(&PlaylistId) -> PathBuf,
+{
+    match request {
+        Request::LyricPostIfMatch(lyric, etag, sender) => {
+            let current = io::get_lyric(lyric_path(&lyric.id)).await.ok();
+            on_precondition(current, &etag, lyric.id, sender, |sender| {
+                Request::LyricPost(lyric, sender)
+            })
+        }
+        Request::LyricDeleteIfMatch(uuid, etag, sender) => {
+            let current = io::get_lyric(lyric_path(&uuid)).await.ok();
+            on_precondition(current, &etag, uuid, sender, |sender| {
+                Request::LyricDelete(uuid, sender)
+            })
+        }
+        Request::PlaylistPostIfMatch(playlist, etag, sender) => {
+            let current = io::get_playlist(playlist_path(&playlist.id)).await.ok();
+            on_precondition(current, &etag, playlist.id, sender, |sender| {
+                Request::PlaylistPost(playlist, sender)
+            })
+        }
+        Request::PlaylistDeleteIfMatch(uuid, etag, sender) => {
+            let current = io::get_playlist(playlist_path(&uuid)).await.ok();
+            on_precondition(current, &etag, uuid, sender, |sender| {
+                Request::PlaylistDelete(uuid, sender)
+            })
+        }
+        request => Some(request),
+    }
+}
+
 async fn handle_request<P, Q>(
     request: Request,
     source_dir: String,
     lyric_path: P,
     playlist_path: Q,
+    index: SearchIndex,
 ) -> Result<(), lipl_core::Error>
 where
-    P: Fn(&Uuid) -> PathBuf,
-    Q: Fn(&Uuid) -> PathBuf,
+    P: Fn(&LyricId) -> PathBuf,
+    Q: Fn(&PlaylistId) -> PathBuf,
 {
     match request {
         Request::Stop(sender) => {
@@ -101,13 +224,13 @@ where
         }
         Request::LyricSummaries(sender) => {
             io::get_list(&source_dir, LYRIC_EXTENSION, io::get_lyric_summary)
-                .err_into()
+                .map_ok(sorted)
                 .map(send(sender, "LyricSummaries"))
                 .await
         }
         Request::LyricList(sender) => {
             io::get_list(&source_dir, LYRIC_EXTENSION, io::get_lyric)
-                .err_into()
+                .map_ok(sorted)
                 .map(send(sender, "LyricList"))
                 .await
         }
@@ -119,7 +242,7 @@ where
         }
         Request::LyricItem(uuid, sender) => {
             io::get_lyric(lyric_path(&uuid))
-                .err_into()
+                .map_err(not_found(uuid))
                 .map(send(sender, format!("LyricItem {uuid}")))
                 .await
         }
@@ -127,41 +250,81 @@ where
             async {
                 let playlists = lyric_path(&uuid)
                     .remove()
+                    .map_err(not_found(uuid))
                     .and_then(|_| io::get_list(&source_dir, TOML_EXTENSION, io::get_playlist))
                     .await?;
+                update_index(&index, |index| index.remove(&uuid));
+                let mut changed = vec![];
                 for mut playlist in playlists {
                     if playlist.members.contains(&uuid) {
                         playlist.members = playlist.members.without(&uuid);
-                        io::post_item(
-                            source_dir.full_path(&uuid.to_string(), TOML_EXTENSION),
-                            playlist,
-                        )
-                        .await?;
+                        playlist.transpositions.remove(&uuid);
+                        io::post_item(playlist_path(&playlist.id), playlist.clone()).await?;
+                        changed.push(playlist);
                     }
                 }
-                Ok::<(), lipl_core::Error>(())
+                Ok::<Vec<Playlist>, lipl_core::Error>(changed)
             }
             .map(send(sender, format!("LyricDelete {uuid}")))
             .await
         }
         Request::LyricPost(lyric, sender) => {
             let path = lyric_path(&lyric.id);
-            io::post_item(&path, lyric.clone())
-                .and_then(|_| io::get_lyric(&path))
-                .err_into()
-                .map(send(sender, format!("LyricPost {}", lyric.title)))
+            async {
+                let previous = io::get_lyric(&path).await.ok();
+                io::post_item(&path, lyric.clone()).await?;
+                let lyric = io::get_lyric(&path).await?;
+                update_index(&index, |index| index.insert(&lyric));
+                io::post_revisions(
+                    revision_path(&source_dir, LYRIC_REVISIONS, &lyric.id),
+                    previous,
+                    lyric.clone(),
+                )
+                .await?;
+                Ok::<Lyric, lipl_core::Error>(lyric)
+            }
+            .map(send(sender, format!("LyricPost {}", lyric.title)))
+            .await
+        }
+        Request::LyricRevisions(uuid, sender) => {
+            io::get_revisions::<Lyric, _>(revision_path(&source_dir, LYRIC_REVISIONS, &uuid))
+                .map_ok(to_infos)
+                .map(send(sender, format!("LyricRevisions {uuid}")))
+                .await
+        }
+        Request::LyricRevision(uuid, revision, sender) => {
+            io::get_revisions(revision_path(&source_dir, LYRIC_REVISIONS, &uuid))
+                .map(|revisions| revisions.and_then(|r| find(r, uuid, revision)))
+                .map(send(sender, format!("LyricRevision {uuid} {revision}")))
                 .await
         }
+        Request::LyricSearch(query, sender) => {
+            async {
+                let built = index.lock().unwrap().is_some();
+                if !built {
+                    let lyrics = io::get_list(&source_dir, LYRIC_EXTENSION, io::get_lyric).await?;
+                    *index.lock().unwrap() = Some(lyrics.iter().collect());
+                }
+                Ok(index
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .map(|index| index.search(&query))
+                    .unwrap_or_default())
+            }
+            .map(send(sender, format!("LyricSearch {query}")))
+            .await
+        }
         Request::PlaylistSummaries(sender) => {
             io::get_list(&source_dir, TOML_EXTENSION, io::get_playlist)
+                .map_ok(sorted)
                 .map_ok(lipl_core::to_summaries)
-                .err_into()
                 .map(send(sender, "PlaylistSummaries"))
                 .await
         }
         Request::PlaylistList(sender) => {
             io::get_list(&source_dir, TOML_EXTENSION, io::get_playlist)
-                .err_into()
+                .map_ok(sorted)
                 .map(send(sender, "PlaylistList"))
                 .await
         }
@@ -173,79 +336,134 @@ where
         }
         Request::PlaylistItem(uuid, sender) => {
             io::get_playlist(playlist_path(&uuid))
-                .err_into()
+                .map_err(not_found(uuid))
                 .map(send(sender, format!("PlaylistItem {uuid}")))
                 .await
         }
         Request::PlaylistDelete(uuid, sender) => {
             let path = playlist_path(&uuid);
             path.remove()
-                .err_into()
+                .map_err(not_found(uuid))
                 .map(send(sender, format!("PlaylistDelete {uuid}")))
                 .await
         }
         Request::PlaylistPost(playlist, sender) => {
+            let path = playlist_path(&playlist.id);
+            let previous = io::get_playlist(&path).await.ok();
             io::get_list(&source_dir, LYRIC_EXTENSION, io::get_lyric_summary)
                 .map_ok(|summaries| lipl_core::ids(summaries.into_iter()))
                 .and_then(|ids| check_members(&playlist, &ids))
-                .and_then(|_| io::post_item(playlist_path(&playlist.id), playlist.clone()))
-                .and_then(|_| io::get_playlist(playlist_path(&playlist.id)))
-                .err_into()
+                .and_then(|_| io::post_item(&path, playlist.clone()))
+                .and_then(|_| io::get_playlist(&path))
+                .and_then(|playlist| async {
+                    io::post_revisions(
+                        revision_path(&source_dir, PLAYLIST_REVISIONS, &playlist.id),
+                        previous,
+                        playlist.clone(),
+                    )
+                    .await
+                    .map(|_| playlist)
+                })
                 .map(send(sender, format!("PlaylistPost {}", playlist.title)))
                 .await
         }
+        Request::PlaylistRevisions(uuid, sender) => {
+            io::get_revisions::<Playlist, _>(revision_path(&source_dir, PLAYLIST_REVISIONS, &uuid))
+                .map_ok(to_infos)
+                .map(send(sender, format!("PlaylistRevisions {uuid}")))
+                .await
+        }
+        Request::PlaylistRevision(uuid, revision, sender) => {
+            io::get_revisions(revision_path(&source_dir, PLAYLIST_REVISIONS, &uuid))
+                .map(|revisions| revisions.and_then(|r| find(r, uuid, revision)))
+                .map(send(sender, format!("PlaylistRevision {uuid} {revision}")))
+                .await
+        }
+        // Conditional requests are resolved before they are handled, one that is not fails instead of stopping the repo
+        Request::LyricDeleteIfMatch(uuid, _, sender) => {
+            send(sender, format!("LyricDeleteIfMatch {uuid}"))(Err(UNRESOLVED))
+        }
+        Request::LyricPostIfMatch(lyric, _, sender) => {
+            send(sender, format!("LyricPostIfMatch {}", lyric.title))(Err(UNRESOLVED))
+        }
+        Request::PlaylistDeleteIfMatch(uuid, _, sender) => {
+            send(sender, format!("PlaylistDeleteIfMatch {uuid}"))(Err(UNRESOLVED))
+        }
+        Request::PlaylistPostIfMatch(playlist, _, sender) => {
+            send(sender, format!("PlaylistPostIfMatch {}", playlist.title))(Err(UNRESOLVED))
+        }
     }
 }
 
-fn path(source_dir: String, extension: &'static str) -> impl Fn(&Uuid) -> PathBuf {
-    move |uuid| source_dir.full_path(&uuid.to_string(), extension)
+/// Path of the file of an item. A file named with the hyphenated id is used if it exists, new files are named in base 58
+fn path<I>(source_dir: String, extension: &'static str) -> impl Fn(&I) -> PathBuf
+where
+    I: Copy + Display + Into<Uuid>,
+{
+    move |id| {
+        let path = source_dir.full_path(&id.to_string(), extension);
+        if path.exists() {
+            return path;
+        }
+        let hyphenated =
+            source_dir.full_path(&(*id).into().inner().hyphenated().to_string(), extension);
+        if hyphenated.exists() {
+            hyphenated
+        } else {
+            path
+        }
+    }
 }
 
 impl FileRepo {
     pub fn new_streaming(source_dir: String) -> lipl_core::Result<Self> {
         Ok(Self {
             path: source_dir.clone(),
-            tx: mpsc::channel::<Request>(1).0,
+            tx: mpsc::channel::<Envelope>(1).0,
+            changes: Changes::default(),
             _join_handle: Arc::new(tokio::spawn(async move { true })),
         })
     }
 
     pub fn new(source_dir: String) -> lipl_core::Result<FileRepo> {
         let dir = source_dir.clone();
-        let (tx, rx) = mpsc::channel::<Request>(10);
-        let transaction_log: PathBuf = PathBuf::from(source_dir.clone()).join(".transaction.log");
-
-        let log = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(transaction_log)?;
-
-        let (_log_join_handle, log_tx) = start_log_thread(log);
+        let (tx, rx) = mpsc::channel::<Envelope>(10);
+        for kind in [LYRIC_REVISIONS, PLAYLIST_REVISIONS] {
+            std::fs::create_dir_all(Path::new(&source_dir).join(REVISION_DIR).join(kind))?;
+        }
 
+        let index = SearchIndex::default();
         let join_handle = tokio::spawn(async move {
-            rx.map(Ok)
-                .inspect_ok(move |request| {
-                    if let Some(transaction) = OptionalTransaction::from(request)
-                        && let Err(error) = log_tx.send(transaction)
-                    {
-                        tracing::error!("Error transaction logging: {error}");
-                    }
-                })
-                .try_for_each(|request| {
+            let precondition_dir = source_dir.clone();
+            rx.filter_map(move |(author, request)| {
+                resolve_precondition(
+                    request,
+                    path(precondition_dir.clone(), LYRIC_EXTENSION),
+                    path(precondition_dir.clone(), TOML_EXTENSION),
+                )
+                .map(|request| request.map(|request| (author, request)))
+            })
+            .map(Ok)
+            .try_for_each(|(author, request)| {
+                with_author(
+                    author,
                     handle_request(
                         request,
                         source_dir.clone(),
                         path(source_dir.clone(), LYRIC_EXTENSION),
                         path(source_dir.clone(), TOML_EXTENSION),
-                    )
-                })
-                .await
-                .is_ok()
+                        index.clone(),
+                    ),
+                )
+            })
+            .await
+            .is_ok()
         });
 
         let file_repo = FileRepo {
             path: dir,
             tx,
+            changes: Changes::default(),
             _join_handle: Arc::new(join_handle),
         };
 
@@ -280,7 +498,28 @@ impl Repo for FileRepo {
             .await
     }
 
-    async fn get_lyric(&self, id: Uuid) -> lipl_core::Result<Lyric> {
+    async fn get_lyrics_page(&self, page: PageRequest) -> lipl_core::Result<Page<Lyric>> {
+        let summaries = self.get_lyric_summaries_page(page).await?;
+        let mut lyrics = vec![];
+        for summary in &summaries.items {
+            lyrics.push(self.get_lyric(summary.id.into()).await?);
+        }
+        Ok(Page {
+            items: lyrics,
+            next: summaries.next,
+        })
+    }
+
+    async fn get_lyric_summaries_page(
+        &self,
+        page: PageRequest,
+    ) -> lipl_core::Result<Page<Summary>> {
+        self.get_lyric_summaries()
+            .map_ok(|summaries| paginate(summaries, &page))
+            .await
+    }
+
+    async fn get_lyric(&self, id: LyricId) -> lipl_core::Result<Lyric> {
         select_by_id(self.tx.clone(), id, Request::LyricItem)
             .err_into()
             .await
@@ -289,11 +528,60 @@ impl Repo for FileRepo {
     async fn upsert_lyric(&self, lyric: Lyric) -> lipl_core::Result<Lyric> {
         post(self.tx.clone(), lyric, Request::LyricPost)
             .err_into()
+            .inspect_ok(|lyric| self.changes.send(Change::lyric_upserted(lyric)))
+            .await
+    }
+
+    async fn delete_lyric(&self, id: LyricId) -> lipl_core::Result<()> {
+        select_by_id(self.tx.clone(), id, Request::LyricDelete)
+            .err_into()
+            .map_ok(|playlists| {
+                self.changes.send_all(Change::lyric_deleted(id, &playlists));
+            })
+            .await
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, etag: String) -> lipl_core::Result<Lyric> {
+        if_match(self.tx.clone(), lyric, etag, Request::LyricPostIfMatch)
+            .err_into()
+            .inspect_ok(|lyric| self.changes.send(Change::lyric_upserted(lyric)))
             .await
     }
 
-    async fn delete_lyric(&self, id: Uuid) -> lipl_core::Result<()> {
-        delete_by_id(self.tx.clone(), id, Request::LyricDelete)
+    async fn delete_lyric_if_match(&self, id: LyricId, etag: String) -> lipl_core::Result<()> {
+        if_match(self.tx.clone(), id, etag, Request::LyricDeleteIfMatch)
+            .err_into()
+            .map_ok(|playlists| {
+                self.changes.send_all(Change::lyric_deleted(id, &playlists));
+            })
+            .await
+    }
+
+    async fn get_lyric_revisions(&self, id: LyricId) -> lipl_core::Result<Vec<RevisionInfo>> {
+        select_by_id(self.tx.clone(), id, Request::LyricRevisions)
+            .err_into()
+            .await
+    }
+
+    async fn get_lyric_revision(
+        &self,
+        id: LyricId,
+        revision: u32,
+    ) -> lipl_core::Result<Revision<Lyric>> {
+        select_with(self.tx.clone(), (id, revision), |(id, revision), sender| {
+            Request::LyricRevision(id, revision, sender)
+        })
+        .err_into()
+        .await
+    }
+
+    async fn restore_lyric_revision(&self, id: LyricId, revision: u32) -> lipl_core::Result<Lyric> {
+        let revision = self.get_lyric_revision(id, revision).await?;
+        self.upsert_lyric(revision.item).await
+    }
+
+    async fn search(&self, query: &str) -> lipl_core::Result<Vec<Hit>> {
+        select_with(self.tx.clone(), query.to_owned(), Request::LyricSearch)
             .err_into()
             .await
     }
@@ -310,7 +598,22 @@ impl Repo for FileRepo {
             .await
     }
 
-    async fn get_playlist(&self, id: Uuid) -> lipl_core::Result<Playlist> {
+    async fn get_playlists_page(&self, page: PageRequest) -> lipl_core::Result<Page<Playlist>> {
+        self.get_playlists()
+            .map_ok(|playlists| paginate(playlists, &page))
+            .await
+    }
+
+    async fn get_playlist_summaries_page(
+        &self,
+        page: PageRequest,
+    ) -> lipl_core::Result<Page<Summary>> {
+        self.get_playlist_summaries()
+            .map_ok(|summaries| paginate(summaries, &page))
+            .await
+    }
+
+    async fn get_playlist(&self, id: PlaylistId) -> lipl_core::Result<Playlist> {
         select_by_id(self.tx.clone(), id, Request::PlaylistItem)
             .err_into()
             .await
@@ -319,15 +622,75 @@ impl Repo for FileRepo {
     async fn upsert_playlist(&self, playlist: Playlist) -> lipl_core::Result<Playlist> {
         post(self.tx.clone(), playlist, Request::PlaylistPost)
             .err_into()
+            .inspect_ok(|playlist| self.changes.send(Change::playlist_upserted(playlist)))
             .await
     }
 
-    async fn delete_playlist(&self, id: Uuid) -> lipl_core::Result<()> {
+    async fn delete_playlist(&self, id: PlaylistId) -> lipl_core::Result<()> {
         delete_by_id(self.tx.clone(), id, Request::PlaylistDelete)
             .err_into()
+            .inspect_ok(|()| self.changes.send(Change::PlaylistDeleted { id }))
+            .await
+    }
+
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        etag: String,
+    ) -> lipl_core::Result<Playlist> {
+        if_match(
+            self.tx.clone(),
+            playlist,
+            etag,
+            Request::PlaylistPostIfMatch,
+        )
+        .err_into()
+        .inspect_ok(|playlist| self.changes.send(Change::playlist_upserted(playlist)))
+        .await
+    }
+
+    async fn delete_playlist_if_match(
+        &self,
+        id: PlaylistId,
+        etag: String,
+    ) -> lipl_core::Result<()> {
+        if_match(self.tx.clone(), id, etag, Request::PlaylistDeleteIfMatch)
+            .err_into()
+            .inspect_ok(|()| self.changes.send(Change::PlaylistDeleted { id }))
             .await
     }
 
+    async fn get_playlist_revisions(&self, id: PlaylistId) -> lipl_core::Result<Vec<RevisionInfo>> {
+        select_by_id(self.tx.clone(), id, Request::PlaylistRevisions)
+            .err_into()
+            .await
+    }
+
+    async fn get_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> lipl_core::Result<Revision<Playlist>> {
+        select_with(self.tx.clone(), (id, revision), |(id, revision), sender| {
+            Request::PlaylistRevision(id, revision, sender)
+        })
+        .err_into()
+        .await
+    }
+
+    async fn restore_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> lipl_core::Result<Playlist> {
+        let revision = self.get_playlist_revision(id, revision).await?;
+        self.upsert_playlist(revision.item).await
+    }
+
+    async fn subscribe(&self) -> lipl_core::Result<ChangeStream> {
+        Ok(self.changes.subscribe())
+    }
+
     async fn stop(&self) -> lipl_core::Result<()> {
         select(self.tx.clone(), Request::Stop).err_into().await
     }
@@ -337,13 +700,15 @@ impl Repo for FileRepo {
 mod test {
     use std::path::PathBuf;
 
-    use super::FileRepo;
+    use super::{
+        Error, FileRepo, LYRIC_EXTENSION, PlaylistId, Request, SearchIndex, TOML_EXTENSION,
+        handle_request, path,
+    };
     use futures_util::TryStreamExt;
 
     fn data_dir() -> PathBuf {
         if let Ok(workspace) = std::env::var("WORKSPACE") {
-            let test_path = PathBuf::from(workspace).join("test");
-            test_path
+            PathBuf::from(workspace).join("test")
         } else {
             std::env::var("DATA_DIR").unwrap().into()
         }
@@ -360,6 +725,25 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn unresolved_precondition_fails_the_request() {
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        let request = Request::PlaylistDeleteIfMatch(PlaylistId::default(), String::new(), sender);
+        let source_dir = data_dir().to_string_lossy().to_string();
+
+        handle_request(
+            request,
+            source_dir.clone(),
+            path(source_dir.clone(), LYRIC_EXTENSION),
+            path(source_dir, TOML_EXTENSION),
+            SearchIndex::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(receiver.await, Ok(Err(Error::Argument(_)))));
+    }
+
     #[tokio::test]
     async fn test_get_playlist_stream() {
         let path = data_dir().to_string_lossy().to_string();
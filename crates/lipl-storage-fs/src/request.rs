@@ -1,12 +1,15 @@
-use crate::Uuid;
 use futures_channel::oneshot::Canceled;
 use futures_channel::{mpsc, oneshot};
 use lipl_core::Error;
+use lipl_core::revision::author;
 use lipl_core::transaction::Request;
 use std::fmt::Debug;
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Request together with the author of the task that sent it
+pub type Envelope = (Option<String>, Request);
+
 fn send_failed<E: std::error::Error>(error: E) -> Error {
     Error::SendFailed(error.to_string())
 }
@@ -16,40 +19,67 @@ fn canceled(error: Canceled) -> Error {
 }
 
 pub async fn select<T>(
-    mut tx: mpsc::Sender<Request>,
+    mut tx: mpsc::Sender<Envelope>,
     f: fn(oneshot::Sender<Result<T>>) -> Request,
 ) -> Result<T> {
     let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<T>>();
-    tx.try_send(f(oneshot_tx)).map_err(send_failed)?;
+    tx.try_send((author(), f(oneshot_tx)))
+        .map_err(send_failed)?;
     oneshot_rx.await.map_err(canceled)?
 }
 
-pub async fn select_by_id<T>(
-    mut tx: mpsc::Sender<Request>,
-    uuid: Uuid,
-    f: fn(Uuid, oneshot::Sender<Result<T>>) -> Request,
+pub async fn select_by_id<I, T>(
+    mut tx: mpsc::Sender<Envelope>,
+    uuid: I,
+    f: fn(I, oneshot::Sender<Result<T>>) -> Request,
 ) -> Result<T> {
     let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<T>>();
-    tx.try_send(f(uuid, oneshot_tx)).map_err(send_failed)?;
+    tx.try_send((author(), f(uuid, oneshot_tx)))
+        .map_err(send_failed)?;
     oneshot_rx.await.map_err(canceled)?
 }
 
-pub async fn delete_by_id(
-    mut tx: mpsc::Sender<Request>,
-    uuid: Uuid,
-    f: fn(Uuid, oneshot::Sender<Result<()>>) -> Request,
+pub async fn delete_by_id<I>(
+    mut tx: mpsc::Sender<Envelope>,
+    uuid: I,
+    f: fn(I, oneshot::Sender<Result<()>>) -> Request,
 ) -> Result<()> {
     let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<()>>();
-    tx.try_send(f(uuid, oneshot_tx)).map_err(send_failed)?;
+    tx.try_send((author(), f(uuid, oneshot_tx)))
+        .map_err(send_failed)?;
     oneshot_rx.await.map_err(canceled)?
 }
 
 pub async fn post<T: Debug>(
-    mut tx: mpsc::Sender<Request>,
+    mut tx: mpsc::Sender<Envelope>,
     t: T,
     f: fn(T, oneshot::Sender<Result<T>>) -> Request,
 ) -> Result<T> {
     let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<T>>();
-    tx.try_send(f(t, oneshot_tx)).map_err(send_failed)?;
+    tx.try_send((author(), f(t, oneshot_tx)))
+        .map_err(send_failed)?;
+    oneshot_rx.await.map_err(canceled)?
+}
+
+pub async fn select_with<T, U>(
+    mut tx: mpsc::Sender<Envelope>,
+    t: T,
+    f: fn(T, oneshot::Sender<Result<U>>) -> Request,
+) -> Result<U> {
+    let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<U>>();
+    tx.try_send((author(), f(t, oneshot_tx)))
+        .map_err(send_failed)?;
+    oneshot_rx.await.map_err(canceled)?
+}
+
+pub async fn if_match<T, U>(
+    mut tx: mpsc::Sender<Envelope>,
+    t: T,
+    etag: String,
+    f: fn(T, String, oneshot::Sender<Result<U>>) -> Request,
+) -> Result<U> {
+    let (oneshot_tx, oneshot_rx) = oneshot::channel::<Result<U>>();
+    tx.try_send((author(), f(t, etag, oneshot_tx)))
+        .map_err(send_failed)?;
     oneshot_rx.await.map_err(canceled)?
 }
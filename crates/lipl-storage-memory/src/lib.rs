@@ -1,26 +1,30 @@
 use lipl_core::Repo;
 use lipl_core::vec_ext::VecExt;
 use lipl_core::{
-    Error, HasSummary, Lyric, LyricPost, Playlist, PlaylistPost, RepoConfig, RepoDb, Result,
-    Summary, Toml, Uuid, by_title, reexport::toml,
+    Error, HasSummary, Lyric, LyricId, LyricPost, Page, PageRequest, Playlist, PlaylistId,
+    PlaylistPost, RepoConfig, RepoDb, Result, Revision, RevisionInfo, Summary, Toml, by_title,
+    change::{Change, ChangeStream, Changes},
+    check_etag,
+    page::paginate,
+    reexport::toml,
+    revision::History,
+    search::{Hit, Index},
+    transaction::recover,
+    with_bs58,
 };
 use std::io::read_to_string;
 use std::{
     collections::HashMap,
     iter::empty,
+    path::PathBuf,
     sync::{Arc, RwLock},
 };
 
-#[derive(Clone)]
-enum Record {
-    Lyric(LyricPost),
-    Playlist(PlaylistPost),
-}
-
 #[derive(Clone, Default)]
 pub struct MemoryRepoConfig {
     pub sample_data: bool,
-    pub transaction_log: Option<Arc<dyn std::io::Read + Send + Sync>>,
+    /// Transaction log that is replayed on startup, after its snapshot if there is one
+    pub transaction_log: Option<PathBuf>,
 }
 
 impl std::str::FromStr for MemoryRepoConfig {
@@ -47,18 +51,25 @@ impl std::str::FromStr for MemoryRepoConfig {
 impl RepoConfig for MemoryRepoConfig {
     type Repo = MemoryRepo;
     async fn to_repo(self) -> lipl_core::Result<Self::Repo> {
-        if self.sample_data {
-            let repo = lipl_sample_data::repo_db();
-            Ok(MemoryRepo::from(repo))
+        let repo = if self.sample_data {
+            MemoryRepo::from(lipl_sample_data::repo_db())
         } else {
-            Ok(MemoryRepo::new(empty(), empty()))
+            MemoryRepo::new(empty(), empty())
+        };
+        if let Some(path) = self.transaction_log {
+            recover(&path, repo.clone()).await?;
         }
+        Ok(repo)
     }
 }
 
 #[derive(Clone)]
 pub struct MemoryRepo {
-    db: Arc<RwLock<HashMap<Uuid, Record>>>,
+    db: Arc<RwLock<Db>>,
+    index: Arc<RwLock<Index>>,
+    lyric_history: Arc<RwLock<History<LyricId, Lyric>>>,
+    playlist_history: Arc<RwLock<History<PlaylistId, Playlist>>>,
+    changes: Changes,
 }
 
 impl From<RepoDb> for MemoryRepo {
@@ -67,12 +78,58 @@ impl From<RepoDb> for MemoryRepo {
     }
 }
 
-fn lyric_to_tuple(lyric: Lyric) -> (Uuid, Record) {
-    (lyric.id, Record::Lyric(lyric.into()))
+#[derive(Default)]
+struct Db {
+    lyrics: HashMap<LyricId, LyricPost>,
+    playlists: HashMap<PlaylistId, PlaylistPost>,
+}
+
+fn lyric_from(db: &Db, id: LyricId) -> Option<Lyric> {
+    db.lyrics
+        .get(&id)
+        .map(|lyric_post| Lyric::from((Some(id), lyric_post.clone())))
+}
+
+fn playlist_from(db: &Db, id: PlaylistId) -> Option<Playlist> {
+    db.playlists
+        .get(&id)
+        .map(|playlist_post| Playlist::from((Some(id), playlist_post.clone())))
 }
 
-fn playlist_to_tuple(playlist: Playlist) -> (Uuid, Record) {
-    (playlist.id, Record::Playlist(playlist.into()))
+/// Removes the lyric and returns the playlists it is removed from
+fn remove_lyric(db: &mut Db, id: LyricId) -> Result<Vec<Playlist>> {
+    db.lyrics.remove(&id).ok_or(Error::NotFound(id.into()))?;
+    Ok(db
+        .playlists
+        .iter_mut()
+        .filter(|(_, playlist_post)| playlist_post.members.contains(&id))
+        .map(|(playlist_id, playlist_post)| {
+            playlist_post.members = playlist_post.members.clone().without(&id);
+            playlist_post.transpositions.remove(&id);
+            Playlist::from((Some(*playlist_id), playlist_post.clone()))
+        })
+        .collect())
+}
+
+fn remove_playlist(db: &mut Db, id: PlaylistId) -> Result<()> {
+    db.playlists
+        .remove(&id)
+        .map(|_| ())
+        .ok_or(Error::NotFound(id.into()))
+}
+
+fn check_members(db: &Db, playlist: &Playlist) -> Result<()> {
+    match playlist
+        .members
+        .iter()
+        .find(|member| !db.lyrics.contains_key(member))
+    {
+        Some(member) => Err(Error::PlaylistInvalidMember(
+            playlist.id.to_string(),
+            member.to_string(),
+        )),
+        None => Ok(()),
+    }
 }
 
 impl MemoryRepo {
@@ -80,34 +137,62 @@ impl MemoryRepo {
         lyrics: impl Iterator<Item = Lyric>,
         playlists: impl Iterator<Item = Playlist>,
     ) -> Self {
+        let lyrics = lyrics.collect::<Vec<_>>();
+        let index = lyrics.iter().collect::<Index>();
         Self {
-            db: Arc::new(RwLock::new(HashMap::from_iter(
-                lyrics
-                    .map(lyric_to_tuple)
-                    .chain(playlists.map(playlist_to_tuple)),
-            ))),
+            db: Arc::new(RwLock::new(Db {
+                lyrics: lyrics
+                    .into_iter()
+                    .map(|lyric| (lyric.id, lyric.into()))
+                    .collect(),
+                playlists: playlists
+                    .map(|playlist| (playlist.id, playlist.into()))
+                    .collect(),
+            })),
+            index: Arc::new(RwLock::new(index)),
+            lyric_history: Arc::default(),
+            playlist_history: Arc::default(),
+            changes: Changes::default(),
         }
     }
 
-    fn to_repo_db(&self) -> RepoDb {
-        self.db
-            .read()
+    fn write_lyric(&self, db: &mut Db, lyric: &Lyric) {
+        let previous = lyric_from(db, lyric.id);
+        db.lyrics.insert(lyric.id, lyric.clone().into());
+        self.index.write().unwrap().insert(lyric);
+        self.lyric_history
+            .write()
             .unwrap()
-            .iter()
-            .fold(
-                (Vec::<Lyric>::new(), Vec::<Playlist>::new()),
-                |acc, (uuid, record)| match record {
-                    Record::Lyric(lyric_post) => (
-                        acc.0.add_one((Some(*uuid), lyric_post.clone()).into()),
-                        acc.1,
-                    ),
-                    Record::Playlist(playlist_post) => (
-                        acc.0,
-                        acc.1.add_one((Some(*uuid), playlist_post.clone()).into()),
-                    ),
-                },
-            )
-            .into()
+            .record(lyric.id, previous, lyric.clone());
+        self.changes.send(Change::lyric_upserted(lyric));
+    }
+
+    fn write_playlist(&self, db: &mut Db, playlist: &Playlist) -> Result<()> {
+        check_members(db, playlist)?;
+        let previous = playlist_from(db, playlist.id);
+        db.playlists.insert(playlist.id, playlist.clone().into());
+        self.playlist_history
+            .write()
+            .unwrap()
+            .record(playlist.id, previous, playlist.clone());
+        self.changes.send(Change::playlist_upserted(playlist));
+        Ok(())
+    }
+
+    fn to_repo_db(&self) -> RepoDb {
+        let db = self.db.read().unwrap();
+        RepoDb {
+            lyrics: db
+                .lyrics
+                .iter()
+                .map(|(id, lyric_post)| (Some(*id), lyric_post.clone()).into())
+                .collect(),
+            playlists: db
+                .playlists
+                .iter()
+                .map(|(id, playlist_post)| (Some(*id), playlist_post.clone()).into())
+                .collect(),
+        }
     }
 }
 
@@ -133,7 +218,7 @@ impl Toml for MemoryRepo {
     where
         W: std::io::Write,
     {
-        toml::ser::to_string_pretty(&self.to_repo_db())
+        with_bs58(|| toml::ser::to_string_pretty(&self.to_repo_db()))
             .map_err(Into::into)
             .and_then(|s| w.write_all(s.as_bytes()).map_err(Into::into))
     }
@@ -151,57 +236,76 @@ impl Repo for MemoryRepo {
             .db
             .read()
             .unwrap()
+            .lyrics
             .iter()
-            .filter_map(|(key, record)| {
-                if let Record::Lyric(lyric_post) = record {
-                    Some(Lyric::from((Some(*key), lyric_post.clone())))
-                } else {
-                    None
-                }
-            })
+            .map(|(id, lyric_post)| Lyric::from((Some(*id), lyric_post.clone())))
             .collect::<Vec<_>>();
 
         lyrics.sort_by(by_title);
         Ok(lyrics)
     }
 
-    async fn get_lyric(&self, uuid: Uuid) -> Result<Lyric> {
-        self.db
-            .read()
-            .unwrap()
-            .get(&uuid)
-            .and_then(|record| match record {
-                Record::Lyric(lyric_post) => Some(Lyric::from((Some(uuid), lyric_post.clone()))),
-                _ => None,
-            })
-            .ok_or(Error::NotFound(uuid))
+    async fn get_lyrics_page(&self, page: PageRequest) -> Result<Page<Lyric>> {
+        self.get_lyrics()
+            .await
+            .map(|lyrics| paginate(lyrics, &page))
+    }
+
+    async fn get_lyric_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.get_lyric_summaries()
+            .await
+            .map(|summaries| paginate(summaries, &page))
+    }
+
+    async fn get_lyric(&self, uuid: LyricId) -> Result<Lyric> {
+        lyric_from(&self.db.read().unwrap(), uuid).ok_or(Error::NotFound(uuid.into()))
     }
 
     async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
-        self.db
-            .write()
-            .unwrap()
-            .entry(lyric.clone().id)
-            .and_modify(|lyric_post| *lyric_post = Record::Lyric(lyric.clone().into()))
-            .or_insert_with(|| Record::Lyric(lyric.clone().into()));
+        self.write_lyric(&mut self.db.write().unwrap(), &lyric);
         Ok(lyric)
     }
 
-    async fn delete_lyric(&self, uuid: Uuid) -> Result<()> {
+    async fn delete_lyric(&self, uuid: LyricId) -> Result<()> {
+        let playlists = remove_lyric(&mut self.db.write().unwrap(), uuid)?;
+        self.index.write().unwrap().remove(&uuid);
+        self.changes
+            .send_all(Change::lyric_deleted(uuid, &playlists));
+        Ok(())
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, if_match: String) -> Result<Lyric> {
         let mut db = self.db.write().unwrap();
-        if db.remove(&uuid).is_some() {
-            db.iter_mut().for_each(|(_, record)| {
-                if let Record::Playlist(playlist_post) = record {
-                    *playlist_post = PlaylistPost {
-                        title: playlist_post.title.clone(),
-                        members: playlist_post.members.clone().without(&uuid),
-                    }
-                }
-            });
-            Ok(())
-        } else {
-            Err(Error::NotFound(uuid))
-        }
+        check_etag(lyric_from(&db, lyric.id).as_ref(), &if_match, lyric.id)?;
+        self.write_lyric(&mut db, &lyric);
+        Ok(lyric)
+    }
+
+    async fn delete_lyric_if_match(&self, uuid: LyricId, if_match: String) -> Result<()> {
+        let mut db = self.db.write().unwrap();
+        check_etag(lyric_from(&db, uuid).as_ref(), &if_match, uuid)?;
+        let playlists = remove_lyric(&mut db, uuid)?;
+        self.index.write().unwrap().remove(&uuid);
+        self.changes
+            .send_all(Change::lyric_deleted(uuid, &playlists));
+        Ok(())
+    }
+
+    async fn get_lyric_revisions(&self, id: LyricId) -> Result<Vec<RevisionInfo>> {
+        Ok(self.lyric_history.read().unwrap().list(&id))
+    }
+
+    async fn get_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Revision<Lyric>> {
+        self.lyric_history.read().unwrap().get(&id, revision)
+    }
+
+    async fn restore_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Lyric> {
+        let revision = self.get_lyric_revision(id, revision).await?;
+        self.upsert_lyric(revision.item).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        Ok(self.index.read().unwrap().search(query))
     }
 
     async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
@@ -215,50 +319,84 @@ impl Repo for MemoryRepo {
             .db
             .read()
             .unwrap()
+            .playlists
             .iter()
-            .filter_map(|(key, record)| match record {
-                Record::Playlist(playlist_post) => {
-                    Some(Playlist::from((Some(*key), playlist_post.clone())))
-                }
-                _ => None,
-            })
+            .map(|(id, playlist_post)| Playlist::from((Some(*id), playlist_post.clone())))
             .collect::<Vec<_>>();
 
         playlists.sort_by(by_title);
         Ok(playlists)
     }
 
-    async fn get_playlist(&self, uuid: Uuid) -> Result<Playlist> {
-        self.db
-            .read()
-            .unwrap()
-            .get(&uuid)
-            .and_then(|record| match record {
-                Record::Playlist(playlist_post) => {
-                    Some(Playlist::from((Some(uuid), playlist_post.clone())))
-                }
-                _ => None,
-            })
-            .ok_or(Error::NotFound(uuid))
+    async fn get_playlists_page(&self, page: PageRequest) -> Result<Page<Playlist>> {
+        self.get_playlists()
+            .await
+            .map(|playlists| paginate(playlists, &page))
+    }
+
+    async fn get_playlist_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.get_playlist_summaries()
+            .await
+            .map(|summaries| paginate(summaries, &page))
+    }
+
+    async fn get_playlist(&self, uuid: PlaylistId) -> Result<Playlist> {
+        playlist_from(&self.db.read().unwrap(), uuid).ok_or(Error::NotFound(uuid.into()))
     }
 
     async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
-        self.db
-            .write()
-            .unwrap()
-            .entry(playlist.clone().id)
-            .and_modify(|record| *record = Record::Playlist(playlist.clone().into()))
-            .or_insert_with(|| Record::Playlist(playlist.clone().into()));
+        self.write_playlist(&mut self.db.write().unwrap(), &playlist)?;
         Ok(playlist)
     }
 
-    async fn delete_playlist(&self, uuid: Uuid) -> Result<()> {
-        self.db
-            .write()
-            .unwrap()
-            .remove(&uuid)
-            .ok_or(Error::NotFound(uuid))
-            .map(|_| ())
+    async fn delete_playlist(&self, uuid: PlaylistId) -> Result<()> {
+        remove_playlist(&mut self.db.write().unwrap(), uuid)?;
+        self.changes.send(Change::PlaylistDeleted { id: uuid });
+        Ok(())
+    }
+
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        if_match: String,
+    ) -> Result<Playlist> {
+        let mut db = self.db.write().unwrap();
+        check_etag(
+            playlist_from(&db, playlist.id).as_ref(),
+            &if_match,
+            playlist.id,
+        )?;
+        self.write_playlist(&mut db, &playlist)?;
+        Ok(playlist)
+    }
+
+    async fn delete_playlist_if_match(&self, uuid: PlaylistId, if_match: String) -> Result<()> {
+        let mut db = self.db.write().unwrap();
+        check_etag(playlist_from(&db, uuid).as_ref(), &if_match, uuid)?;
+        remove_playlist(&mut db, uuid)?;
+        self.changes.send(Change::PlaylistDeleted { id: uuid });
+        Ok(())
+    }
+
+    async fn get_playlist_revisions(&self, id: PlaylistId) -> Result<Vec<RevisionInfo>> {
+        Ok(self.playlist_history.read().unwrap().list(&id))
+    }
+
+    async fn get_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> Result<Revision<Playlist>> {
+        self.playlist_history.read().unwrap().get(&id, revision)
+    }
+
+    async fn restore_playlist_revision(&self, id: PlaylistId, revision: u32) -> Result<Playlist> {
+        let revision = self.get_playlist_revision(id, revision).await?;
+        self.upsert_playlist(revision.item).await
+    }
+
+    async fn subscribe(&self) -> Result<ChangeStream> {
+        Ok(self.changes.subscribe())
     }
 
     async fn stop(&self) -> Result<()> {
@@ -269,7 +407,9 @@ impl Repo for MemoryRepo {
 #[cfg(test)]
 mod tests {
     use super::MemoryRepo;
-    use lipl_core::{LyricPost, PlaylistPost, Repo};
+    use lipl_core::{
+        Error, Etag, LyricPost, Metadata, PageRequest, PlaylistPost, Repo, Transpositions,
+    };
 
     #[tokio::test(flavor = "multi_thread")]
     async fn post_lyric() {
@@ -277,6 +417,7 @@ mod tests {
 
         let lyric_post = LyricPost {
             title: "Alle 13 goed".to_owned(),
+            metadata: Metadata::default(),
             parts: vec![],
         };
 
@@ -293,6 +434,7 @@ mod tests {
 
         let lyric_post = LyricPost {
             title: "Alle 13 goed".to_owned(),
+            metadata: Metadata::default(),
             parts: vec![],
         };
 
@@ -315,6 +457,7 @@ mod tests {
         let playlist_post = PlaylistPost {
             title: "Alle 13 goed".to_owned(),
             members: vec![],
+            transpositions: Transpositions::default(),
         };
 
         let playlist = db
@@ -326,4 +469,96 @@ mod tests {
         assert_eq!(playlists[0].title, "Alle 13 goed".to_owned());
         assert_eq!(playlists[0].id, playlist.id);
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lyric_summaries_page() {
+        let db = MemoryRepo::from(lipl_sample_data::repo_db());
+        let all = db.get_lyric_summaries().await.unwrap();
+
+        let mut cursor = None;
+        let mut paged = vec![];
+        loop {
+            let page = db
+                .get_lyric_summaries_page(PageRequest::new(10, cursor))
+                .await
+                .unwrap();
+            paged.extend(page.items);
+            cursor = page.next;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(paged.len(), all.len());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn search_after_change() {
+        let db = MemoryRepo::default();
+
+        let mut lyric = db
+            .upsert_lyric(LyricPost::from(("Roodkapje", "Zeg roodkapje waar ga je hene")).into())
+            .await
+            .unwrap();
+        assert_eq!(db.search("hene").await.unwrap()[0].id, lyric.id);
+
+        lyric.parts = vec![vec!["'k ga naar grootmoeder".to_owned()]];
+        db.upsert_lyric(lyric.clone()).await.unwrap();
+        assert!(db.search("hene").await.unwrap().is_empty());
+        assert_eq!(db.search("ik ga").await.unwrap()[0].id, lyric.id);
+
+        db.delete_lyric(lyric.id).await.unwrap();
+        assert!(db.search("grootmoeder").await.unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn upsert_lyric_if_match() {
+        let db = MemoryRepo::default();
+
+        let mut lyric = db
+            .upsert_lyric(LyricPost::from(("Roodkapje", "Zeg roodkapje")).into())
+            .await
+            .unwrap();
+        let etag = lyric.etag().unwrap();
+
+        lyric.title = "Roodkapje 2".to_owned();
+        db.upsert_lyric_if_match(lyric.clone(), etag.clone())
+            .await
+            .unwrap();
+
+        lyric.title = "Roodkapje 3".to_owned();
+        assert!(matches!(
+            db.upsert_lyric_if_match(lyric.clone(), etag.clone()).await,
+            Err(Error::PreconditionFailed(_))
+        ));
+        assert!(matches!(
+            db.delete_lyric_if_match(lyric.id, etag).await,
+            Err(Error::PreconditionFailed(_))
+        ));
+        assert_eq!(db.get_lyric(lyric.id).await.unwrap().title, "Roodkapje 2");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn restore_deleted_lyric() {
+        let db = MemoryRepo::default();
+
+        let mut lyric = db
+            .upsert_lyric(LyricPost::from(("Roodkapje", "Zeg roodkapje")).into())
+            .await
+            .unwrap();
+        lyric.title = "Roodkapje 2".to_owned();
+        db.upsert_lyric(lyric.clone()).await.unwrap();
+        db.delete_lyric(lyric.id).await.unwrap();
+
+        let revisions = db.get_lyric_revisions(lyric.id).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+
+        let restored = db.restore_lyric_revision(lyric.id, 1).await.unwrap();
+        assert_eq!(restored.title, "Roodkapje");
+        assert_eq!(db.get_lyric(lyric.id).await.unwrap().title, "Roodkapje");
+        assert_eq!(db.get_lyric_revisions(lyric.id).await.unwrap().len(), 3);
+        assert!(matches!(
+            db.restore_lyric_revision(lyric.id, 4).await,
+            Err(Error::RevisionNotFound(_, 4))
+        ));
+    }
 }
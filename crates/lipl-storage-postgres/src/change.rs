@@ -0,0 +1,49 @@
+use futures_channel::mpsc;
+use futures_util::{StreamExt, future::ready, stream};
+use lipl_core::{
+    Error, Result,
+    change::{Change, ChangeStream},
+    postgres_error, with_bs58,
+};
+use tokio_postgres::{AsyncMessage, NoTls, Transaction};
+
+use crate::PostgresConnectionPool;
+
+const NOTIFY: &str = "SELECT pg_notify('lipl_change', $1);";
+const LISTEN: &str = "LISTEN lipl_change;";
+
+/// Notifies every listener on the database of `change` when `transaction` is committed.
+/// Listeners get only the changes that are stored, in the order in which they are committed.
+pub(crate) async fn notify(transaction: &Transaction<'_>, change: &Change) -> Result<()> {
+    let payload =
+        with_bs58(|| serde_json::to_string(change)).map_err(|e| Error::Json(Box::new(e)))?;
+    transaction
+        .execute(NOTIFY, &[&payload])
+        .await
+        .map_err(postgres_error)
+        .map(|_| ())
+}
+
+impl PostgresConnectionPool {
+    /// Listens on a connection of its own, that is closed when the stream is dropped
+    pub(crate) async fn listen(&self) -> Result<ChangeStream> {
+        let (client, mut connection) = self.config.connect(NoTls).await.map_err(postgres_error)?;
+        let (sender, receiver) = mpsc::unbounded();
+        tokio::spawn(
+            stream::poll_fn(move |cx| connection.poll_message(cx))
+                .map(Ok)
+                .forward(sender),
+        );
+        client.batch_execute(LISTEN).await.map_err(postgres_error)?;
+        Ok(Box::pin(receiver.filter_map(move |message| {
+            // The connection is open as long as the client
+            let _listening = &client;
+            ready(match message {
+                Ok(AsyncMessage::Notification(notification)) => {
+                    serde_json::from_str(notification.payload()).ok()
+                }
+                _ => None,
+            })
+        })))
+    }
+}
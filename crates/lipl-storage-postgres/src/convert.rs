@@ -1,5 +1,9 @@
+use lipl_core::search::{Hit, snippet};
 use lipl_core::vec_ext::VecExt;
-use lipl_core::{Lyric, Playlist, Result, Summary, Uuid, postgres_error, reexport};
+use lipl_core::{
+    Error, Lyric, LyricId, Metadata, Playlist, Result, Revision, RevisionInfo, Summary,
+    postgres_error, reexport, transpose::to_transpositions,
+};
 use tokio_postgres::Row;
 
 pub fn to_list<F, T>(f: F) -> impl Fn(Vec<Row>) -> Result<Vec<T>>
@@ -9,6 +13,39 @@ where
     move |rows| rows.try_map(f)
 }
 
+fn get_opt_string(row: &Row, column: &str) -> Result<Option<String>> {
+    row.try_get::<&str, Option<String>>(column)
+        .map_err(postgres_error)
+}
+
+fn get_opt_number<N, T>(row: &Row, column: &str) -> Result<Option<T>>
+where
+    N: for<'a> tokio_postgres::types::FromSql<'a> + Copy + std::fmt::Display,
+    T: TryFrom<N>,
+{
+    row.try_get::<&str, Option<N>>(column)
+        .map_err(postgres_error)?
+        .map(|number| T::try_from(number).map_err(|_| Error::Parse(format!("{column} {number}"))))
+        .transpose()
+}
+
+fn to_metadata(row: &Row) -> Result<Metadata> {
+    Ok(Metadata {
+        subtitle: get_opt_string(row, column::SUB_TITLE)?,
+        lyricist: get_opt_string(row, column::LYRICIST)?,
+        composer: get_opt_string(row, column::COMPOSER)?,
+        key: get_opt_string(row, column::MUSICAL_KEY)?,
+        tempo: get_opt_number::<i32, _>(row, column::TEMPO)?,
+        copyright: get_opt_string(row, column::COPYRIGHT)?,
+        ccli: get_opt_number::<i64, _>(row, column::CCLI)?,
+        language: get_opt_string(row, column::LANGUAGE)?,
+        tags: row
+            .try_get::<&str, Option<Vec<String>>>(column::TAGS)
+            .map_err(postgres_error)?
+            .unwrap_or_default(),
+    })
+}
+
 pub fn to_lyric(row: Row) -> Result<Lyric> {
     Ok(Lyric {
         id: row
@@ -18,6 +55,7 @@ pub fn to_lyric(row: Row) -> Result<Lyric> {
         title: row
             .try_get::<&str, String>(column::TITLE)
             .map_err(postgres_error)?,
+        metadata: to_metadata(&row)?,
         parts: lipl_core::parts::to_parts(
             &row.try_get::<&str, String>(column::PARTS)
                 .map_err(postgres_error)?,
@@ -25,7 +63,26 @@ pub fn to_lyric(row: Row) -> Result<Lyric> {
     })
 }
 
+/// Id, title and parts of a lyric row, to fill its search text
+pub fn to_search_source(row: &Row) -> Result<(reexport::uuid::Uuid, String, Vec<Vec<String>>)> {
+    Ok((
+        row.try_get::<&str, reexport::uuid::Uuid>(column::ID)
+            .map_err(postgres_error)?,
+        row.try_get::<&str, String>(column::TITLE)
+            .map_err(postgres_error)?,
+        lipl_core::parts::to_parts(
+            &row.try_get::<&str, String>(column::PARTS)
+                .map_err(postgres_error)?,
+        ),
+    ))
+}
+
 pub fn to_playlist(row: Row) -> Result<Playlist> {
+    let members = row
+        .try_get::<&str, Option<Vec<reexport::uuid::Uuid>>>(column::MEMBERS)
+        .map_err(postgres_error)?
+        .unwrap_or_default()
+        .map(LyricId::from);
     Ok(Playlist {
         id: row
             .try_get::<&str, reexport::uuid::Uuid>(column::ID)
@@ -34,11 +91,11 @@ pub fn to_playlist(row: Row) -> Result<Playlist> {
         title: row
             .try_get::<&str, String>(column::TITLE)
             .map_err(postgres_error)?,
-        members: row
-            .try_get::<&str, Option<Vec<reexport::uuid::Uuid>>>(column::MEMBERS)
-            .map_err(postgres_error)?
-            .unwrap_or_default()
-            .map(Uuid::from),
+        transpositions: get_opt_string(&row, column::TRANSPOSITIONS)?
+            .map(|text| to_transpositions(text, &members))
+            .transpose()?
+            .unwrap_or_default(),
+        members,
     })
 }
 
@@ -54,8 +111,52 @@ pub fn to_summary(row: Row) -> Result<Summary> {
     })
 }
 
-pub fn to_inner(uuid: Uuid) -> reexport::uuid::Uuid {
-    uuid.inner()
+pub fn to_hit(query: &str) -> impl Fn(Row) -> Result<Hit> + Copy + '_ {
+    move |row| {
+        let lyric = to_lyric(row.clone())?;
+        Ok(Hit {
+            id: lyric.id,
+            snippet: snippet(&lyric.parts, query),
+            title: lyric.title,
+            score: row
+                .try_get::<&str, f32>(column::RANK)
+                .map_err(postgres_error)?,
+        })
+    }
+}
+
+pub fn to_revision_info(row: &Row) -> Result<RevisionInfo> {
+    let revision = row
+        .try_get::<&str, i32>(column::REVISION)
+        .map_err(postgres_error)?;
+    Ok(RevisionInfo {
+        revision: u32::try_from(revision)
+            .map_err(|_| Error::Parse(format!("revision {revision}")))?,
+        timestamp: row
+            .try_get::<&str, String>(column::TIMESTAMP)
+            .map_err(postgres_error)?,
+        author: row
+            .try_get::<&str, Option<String>>(column::AUTHOR)
+            .map_err(postgres_error)?,
+    })
+}
+
+pub fn to_lyric_revision(row: Row) -> Result<Revision<Lyric>> {
+    Ok(Revision {
+        info: to_revision_info(&row)?,
+        item: to_lyric(row)?,
+    })
+}
+
+pub fn to_playlist_revision(row: Row) -> Result<Revision<Playlist>> {
+    Ok(Revision {
+        info: to_revision_info(&row)?,
+        item: to_playlist(row)?,
+    })
+}
+
+pub fn to_inner(id: LyricId) -> reexport::uuid::Uuid {
+    id.inner()
 }
 
 mod column {
@@ -63,4 +164,18 @@ mod column {
     pub const PARTS: &str = "parts";
     pub const TITLE: &str = "title";
     pub const MEMBERS: &str = "members";
+    pub const RANK: &str = "rank";
+    pub const REVISION: &str = "revision";
+    pub const TIMESTAMP: &str = "timestamp";
+    pub const AUTHOR: &str = "author";
+    pub const SUB_TITLE: &str = "sub_title";
+    pub const LYRICIST: &str = "lyricist";
+    pub const COMPOSER: &str = "composer";
+    pub const MUSICAL_KEY: &str = "musical_key";
+    pub const TEMPO: &str = "tempo";
+    pub const COPYRIGHT: &str = "copyright";
+    pub const CCLI: &str = "ccli";
+    pub const LANGUAGE: &str = "language";
+    pub const TAGS: &str = "tags";
+    pub const TRANSPOSITIONS: &str = "transpositions";
 }
@@ -1,15 +1,57 @@
 CREATE TABLE IF NOT EXISTS lyric (
     id UUID PRIMARY KEY,
-    title VARCHAR UNIQUE NOT NULL,
+    title VARCHAR NOT NULL,
     sub_title VARCHAR,
+    lyricist VARCHAR,
+    composer VARCHAR,
+    musical_key VARCHAR,
+    tempo INTEGER,
+    copyright VARCHAR,
+    ccli BIGINT,
+    language VARCHAR,
+    tags VARCHAR[],
     parts VARCHAR
 );
 
+ALTER TABLE lyric
+    ADD COLUMN IF NOT EXISTS lyricist VARCHAR,
+    ADD COLUMN IF NOT EXISTS composer VARCHAR,
+    ADD COLUMN IF NOT EXISTS musical_key VARCHAR,
+    ADD COLUMN IF NOT EXISTS tempo INTEGER,
+    ADD COLUMN IF NOT EXISTS copyright VARCHAR,
+    ADD COLUMN IF NOT EXISTS ccli BIGINT,
+    ADD COLUMN IF NOT EXISTS language VARCHAR,
+    ADD COLUMN IF NOT EXISTS tags VARCHAR[];
+
+-- Titles are unique according to the title rule, with an index created by the server
+ALTER TABLE lyric DROP CONSTRAINT IF EXISTS lyric_title_key;
+
+-- Words of the title and of the parts, normalized by the server with lipl_core::search
+ALTER TABLE lyric
+    ADD COLUMN IF NOT EXISTS search_title VARCHAR,
+    ADD COLUMN IF NOT EXISTS search_parts VARCHAR;
+
+-- The search of older versions normalized in SQL
+ALTER TABLE lyric DROP COLUMN IF EXISTS search;
+DROP FUNCTION IF EXISTS lipl_normalize(text);
+
+ALTER TABLE lyric ADD COLUMN IF NOT EXISTS search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', COALESCE(search_title, '')), 'A') ||
+    setweight(to_tsvector('simple', COALESCE(search_parts, '')), 'D')
+) STORED;
+
+CREATE INDEX IF NOT EXISTS lyric_search_vector ON lyric USING GIN (search_vector);
+
 CREATE TABLE IF NOT EXISTS playlist (
     id UUID PRIMARY KEY,
-    title VARCHAR UNIQUE NOT NULL
+    title VARCHAR NOT NULL,
+    transpositions VARCHAR
 );
 
+ALTER TABLE playlist ADD COLUMN IF NOT EXISTS transpositions VARCHAR;
+
+ALTER TABLE playlist DROP CONSTRAINT IF EXISTS playlist_title_key;
+
 CREATE TABLE IF NOT EXISTS member (
     id SERIAL PRIMARY KEY,
     lyric_id UUID NOT NULL REFERENCES lyric ON DELETE CASCADE,
@@ -21,6 +63,49 @@ CREATE INDEX IF NOT EXISTS member_lyric_id ON member (lyric_id);
 
 CREATE INDEX IF NOT EXISTS member_playlist_id ON member (playlist_id);
 
+CREATE TABLE IF NOT EXISTS lyric_revision (
+    lyric_id UUID NOT NULL,
+    revision INTEGER NOT NULL,
+    timestamp VARCHAR NOT NULL,
+    author VARCHAR,
+    title VARCHAR NOT NULL,
+    sub_title VARCHAR,
+    lyricist VARCHAR,
+    composer VARCHAR,
+    musical_key VARCHAR,
+    tempo INTEGER,
+    copyright VARCHAR,
+    ccli BIGINT,
+    language VARCHAR,
+    tags VARCHAR[],
+    parts VARCHAR NOT NULL,
+    PRIMARY KEY (lyric_id, revision)
+);
+
+ALTER TABLE lyric_revision
+    ADD COLUMN IF NOT EXISTS sub_title VARCHAR,
+    ADD COLUMN IF NOT EXISTS lyricist VARCHAR,
+    ADD COLUMN IF NOT EXISTS composer VARCHAR,
+    ADD COLUMN IF NOT EXISTS musical_key VARCHAR,
+    ADD COLUMN IF NOT EXISTS tempo INTEGER,
+    ADD COLUMN IF NOT EXISTS copyright VARCHAR,
+    ADD COLUMN IF NOT EXISTS ccli BIGINT,
+    ADD COLUMN IF NOT EXISTS language VARCHAR,
+    ADD COLUMN IF NOT EXISTS tags VARCHAR[];
+
+CREATE TABLE IF NOT EXISTS playlist_revision (
+    playlist_id UUID NOT NULL,
+    revision INTEGER NOT NULL,
+    timestamp VARCHAR NOT NULL,
+    author VARCHAR,
+    title VARCHAR NOT NULL,
+    members UUID[] NOT NULL,
+    transpositions VARCHAR,
+    PRIMARY KEY (playlist_id, revision)
+);
+
+ALTER TABLE playlist_revision ADD COLUMN IF NOT EXISTS transpositions VARCHAR;
+
 CREATE OR REPLACE FUNCTION fn_upsert_lyric(new_id uuid, new_title text, new_parts text)
 RETURNS TABLE (
     id uuid,
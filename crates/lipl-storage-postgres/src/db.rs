@@ -1,25 +1,304 @@
 use futures_util::TryFutureExt;
 use lipl_core::vec_ext::VecExt;
-use lipl_core::{Error, Lyric, Playlist, Repo, Result, Summary, Uuid, parts::to_text};
+use lipl_core::{
+    Error, Etag, Lyric, LyricId, Page, PageRequest, Playlist, PlaylistId, Repo, Result, Revision,
+    RevisionInfo, Summary, Uuid,
+    change::{Change, ChangeStream},
+    check_etag,
+    parts::to_text,
+    postgres_error, revision,
+    search::{Hit, document_text, tokens},
+    transpose::transpositions_to_text,
+    validate::not_unique,
+};
+use tokio_postgres::{Row, Transaction, error::SqlState};
 
+use super::change::notify;
 use super::convert;
 use crate::PostgresConnectionPool;
 
-fn error_on_count(count: u64, uuid: Uuid) -> Result<()> {
+fn error_on_count(count: u64, uuid: impl Into<Uuid>) -> Result<()> {
     if count < 1 {
-        Err(Error::NoKey(uuid.to_string()))
+        Err(Error::NotFound(uuid.into()))
     } else {
         Ok(())
     }
 }
 
-fn pg_error_to_lipl_core(uuid: Uuid) -> impl Fn(Error) -> lipl_core::Error {
+/// A write that stores a title that is already used violates the unique index on the titles.
+/// It fails with a `not-unique` error for the title, instead of a database error.
+fn title_error(title: &str) -> impl FnOnce(tokio_postgres::Error) -> Error + '_ {
+    move |error| {
+        if error.code() == Some(&SqlState::UNIQUE_VIOLATION)
+            && error
+                .as_db_error()
+                .and_then(|db_error| db_error.constraint())
+                .is_some_and(|constraint| constraint.contains("_title_unique"))
+        {
+            Error::Validation(vec![not_unique(title)])
+        } else {
+            postgres_error(error)
+        }
+    }
+}
+
+fn not_found(uuid: impl Into<Uuid>) -> impl Fn(Error) -> lipl_core::Error {
+    let uuid = uuid.into();
+    move |pg_error| match pg_error {
+        Error::NoResults => Error::NotFound(uuid),
+        _ => pg_error,
+    }
+}
+
+fn page_params(
+    page: &PageRequest,
+) -> (Option<String>, Option<lipl_core::reexport::uuid::Uuid>, i64) {
+    (
+        page.cursor.as_ref().map(|cursor| cursor.title.clone()),
+        page.cursor.as_ref().map(|cursor| cursor.id.inner()),
+        i64::try_from(page.fetch_limit()).unwrap_or(i64::MAX),
+    )
+}
+
+fn revision_not_found(uuid: impl Into<Uuid>, revision: u32) -> impl Fn(Error) -> lipl_core::Error {
+    let uuid = uuid.into();
     move |pg_error| match pg_error {
-        Error::NoResults => Error::NoKey(uuid.to_string()),
+        Error::NoResults => Error::RevisionNotFound(uuid, revision),
         _ => pg_error,
     }
 }
 
+fn revision_param(uuid: impl Into<Uuid>, revision: u32) -> Result<i32> {
+    i32::try_from(revision).map_err(|_| Error::RevisionNotFound(uuid.into(), revision))
+}
+
+/// Locks the row with id `id` using `lock` and checks the entity tag of the item selected by `read` against `if_match`.
+async fn check_if_match<C, U>(
+    transaction: &Transaction<'_>,
+    id: impl Into<Uuid>,
+    if_match: &str,
+    lock: &str,
+    read: (&str, C),
+) -> Result<()>
+where
+    C: Fn(Row) -> Result<U>,
+    U: Etag,
+{
+    let id = id.into();
+    transaction
+        .execute(lock, &[&id.inner()])
+        .await
+        .map_err(postgres_error)?;
+    let current = transaction
+        .query_opt(read.0, &[&id.inner()])
+        .await
+        .map_err(postgres_error)?
+        .map(read.1)
+        .transpose()?;
+    check_etag(current.as_ref(), if_match, id)
+}
+
+/// Deletes the item with id `uuid` using `sql` and notifies `change`
+async fn delete(
+    transaction: &Transaction<'_>,
+    sql: &str,
+    uuid: impl Into<Uuid>,
+    change: Change,
+) -> Result<()> {
+    let uuid = uuid.into();
+    let count = transaction
+        .execute(sql, &[&uuid.inner()])
+        .await
+        .map_err(postgres_error)?;
+    error_on_count(count, uuid)?;
+    notify(transaction, &change).await
+}
+
+/// Deletes the lyric with id `id`, which removes it from the playlists.
+/// The deletion is notified, followed by the playlists as they are now.
+async fn delete_lyric(transaction: &Transaction<'_>, id: LyricId) -> Result<()> {
+    let playlist_ids = transaction
+        .query(lyric::PLAYLISTS, &[&id.inner()])
+        .await
+        .map_err(postgres_error)?
+        .into_iter()
+        .map(|row| row.try_get::<_, lipl_core::reexport::uuid::Uuid>(0))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(postgres_error)?;
+    let count = transaction
+        .execute(lyric::DELETE, &[&id.inner()])
+        .await
+        .map_err(postgres_error)?;
+    error_on_count(count, id)?;
+    let mut playlists = vec![];
+    for playlist_id in playlist_ids {
+        let row = transaction
+            .query_one(playlist::ITEM, &[&playlist_id])
+            .await
+            .map_err(postgres_error)?;
+        playlists.push(convert::to_playlist(row)?);
+    }
+    for change in Change::lyric_deleted(id, &playlists) {
+        notify(transaction, &change).await?;
+    }
+    Ok(())
+}
+
+/// Normalized words of the title and of the parts, see `lipl_core::search`
+fn search_text(title: &str, parts: &[Vec<String>]) -> (String, String) {
+    (document_text(title, &[]), document_text("", parts))
+}
+
+/// Upserts `lyric` with id `id`, records a revision and notifies the change.
+/// The lyric as it was before is recorded first if the lyric has no revisions yet.
+async fn write_lyric(transaction: &Transaction<'_>, id: LyricId, lyric: &Lyric) -> Result<Lyric> {
+    let timestamp = revision::now();
+    transaction
+        .execute(lyric::LOCK, &[&id.inner()])
+        .await
+        .map_err(postgres_error)?;
+    transaction
+        .execute(lyric::BASELINE, &[&id.inner(), &timestamp])
+        .await
+        .map_err(postgres_error)?;
+    let statement = transaction
+        .prepare_typed(lyric::UPSERT, lyric::UPSERT_TYPES)
+        .await
+        .map_err(postgres_error)?;
+    let metadata = &lyric.metadata;
+    let (search_title, search_parts) = search_text(&lyric.title, &lyric.parts);
+    let row = transaction
+        .query_one(
+            &statement,
+            &[
+                &id.inner(),
+                &lyric.title,
+                &to_text(&lyric.parts),
+                &metadata.subtitle,
+                &metadata.lyricist,
+                &metadata.composer,
+                &metadata.key,
+                &metadata.tempo.map(i32::from),
+                &metadata.copyright,
+                &metadata.ccli.map(i64::from),
+                &metadata.language,
+                &(!metadata.tags.is_empty()).then_some(&metadata.tags),
+                &search_title,
+                &search_parts,
+            ],
+        )
+        .await
+        .map_err(title_error(&lyric.title))?;
+    let lyric = convert::to_lyric(row)?;
+    transaction
+        .execute(
+            lyric::REVISION,
+            &[&lyric.id.inner(), &timestamp, &revision::author()],
+        )
+        .await
+        .map_err(postgres_error)?;
+    notify(transaction, &Change::lyric_upserted(&lyric)).await?;
+    Ok(lyric)
+}
+
+/// Upserts `playlist` with id `id`, records a revision and notifies the change.
+/// The playlist as it was before is recorded first if the playlist has no revisions yet.
+async fn write_playlist(
+    transaction: &Transaction<'_>,
+    id: PlaylistId,
+    playlist: &Playlist,
+) -> Result<Playlist> {
+    if let Some(row) = transaction
+        .query_opt(
+            playlist::INVALID_MEMBER,
+            &[&playlist.members.clone().map(convert::to_inner).as_slice()],
+        )
+        .await
+        .map_err(postgres_error)?
+    {
+        let member = row
+            .try_get::<_, lipl_core::reexport::uuid::Uuid>(0)
+            .map_err(postgres_error)?;
+        return Err(Error::PlaylistInvalidMember(
+            id.to_string(),
+            LyricId::from(member).to_string(),
+        ));
+    }
+    let timestamp = revision::now();
+    let transpositions = (!playlist.transpositions.is_empty())
+        .then(|| transpositions_to_text(&playlist.transpositions));
+    transaction
+        .execute(playlist::LOCK, &[&id.inner()])
+        .await
+        .map_err(postgres_error)?;
+    transaction
+        .execute(playlist::BASELINE, &[&id.inner(), &timestamp])
+        .await
+        .map_err(postgres_error)?;
+    let statement = transaction
+        .prepare_typed(playlist::UPSERT, playlist::UPSERT_TYPES)
+        .await
+        .map_err(postgres_error)?;
+    let row = transaction
+        .query_one(
+            &statement,
+            &[
+                &id.inner(),
+                &playlist.title,
+                &playlist.members.clone().map(convert::to_inner).as_slice(),
+                &transpositions,
+            ],
+        )
+        .await
+        .map_err(title_error(&playlist.title))?;
+    transaction
+        .execute(
+            playlist::SET_TRANSPOSITIONS,
+            &[&id.inner(), &transpositions],
+        )
+        .await
+        .map_err(postgres_error)?;
+    let playlist = convert::to_playlist(row)?;
+    transaction
+        .execute(
+            playlist::REVISION,
+            &[
+                &playlist.id.inner(),
+                &timestamp,
+                &revision::author(),
+                &playlist.title,
+                &playlist.members.clone().map(convert::to_inner).as_slice(),
+                &transpositions,
+            ],
+        )
+        .await
+        .map_err(postgres_error)?;
+    notify(transaction, &Change::playlist_upserted(&playlist)).await?;
+    Ok(playlist)
+}
+
+impl PostgresConnectionPool {
+    /// Fills the search text of lyrics stored before it was added
+    pub(crate) async fn fill_search_text(&self) -> Result<()> {
+        self.in_transaction(async |transaction| {
+            let rows = transaction
+                .query(lyric::WITHOUT_SEARCH_TEXT, &[])
+                .await
+                .map_err(postgres_error)?;
+            for row in rows {
+                let (id, title, parts) = convert::to_search_source(&row)?;
+                let (search_title, search_parts) = search_text(&title, &parts);
+                transaction
+                    .execute(lyric::SEARCH_TEXT, &[&id, &search_title, &search_parts])
+                    .await
+                    .map_err(postgres_error)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
 impl Repo for PostgresConnectionPool {
     async fn get_lyric_summaries(&self) -> Result<Vec<Summary>> {
         self.query(lyric::LIST, lyric::LIST_TYPES, convert::to_summary, &[])
@@ -38,37 +317,111 @@ impl Repo for PostgresConnectionPool {
         .await
     }
 
-    async fn get_lyric(&self, uuid: Uuid) -> Result<Lyric> {
+    async fn get_lyrics_page(&self, page: PageRequest) -> Result<Page<Lyric>> {
+        let (title, id, limit) = page_params(&page);
+        self.query(
+            lyric::LIST_FULL_PAGE,
+            lyric::PAGE_TYPES,
+            convert::to_lyric,
+            &[&title, &id, &limit],
+        )
+        .map_ok(|lyrics| Page::from_fetched(lyrics, &page))
+        .await
+    }
+
+    async fn get_lyric_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        let (title, id, limit) = page_params(&page);
+        self.query(
+            lyric::LIST_PAGE,
+            lyric::PAGE_TYPES,
+            convert::to_summary,
+            &[&title, &id, &limit],
+        )
+        .map_ok(|summaries| Page::from_fetched(summaries, &page))
+        .await
+    }
+
+    async fn get_lyric(&self, uuid: LyricId) -> Result<Lyric> {
         self.query_one(
             lyric::ITEM,
             lyric::ITEM_TYPES,
             convert::to_lyric,
             &[&uuid.inner()],
         )
-        .map_err(pg_error_to_lipl_core(uuid))
+        .map_err(not_found(uuid))
         .await
     }
 
     async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
+        self.in_transaction(async |transaction| write_lyric(transaction, lyric.id, &lyric).await)
+            .await
+    }
+
+    async fn delete_lyric(&self, uuid: LyricId) -> Result<()> {
+        self.in_transaction(async |transaction| delete_lyric(transaction, uuid).await)
+            .await
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, if_match: String) -> Result<Lyric> {
+        self.in_transaction(async |transaction| {
+            let read = (lyric::ITEM, convert::to_lyric);
+            check_if_match(transaction, lyric.id, &if_match, lyric::LOCK, read).await?;
+            write_lyric(transaction, lyric.id, &lyric).await
+        })
+        .await
+    }
+
+    async fn delete_lyric_if_match(&self, uuid: LyricId, if_match: String) -> Result<()> {
+        self.in_transaction(async |transaction| {
+            let read = (lyric::ITEM, convert::to_lyric);
+            check_if_match(transaction, uuid, &if_match, lyric::LOCK, read).await?;
+            delete_lyric(transaction, uuid).await
+        })
+        .await
+    }
+
+    async fn get_lyric_revisions(&self, uuid: LyricId) -> Result<Vec<RevisionInfo>> {
+        self.query(
+            lyric::REVISIONS,
+            lyric::REVISIONS_TYPES,
+            |row| convert::to_revision_info(&row),
+            &[&uuid.inner()],
+        )
+        .await
+    }
+
+    async fn get_lyric_revision(&self, uuid: LyricId, revision: u32) -> Result<Revision<Lyric>> {
         self.query_one(
-            lyric::UPSERT,
-            lyric::UPSERT_TYPES,
-            convert::to_lyric,
-            &[
-                &Uuid::default().inner(),
-                &lyric.title.clone(),
-                &to_text(&lyric.parts),
-            ],
+            lyric::REVISION_ITEM,
+            lyric::REVISION_ITEM_TYPES,
+            convert::to_lyric_revision,
+            &[&uuid.inner(), &revision_param(uuid, revision)?],
         )
-        .err_into()
+        .map_err(revision_not_found(uuid, revision))
         .await
     }
 
-    async fn delete_lyric(&self, uuid: Uuid) -> Result<()> {
-        let count = self
-            .execute(lyric::DELETE, lyric::DELETE_TYPES, &[&uuid.inner()])
-            .await?;
-        error_on_count(count, uuid)
+    async fn restore_lyric_revision(&self, uuid: LyricId, revision: u32) -> Result<Lyric> {
+        let revision = self.get_lyric_revision(uuid, revision).await?;
+        self.in_transaction(async |transaction| {
+            write_lyric(transaction, uuid, &revision.item).await
+        })
+        .await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        let tokens = tokens(query);
+        if tokens.is_empty() {
+            return Ok(vec![]);
+        }
+        self.query(
+            lyric::SEARCH,
+            lyric::SEARCH_TYPES,
+            convert::to_hit(query),
+            &[&tokens.join(" & ")],
+        )
+        .err_into()
+        .await
     }
 
     async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
@@ -93,39 +446,116 @@ impl Repo for PostgresConnectionPool {
         .await
     }
 
-    async fn get_playlist(&self, uuid: Uuid) -> Result<Playlist> {
+    async fn get_playlists_page(&self, page: PageRequest) -> Result<Page<Playlist>> {
+        let (title, id, limit) = page_params(&page);
+        self.query(
+            playlist::LIST_FULL_PAGE,
+            playlist::PAGE_TYPES,
+            convert::to_playlist,
+            &[&title, &id, &limit],
+        )
+        .map_ok(|playlists| Page::from_fetched(playlists, &page))
+        .await
+    }
+
+    async fn get_playlist_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        let (title, id, limit) = page_params(&page);
+        self.query(
+            playlist::LIST_PAGE,
+            playlist::PAGE_TYPES,
+            convert::to_summary,
+            &[&title, &id, &limit],
+        )
+        .map_ok(|summaries| Page::from_fetched(summaries, &page))
+        .await
+    }
+
+    async fn get_playlist(&self, uuid: PlaylistId) -> Result<Playlist> {
         self.query_one(
             playlist::ITEM,
             playlist::ITEM_TYPES,
             convert::to_playlist,
             &[&uuid.inner()],
         )
-        .map_err(pg_error_to_lipl_core(uuid))
+        .map_err(not_found(uuid))
         .await
     }
 
-    async fn delete_playlist(&self, uuid: Uuid) -> Result<()> {
-        let count = self
-            .execute(playlist::DELETE, playlist::DELETE_TYPES, &[&uuid.inner()])
-            .await?;
-        error_on_count(count, uuid)
+    async fn delete_playlist(&self, uuid: PlaylistId) -> Result<()> {
+        self.in_transaction(async |transaction| {
+            let change = Change::PlaylistDeleted { id: uuid };
+            delete(transaction, playlist::DELETE, uuid, change).await
+        })
+        .await
     }
 
     async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
+        self.in_transaction(async |transaction| {
+            write_playlist(transaction, playlist.id, &playlist).await
+        })
+        .await
+    }
+
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        if_match: String,
+    ) -> Result<Playlist> {
+        self.in_transaction(async |transaction| {
+            let read = (playlist::ITEM, convert::to_playlist);
+            check_if_match(transaction, playlist.id, &if_match, playlist::LOCK, read).await?;
+            write_playlist(transaction, playlist.id, &playlist).await
+        })
+        .await
+    }
+
+    async fn delete_playlist_if_match(&self, uuid: PlaylistId, if_match: String) -> Result<()> {
+        self.in_transaction(async |transaction| {
+            let read = (playlist::ITEM, convert::to_playlist);
+            check_if_match(transaction, uuid, &if_match, playlist::LOCK, read).await?;
+            let change = Change::PlaylistDeleted { id: uuid };
+            delete(transaction, playlist::DELETE, uuid, change).await
+        })
+        .await
+    }
+
+    async fn get_playlist_revisions(&self, uuid: PlaylistId) -> Result<Vec<RevisionInfo>> {
+        self.query(
+            playlist::REVISIONS,
+            playlist::REVISIONS_TYPES,
+            |row| convert::to_revision_info(&row),
+            &[&uuid.inner()],
+        )
+        .await
+    }
+
+    async fn get_playlist_revision(
+        &self,
+        uuid: PlaylistId,
+        revision: u32,
+    ) -> Result<Revision<Playlist>> {
         self.query_one(
-            playlist::UPSERT,
-            playlist::UPSERT_TYPES,
-            convert::to_playlist,
-            &[
-                &Uuid::default().inner(),
-                &playlist.title.clone(),
-                &playlist.members.map(convert::to_inner).as_slice(),
-            ],
+            playlist::REVISION_ITEM,
+            playlist::REVISION_ITEM_TYPES,
+            convert::to_playlist_revision,
+            &[&uuid.inner(), &revision_param(uuid, revision)?],
         )
-        .err_into()
+        .map_err(revision_not_found(uuid, revision))
         .await
     }
 
+    async fn restore_playlist_revision(&self, uuid: PlaylistId, revision: u32) -> Result<Playlist> {
+        let revision = self.get_playlist_revision(uuid, revision).await?;
+        self.in_transaction(async |transaction| {
+            write_playlist(transaction, uuid, &revision.item).await
+        })
+        .await
+    }
+
+    async fn subscribe(&self) -> Result<ChangeStream> {
+        self.listen().await
+    }
+
     async fn stop(&self) -> Result<()> {
         Ok(())
     }
@@ -134,20 +564,108 @@ impl Repo for PostgresConnectionPool {
 mod lyric {
     use tokio_postgres::types::Type;
 
+    /// Columns read by `convert::to_lyric`, besides id, title and parts
+    macro_rules! metadata {
+        () => {
+            "sub_title, lyricist, composer, musical_key, tempo, copyright, ccli, language, tags"
+        };
+    }
+
     pub const LIST: &str = "SELECT id, title FROM lyric ORDER BY title;";
     pub const LIST_TYPES: &[Type] = &[];
 
-    pub const LIST_FULL: &str = "SELECT id, title, parts FROM lyric ORDER BY title;";
+    pub const LIST_FULL: &str = concat!(
+        "SELECT id, title, parts, ",
+        metadata!(),
+        " FROM lyric ORDER BY title;"
+    );
     pub const LIST_FULL_TYPES: &[Type] = &[];
 
-    pub const ITEM: &str = "SELECT * FROM lyric WHERE id = $1;";
+    pub const LIST_PAGE: &str = "SELECT id, title FROM lyric WHERE $1::VARCHAR IS NULL OR (title, id) > ($1, $2) ORDER BY title, id LIMIT $3;";
+    pub const LIST_FULL_PAGE: &str = concat!(
+        "SELECT id, title, parts, ",
+        metadata!(),
+        " FROM lyric WHERE $1::VARCHAR IS NULL OR (title, id) > ($1, $2) ORDER BY title, id LIMIT $3;"
+    );
+    pub const PAGE_TYPES: &[Type] = &[Type::VARCHAR, Type::UUID, Type::INT8];
+
+    pub const ITEM: &str = concat!(
+        "SELECT id, title, parts, ",
+        metadata!(),
+        " FROM lyric WHERE id = $1;"
+    );
     pub const ITEM_TYPES: &[Type] = &[Type::UUID];
 
+    pub const LOCK: &str = "SELECT id FROM lyric WHERE id = $1 FOR UPDATE;";
+
     pub const DELETE: &str = "DELETE FROM lyric WHERE id = $1;";
-    pub const DELETE_TYPES: &[Type] = &[Type::UUID];
 
-    pub const UPSERT: &str = "SELECT * from fn_upsert_lyric($1, $2, $3)";
-    pub const UPSERT_TYPES: &[Type] = &[Type::UUID, Type::VARCHAR, Type::VARCHAR];
+    /// Playlists that have the lyric as member
+    pub const PLAYLISTS: &str = "SELECT DISTINCT playlist_id FROM member WHERE lyric_id = $1;";
+
+    pub const SEARCH: &str = concat!(
+        "SELECT id, title, parts, ",
+        metadata!(),
+        ", ts_rank(search_vector, query) AS rank FROM lyric, to_tsquery('simple', $1) AS query WHERE search_vector @@ query ORDER BY rank DESC, title;"
+    );
+    pub const SEARCH_TYPES: &[Type] = &[Type::VARCHAR];
+
+    pub const UPSERT: &str = concat!(
+        "INSERT INTO lyric (id, title, parts, ",
+        metadata!(),
+        ", search_title, search_parts) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) ON CONFLICT (id) DO UPDATE SET title = $2, parts = $3, sub_title = $4, lyricist = $5, composer = $6, musical_key = $7, tempo = $8, copyright = $9, ccli = $10, language = $11, tags = $12, search_title = $13, search_parts = $14 RETURNING id, title, parts, ",
+        metadata!(),
+        ";"
+    );
+    pub const UPSERT_TYPES: &[Type] = &[
+        Type::UUID,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::VARCHAR,
+        Type::INT4,
+        Type::VARCHAR,
+        Type::INT8,
+        Type::VARCHAR,
+        Type::VARCHAR_ARRAY,
+        Type::VARCHAR,
+        Type::VARCHAR,
+    ];
+
+    /// Lyrics stored before the search text was added
+    pub const WITHOUT_SEARCH_TEXT: &str =
+        "SELECT id, title, COALESCE(parts, '') AS parts FROM lyric WHERE search_title IS NULL;";
+    pub const SEARCH_TEXT: &str =
+        "UPDATE lyric SET search_title = $2, search_parts = $3 WHERE id = $1;";
+
+    /// Copies the lyric as it is now to revision 1, if the lyric has no revisions yet
+    pub const BASELINE: &str = concat!(
+        "INSERT INTO lyric_revision (lyric_id, revision, timestamp, author, title, parts, ",
+        metadata!(),
+        ") SELECT id, 1, $2::VARCHAR, NULL, title, COALESCE(parts, ''), ",
+        metadata!(),
+        " FROM lyric WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM lyric_revision WHERE lyric_id = $1);"
+    );
+    /// Copies the lyric as it is now to the next revision
+    pub const REVISION: &str = concat!(
+        "INSERT INTO lyric_revision (lyric_id, revision, timestamp, author, title, parts, ",
+        metadata!(),
+        ") SELECT id, (SELECT COALESCE(MAX(revision), 0) + 1 FROM lyric_revision WHERE lyric_id = $1), $2::VARCHAR, $3::VARCHAR, title, COALESCE(parts, ''), ",
+        metadata!(),
+        " FROM lyric WHERE id = $1;"
+    );
+
+    pub const REVISIONS: &str = "SELECT revision, timestamp, author FROM lyric_revision WHERE lyric_id = $1 ORDER BY revision;";
+    pub const REVISIONS_TYPES: &[Type] = &[Type::UUID];
+
+    pub const REVISION_ITEM: &str = concat!(
+        "SELECT revision, timestamp, author, lyric_id AS id, title, parts, ",
+        metadata!(),
+        " FROM lyric_revision WHERE lyric_id = $1 AND revision = $2;"
+    );
+    pub const REVISION_ITEM_TYPES: &[Type] = &[Type::UUID, Type::INT4];
 }
 
 mod playlist {
@@ -156,15 +674,33 @@ mod playlist {
     pub const LIST: &str = "SELECT id, title FROM playlist ORDER BY title;";
     pub const LIST_TYPES: &[Type] = &[];
 
-    pub const LIST_FULL: &str = "SELECT playlist.id AS id, title, ARRAY_AGG(lyric_id ORDER BY ordering) members FROM playlist INNER JOIN member ON playlist.id = playlist_id GROUP BY playlist.id ORDER BY playlist.title;";
+    pub const LIST_FULL: &str = "SELECT playlist.id AS id, title, ARRAY_REMOVE(ARRAY_AGG(lyric_id ORDER BY ordering), NULL) members, transpositions FROM playlist LEFT JOIN member ON playlist.id = playlist_id GROUP BY playlist.id ORDER BY playlist.title;";
     pub const LIST_FULL_TYPES: &[Type] = &[];
 
-    pub const ITEM: &str = "SELECT playlist.id AS id, title, ARRAY_AGG(lyric_id ORDER BY ordering) members FROM playlist INNER JOIN member ON playlist.id = playlist_id GROUP BY playlist.id HAVING playlist.id = $1";
+    pub const LIST_PAGE: &str = "SELECT id, title FROM playlist WHERE $1::VARCHAR IS NULL OR (title, id) > ($1, $2) ORDER BY title, id LIMIT $3;";
+    pub const LIST_FULL_PAGE: &str = "SELECT playlist.id AS id, title, ARRAY_REMOVE(ARRAY_AGG(lyric_id ORDER BY ordering), NULL) members, transpositions FROM playlist LEFT JOIN member ON playlist.id = playlist_id WHERE $1::VARCHAR IS NULL OR (title, playlist.id) > ($1, $2) GROUP BY playlist.id ORDER BY playlist.title, playlist.id LIMIT $3;";
+    pub const PAGE_TYPES: &[Type] = &[Type::VARCHAR, Type::UUID, Type::INT8];
+
+    pub const ITEM: &str = "SELECT playlist.id AS id, title, ARRAY_REMOVE(ARRAY_AGG(lyric_id ORDER BY ordering), NULL) members, transpositions FROM playlist LEFT JOIN member ON playlist.id = playlist_id WHERE playlist.id = $1 GROUP BY playlist.id;";
     pub const ITEM_TYPES: &[Type] = &[Type::UUID];
 
+    pub const LOCK: &str = "SELECT id FROM playlist WHERE id = $1 FOR UPDATE;";
+
+    /// First member that is not a lyric
+    pub const INVALID_MEMBER: &str = "SELECT member FROM UNNEST($1::UUID[]) AS member WHERE NOT EXISTS (SELECT 1 FROM lyric WHERE id = member) LIMIT 1;";
+
     pub const DELETE: &str = "DELETE FROM playlist WHERE id = $1;";
-    pub const DELETE_TYPES: &[Type] = &[Type::UUID];
 
-    pub const UPSERT: &str = "SELECT * from fn_upsert_playlist($1, $2, $3);";
-    pub const UPSERT_TYPES: &[Type] = &[Type::UUID, Type::VARCHAR, Type::UUID_ARRAY];
+    pub const UPSERT: &str = "SELECT id, title, members, $4::VARCHAR AS transpositions FROM fn_upsert_playlist($1, $2, $3);";
+    pub const UPSERT_TYPES: &[Type] = &[Type::UUID, Type::VARCHAR, Type::UUID_ARRAY, Type::VARCHAR];
+    pub const SET_TRANSPOSITIONS: &str = "UPDATE playlist SET transpositions = $2 WHERE id = $1;";
+
+    pub const BASELINE: &str = "INSERT INTO playlist_revision (playlist_id, revision, timestamp, author, title, members, transpositions) SELECT id, 1, $2::VARCHAR, NULL, title, ARRAY(SELECT lyric_id FROM member WHERE playlist_id = $1 ORDER BY ordering), transpositions FROM playlist WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM playlist_revision WHERE playlist_id = $1);";
+    pub const REVISION: &str = "INSERT INTO playlist_revision (playlist_id, revision, timestamp, author, title, members, transpositions) SELECT $1, COALESCE(MAX(revision), 0) + 1, $2::VARCHAR, $3::VARCHAR, $4::VARCHAR, $5::UUID[], $6::VARCHAR FROM playlist_revision WHERE playlist_id = $1;";
+
+    pub const REVISIONS: &str = "SELECT revision, timestamp, author FROM playlist_revision WHERE playlist_id = $1 ORDER BY revision;";
+    pub const REVISIONS_TYPES: &[Type] = &[Type::UUID];
+
+    pub const REVISION_ITEM: &str = "SELECT revision, timestamp, author, playlist_id AS id, title, members, transpositions FROM playlist_revision WHERE playlist_id = $1 AND revision = $2;";
+    pub const REVISION_ITEM_TYPES: &[Type] = &[Type::UUID, Type::INT4];
 }
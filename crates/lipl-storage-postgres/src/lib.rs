@@ -1,12 +1,15 @@
+use std::str::FromStr;
+
 use bb8_postgres::{PostgresConnectionManager, bb8::Pool};
 use futures_util::TryFutureExt;
-use lipl_core::{Error, Repo, RepoConfig, Result, postgres_error};
+use lipl_core::{Error, Repo, RepoConfig, Result, postgres_error, validate::TitleRule};
 use serde::Serialize;
 use tokio_postgres::{
-    NoTls, Row,
+    Config, NoTls, Row, Transaction,
     types::{ToSql, Type},
 };
 
+mod change;
 mod convert;
 mod db;
 
@@ -14,34 +17,47 @@ pub type ConnectionPool = Pool<PostgresConnectionManager<NoTls>>;
 
 pub const CREATE_DB: &str = include_str!("create_db.sql");
 
+/// Names of the unique indexes on the titles, by title rule
+const TITLE_INDEXES: [(TitleRule, &str, &str); 2] = [
+    (TitleRule::Unique, "title_unique", "trim(title)"),
+    (
+        TitleRule::UniqueIgnoreCase,
+        "title_unique_ignore_case",
+        "lower(trim(title))",
+    ),
+];
+
+/// Creates the unique index on the titles that `titles` requires and drops the others,
+/// so concurrent writes cannot store the same title
+fn title_indexes(titles: TitleRule) -> String {
+    let mut sql = String::new();
+    for table in ["lyric", "playlist"] {
+        for (rule, name, expression) in TITLE_INDEXES {
+            if rule == titles {
+                sql.push_str(&format!(
+                    "CREATE UNIQUE INDEX IF NOT EXISTS {table}_{name} ON {table} ({expression});\n"
+                ));
+            } else {
+                sql.push_str(&format!("DROP INDEX IF EXISTS {table}_{name};\n"));
+            }
+        }
+    }
+    sql
+}
+
 #[derive(Clone)]
 pub struct PostgresConnectionPool {
     inner: ConnectionPool,
-}
-
-impl From<ConnectionPool> for PostgresConnectionPool {
-    fn from(pool: ConnectionPool) -> Self {
-        Self { inner: pool }
-    }
+    /// Used to open the connection that listens for changes
+    config: Config,
 }
 
 impl PostgresConnectionPool {
-    async fn execute<'a>(
-        &'a self,
-        sql: &'static str,
-        types: &'a [Type],
-        params: &'a [&(dyn ToSql + Sync)],
-    ) -> Result<u64> {
-        let connection = self.inner.get().await.map_err(postgres_error)?;
-        let statement = connection
-            .prepare_typed(sql, types)
-            .await
-            .map_err(postgres_error)?;
-        let count = connection
-            .execute(&statement, params)
-            .await
-            .map_err(postgres_error)?;
-        Ok(count)
+    pub fn new(pool: ConnectionPool, config: Config) -> Self {
+        Self {
+            inner: pool,
+            config,
+        }
     }
 
     async fn batch_execute(&self, sql: &str) -> Result<()> {
@@ -97,40 +113,73 @@ impl PostgresConnectionPool {
             _ => Err(Error::NoResults),
         }
     }
+
+    /// Executes `f` in a transaction that is committed when `f` succeeds.
+    async fn in_transaction<T, F>(&self, f: F) -> Result<T>
+    where
+        F: AsyncFnOnce(&Transaction<'_>) -> Result<T>,
+    {
+        let mut connection = self.inner.get().await.map_err(postgres_error)?;
+        let transaction = connection.transaction().await.map_err(postgres_error)?;
+        let result = f(&transaction).await?;
+        transaction.commit().await.map_err(postgres_error)?;
+        Ok(result)
+    }
 }
 
 pub struct PostgresConfig {
     connection_string: String,
+    titles: TitleRule,
 }
 
 impl From<String> for PostgresConfig {
     fn from(connection_string: String) -> Self {
-        PostgresConfig { connection_string }
+        PostgresConfig {
+            connection_string,
+            titles: TitleRule::default(),
+        }
+    }
+}
+
+impl PostgresConfig {
+    /// Sets the title rule that the unique index on the titles enforces, `Unique` by default
+    #[must_use]
+    pub fn with_titles(self, titles: TitleRule) -> Self {
+        Self { titles, ..self }
     }
 }
 
 impl RepoConfig for PostgresConfig {
     type Repo = PostgresConnectionPool;
     async fn to_repo(self) -> Result<Self::Repo> {
-        connection_pool(&self.connection_string).await
+        connection_pool(&self.connection_string, self.titles).await
     }
 }
 
-pub async fn connection_pool(connection: &str) -> Result<PostgresConnectionPool> {
-    let manager = PostgresConnectionManager::new_from_stringlike(connection, NoTls)
-        .map_err(postgres_error)?;
+/// Connects to the database and creates or upgrades the schema.
+/// Fails if the stored titles break `titles`.
+pub async fn connection_pool(
+    connection: &str,
+    titles: TitleRule,
+) -> Result<PostgresConnectionPool> {
+    let config = Config::from_str(connection).map_err(postgres_error)?;
+    let manager = PostgresConnectionManager::new(config.clone(), NoTls);
 
     let pool = Pool::builder()
         .build(manager)
         .await
         .map_err(postgres_error)
         .unwrap();
-    let postgres_connection_pool = PostgresConnectionPool::from(pool);
+    let postgres_connection_pool = PostgresConnectionPool::new(pool, config);
     tracing::info!("About to execute database creation script");
     postgres_connection_pool
         .batch_execute(CREATE_DB)
         .await
         .unwrap();
+    postgres_connection_pool.fill_search_text().await?;
+    postgres_connection_pool
+        .batch_execute(&title_indexes(titles))
+        .await?;
     tracing::info!("Finished executing database creation script");
 
     tracing::info!("Warm up cache");
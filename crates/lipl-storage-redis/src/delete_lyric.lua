@@ -1,4 +1,10 @@
+local lyric_key = table.concat({'lyric', ARGV[1]}, ':')
+if redis.call('EXISTS', lyric_key) == 0 then
+    return false
+end
+
 local playlists = redis.call('KEYS', 'playlist:*')
+local updated = {}
 
 for i,playlist_key in ipairs(playlists) do
     local members = {}
@@ -12,10 +18,10 @@ for i,playlist_key in ipairs(playlists) do
     end
     if needs_update then
         redis.call('HSET', playlist_key, 'members', table.concat(members, ' '))
+        table.insert(updated, playlist_key)
     end
 end
 
-local lyric_key = table.concat({'lyric', ARGV[1]}, ':')
 redis.call('DEL', lyric_key)
-
-return
+redis.call('PUBLISH', ARGV[2], ARGV[3])
+return updated
@@ -1,20 +1,22 @@
-use lipl_core::{Lyric, Playlist, Uuid, parts::to_parts};
+use lipl_core::{Lyric, LyricId, Metadata, Playlist, PlaylistId, Transpositions, parts::to_parts};
 pub use redis_repo::RedisRepoConfig;
 
 pub mod redis_repo;
 
 pub fn new_lyric(title: &str, text: &str) -> Lyric {
     Lyric {
-        id: Uuid::default(),
+        id: LyricId::default(),
         title: title.to_owned(),
+        metadata: Metadata::default(),
         parts: to_parts(text),
     }
 }
 
-pub fn new_playlist(title: &str, members: Vec<Uuid>) -> Playlist {
+pub fn new_playlist(title: &str, members: Vec<LyricId>) -> Playlist {
     Playlist {
-        id: Uuid::default(),
+        id: PlaylistId::default(),
         title: title.to_owned(),
         members,
+        transpositions: Transpositions::default(),
     }
 }
@@ -2,38 +2,88 @@ use bb8_redis::redis::AsyncCommands;
 use bb8_redis::{
     RedisConnectionManager,
     bb8::{Pool, PooledConnection},
-    redis::{IntoConnectionInfo, cmd},
+    redis::{Client, FromRedisValue, IntoConnectionInfo, Pipeline, cmd, pipe},
+};
+use futures_util::{
+    FutureExt, StreamExt, TryFutureExt,
+    future::{ready, try_join_all},
 };
-use futures_util::{FutureExt, TryFutureExt, future::try_join_all};
 use lipl_core::{
-    Error, Lyric, Playlist, Repo, RepoConfig, Result, Summary, Uuid, by_title,
+    Error, Etag, Lyric, LyricId, Metadata, Page, PageRequest, Playlist, PlaylistId, Repo,
+    RepoConfig, Result, Revision, RevisionInfo, Summary, Uuid, by_title,
+    change::{Change, ChangeStream},
+    check_etag,
+    metadata::{tags_to_text, to_tags},
+    page::paginate,
     parts::{to_parts, to_text},
     redis_error,
+    revision::to_record,
+    search::{Hit, scan},
+    transpose::{to_transpositions, transpositions_to_text},
+    with_bs58,
 };
+use serde::{Serialize, de::DeserializeOwned};
 use std::{collections::HashMap, ops::DerefMut, str::FromStr};
 
 const LYRIC: &str = "lyric";
 const PLAYLIST: &str = "playlist";
+const REVISION: &str = "revision";
 const TEXT_ATTR: &str = "text";
 const TITLE_ATTR: &str = "title";
 const MEMBERS_ATTR: &str = "members";
+const SUBTITLE_ATTR: &str = "subtitle";
+const LYRICIST_ATTR: &str = "lyricist";
+const COMPOSER_ATTR: &str = "composer";
+const KEY_ATTR: &str = "key";
+const TEMPO_ATTR: &str = "tempo";
+const COPYRIGHT_ATTR: &str = "copyright";
+const CCLI_ATTR: &str = "ccli";
+const LANGUAGE_ATTR: &str = "language";
+const TAGS_ATTR: &str = "tags";
+const TRANSPOSITIONS_ATTR: &str = "transpositions";
 const WILDCARD: &str = "*";
 const SEP: &str = ":";
+/// Channel on which every change is published
+const CHANGE_CHANNEL: &str = "lipl:change";
+/// Deletes the key `KEYS[1]` and publishes `ARGV[2]` on channel `ARGV[1]` if it existed
+const DELETE_AND_PUBLISH: &str = "local deleted = redis.call('DEL', KEYS[1]) if deleted > 0 then redis.call('PUBLISH', ARGV[1], ARGV[2]) end return deleted";
+/// Hash fields in which an item is stored
+type Fields<T> = fn(&T) -> Vec<(String, String)>;
+
 const LYRIC_ALL: [&str; 3] = [LYRIC, SEP, WILDCARD];
 const PLAYLIST_ALL: [&str; 3] = [PLAYLIST, SEP, WILDCARD];
 
-fn bs58_to_uuid(r: Result<Vec<String>>) -> Result<Vec<Uuid>> {
+fn bs58_to_ids<I>(r: Result<Vec<String>>) -> Result<Vec<I>>
+where
+    I: From<Uuid>,
+{
     r.and_then(|keys| {
         keys.iter()
-            .map(|s| key_to_uuid(s))
+            .map(|s| key_to_uuid(s).map(I::from))
             .collect::<Result<Vec<_>>>()
     })
 }
 
-fn hashmap_to_lyric(id: Uuid) -> impl Fn(HashMap<String, String>) -> Lyric {
+/// Metadata fields that are missing or do not parse are left empty
+fn hashmap_to_metadata(hm: &HashMap<String, String>) -> Metadata {
+    Metadata {
+        subtitle: hm.get(SUBTITLE_ATTR).cloned(),
+        lyricist: hm.get(LYRICIST_ATTR).cloned(),
+        composer: hm.get(COMPOSER_ATTR).cloned(),
+        key: hm.get(KEY_ATTR).cloned(),
+        tempo: hm.get(TEMPO_ATTR).and_then(|tempo| tempo.parse().ok()),
+        copyright: hm.get(COPYRIGHT_ATTR).cloned(),
+        ccli: hm.get(CCLI_ATTR).and_then(|ccli| ccli.parse().ok()),
+        language: hm.get(LANGUAGE_ATTR).cloned(),
+        tags: hm.get(TAGS_ATTR).map(to_tags).unwrap_or_default(),
+    }
+}
+
+fn hashmap_to_lyric(id: LyricId) -> impl Fn(HashMap<String, String>) -> Lyric {
     move |hm| Lyric {
         id,
         title: hm.get(TITLE_ATTR).cloned().unwrap_or_default(),
+        metadata: hashmap_to_metadata(&hm),
         parts: to_parts(hm.get(TEXT_ATTR).cloned().unwrap_or_default()),
     }
 }
@@ -45,34 +95,173 @@ fn hashmap_to_summary(id: Uuid) -> impl Fn(HashMap<String, String>) -> Summary {
     }
 }
 
-fn hashmap_to_playlist(id: Uuid) -> impl Fn(Result<HashMap<String, String>>) -> Result<Playlist> {
+fn hashmap_to_playlist(
+    id: PlaylistId,
+) -> impl Fn(Result<HashMap<String, String>>) -> Result<Playlist> {
     move |result| {
         result.and_then(|hm| {
             hm.get(MEMBERS_ATTR)
                 .cloned()
                 .unwrap_or_default()
-                .split(' ')
-                .map(|key| key.parse::<Uuid>().ok().ok_or(Error::Key(key.to_owned())))
-                .collect::<Result<Vec<Uuid>>>()
+                .split_whitespace()
+                .map(|key| {
+                    key.parse::<LyricId>()
+                        .ok()
+                        .ok_or(Error::Key(key.to_owned()))
+                })
+                .collect::<Result<Vec<LyricId>>>()
                 .and_then(|members| {
                     hm.get(TITLE_ATTR)
                         .ok_or(Error::Key(id.to_string()))
                         .cloned()
                         .map(|title| (members, title))
                 })
-                .map(|(members, title)| Playlist { id, title, members })
+                .and_then(|(members, title)| {
+                    Ok(Playlist {
+                        id,
+                        title,
+                        transpositions: hm
+                            .get(TRANSPOSITIONS_ATTR)
+                            .map(|text| to_transpositions(text, &members))
+                            .transpose()?
+                            .unwrap_or_default(),
+                        members,
+                    })
+                })
         })
     }
 }
 
-fn lyric_key(id: Uuid) -> String {
+fn current_lyric(id: LyricId) -> impl Fn(HashMap<String, String>) -> Option<Lyric> {
+    move |hm| (!hm.is_empty()).then(|| hashmap_to_lyric(id)(hm))
+}
+
+fn current_playlist(id: PlaylistId) -> impl Fn(HashMap<String, String>) -> Option<Playlist> {
+    move |hm| {
+        (!hm.is_empty())
+            .then(|| hashmap_to_playlist(id)(Ok(hm)).ok())
+            .flatten()
+    }
+}
+
+fn metadata_fields(metadata: &Metadata) -> Vec<(String, String)> {
+    [
+        (SUBTITLE_ATTR, metadata.subtitle.clone()),
+        (LYRICIST_ATTR, metadata.lyricist.clone()),
+        (COMPOSER_ATTR, metadata.composer.clone()),
+        (KEY_ATTR, metadata.key.clone()),
+        (TEMPO_ATTR, metadata.tempo.map(|tempo| tempo.to_string())),
+        (COPYRIGHT_ATTR, metadata.copyright.clone()),
+        (CCLI_ATTR, metadata.ccli.map(|ccli| ccli.to_string())),
+        (LANGUAGE_ATTR, metadata.language.clone()),
+        (
+            TAGS_ATTR,
+            (!metadata.tags.is_empty()).then(|| tags_to_text(&metadata.tags)),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(field, value)| value.map(|value| (field.to_owned(), value)))
+    .collect()
+}
+
+fn lyric_fields(lyric: &Lyric) -> Vec<(String, String)> {
+    [
+        (TITLE_ATTR.to_owned(), lyric.title.clone()),
+        (TEXT_ATTR.to_owned(), to_text(&lyric.parts)),
+    ]
+    .into_iter()
+    .chain(metadata_fields(&lyric.metadata))
+    .collect()
+}
+
+fn playlist_fields(playlist: &Playlist) -> Vec<(String, String)> {
+    let mut fields = vec![
+        (TITLE_ATTR.to_owned(), playlist.title.clone()),
+        (
+            MEMBERS_ATTR.to_owned(),
+            playlist
+                .members
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    ];
+    if !playlist.transpositions.is_empty() {
+        fields.push((
+            TRANSPOSITIONS_ATTR.to_owned(),
+            transpositions_to_text(&playlist.transpositions),
+        ));
+    }
+    fields
+}
+
+fn lyric_key(id: LyricId) -> String {
     format!("{LYRIC}{SEP}{id}")
 }
 
-fn playlist_key(id: Uuid) -> String {
+fn playlist_key(id: PlaylistId) -> String {
     format!("{PLAYLIST}{SEP}{id}")
 }
 
+/// Key of the list with the revisions of an item, one json object per revision
+fn revisions_key(kind: &str, id: impl Into<Uuid>) -> String {
+    format!("{REVISION}{SEP}{kind}{SEP}{}", id.into())
+}
+
+fn json_error(error: serde_json::Error) -> Error {
+    Error::Json(Box::new(error))
+}
+
+fn change_message(change: &Change) -> Result<String> {
+    with_bs58(|| serde_json::to_string(change)).map_err(json_error)
+}
+
+/// Revisions are numbered by their position in the list
+fn to_revision<T>(json: &str, revision: usize) -> Result<Revision<T>>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_str::<Revision<T>>(json)
+        .map_err(json_error)
+        .map(|mut item| {
+            item.info.revision = u32::try_from(revision).unwrap_or(u32::MAX);
+            item
+        })
+}
+
+fn push_revisions<T>(
+    pipeline: &mut Pipeline,
+    key: &str,
+    count: usize,
+    previous: Option<T>,
+    item: T,
+) -> Result<()>
+where
+    T: Serialize,
+{
+    for revision in to_record(count, previous, item) {
+        pipeline
+            .rpush(
+                key,
+                with_bs58(|| serde_json::to_string(&revision)).map_err(json_error)?,
+            )
+            .ignore();
+    }
+    Ok(())
+}
+
+/// Deleting a key that does not exist fails with not found
+fn deleted(id: impl Into<Uuid>) -> impl FnOnce(u64) -> Result<()> {
+    move |count| {
+        if count == 0 {
+            Err(Error::NotFound(id.into()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
 fn key_to_uuid(key: &str) -> Result<Uuid> {
     key.split(':')
         .collect::<Vec<&str>>()
@@ -142,6 +331,8 @@ where
 #[derive(Clone)]
 pub struct RedisRepo {
     pool: Pool<RedisConnectionManager>,
+    /// Opens the connection that is subscribed to the changes
+    client: Client,
     delete_lyric_sha: String,
 }
 
@@ -150,7 +341,9 @@ impl RedisRepo {
     where
         T: IntoConnectionInfo,
     {
-        let manager = bb8_redis::RedisConnectionManager::new(config.url).map_err(redis_error)?;
+        let client = Client::open(config.url).map_err(redis_error)?;
+        let manager = bb8_redis::RedisConnectionManager::new(client.get_connection_info().clone())
+            .map_err(redis_error)?;
         let pool = bb8_redis::bb8::Pool::builder()
             .build(manager)
             .map_err(redis_error)
@@ -175,46 +368,260 @@ impl RedisRepo {
 
         Ok(Self {
             pool,
+            client,
             delete_lyric_sha,
         })
     }
 
-    async fn delete_lyric_script(&self, id: Uuid) -> Result<()> {
+    async fn delete_lyric_script(&self, id: LyricId) -> Result<()> {
+        let message = change_message(&Change::LyricDeleted { id })?;
         let mut connection = self.connection().await?;
-        cmd("EVALSHA")
+        let playlists = cmd("EVALSHA")
             .arg(self.delete_lyric_sha.clone())
             .arg("0")
             .arg(id.to_string())
-            .query_async(connection.deref_mut())
+            .arg(CHANGE_CHANNEL)
+            .arg(message)
+            .query_async::<Option<Vec<String>>>(connection.deref_mut())
             .map_err(redis_error)
-            .map(Into::into)
-            .await
+            .await?
+            .ok_or(Error::NotFound(id.into()))?;
+        self.publish_playlists(playlists).await
+    }
+
+    /// Publishes the playlists with keys `keys` as they are now, after a lyric is removed from them
+    async fn publish_playlists(&self, keys: Vec<String>) -> Result<()> {
+        for key in keys {
+            let playlist = self.get_playlist(key_to_uuid(&key)?.into()).await?;
+            self.connection()
+                .await?
+                .publish::<_, _, ()>(
+                    CHANGE_CHANNEL,
+                    change_message(&Change::playlist_upserted(&playlist))?,
+                )
+                .map_err(redis_error)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Fails if one of the members of `playlist` is not a lyric
+    async fn check_members(&self, playlist: &Playlist) -> Result<()> {
+        let mut connection = self.connection().await?;
+        for member in &playlist.members {
+            if !connection
+                .exists::<String, bool>(lyric_key(*member))
+                .map_err(redis_error)
+                .await?
+            {
+                return Err(Error::PlaylistInvalidMember(
+                    playlist.id.to_string(),
+                    member.to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_lyric_pipeline(&self, id: LyricId) -> Result<Pipeline> {
+        let mut pipeline = pipe();
+        pipeline
+            .atomic()
+            .cmd("EVALSHA")
+            .arg(self.delete_lyric_sha.clone())
+            .arg("0")
+            .arg(id.to_string())
+            .arg(CHANGE_CHANNEL)
+            .arg(change_message(&Change::LyricDeleted { id })?);
+        Ok(pipeline)
+    }
+
+    /// Executes the commands in the pipeline built from the item stored at `key` and the number of revisions
+    /// in `revisions`, and returns the replies that are not ignored. Both keys are watched, so the transaction is aborted if they change between the read
+    /// and the write. With `if_match` the commands are only executed if the entity tag of the item matches
+    /// and an aborted transaction fails, without it the transaction is tried again.
+    async fn execute_if_match<F, T, P, R>(
+        &self,
+        key: String,
+        revisions: Option<&str>,
+        current: F,
+        if_match: Option<&str>,
+        id: Uuid,
+        pipeline: P,
+    ) -> Result<R>
+    where
+        F: Fn(HashMap<String, String>) -> Option<T>,
+        T: Etag,
+        P: Fn(Option<T>, usize) -> Result<Pipeline>,
+        R: FromRedisValue,
+    {
+        let mut connection = self.connection().await?;
+        loop {
+            cmd("WATCH")
+                .arg(&key)
+                .arg(revisions)
+                .query_async::<()>(connection.deref_mut())
+                .map_err(redis_error)
+                .await?;
+            let item = connection
+                .hgetall(&key)
+                .map_err(redis_error)
+                .map_ok(&current)
+                .await?;
+            if let Some(if_match) = if_match
+                && let Err(error) = check_etag(item.as_ref(), if_match, id)
+            {
+                cmd("UNWATCH")
+                    .query_async::<()>(connection.deref_mut())
+                    .map_err(redis_error)
+                    .await?;
+                return Err(error);
+            }
+            let count = match revisions {
+                Some(revisions) => {
+                    connection
+                        .llen::<&str, usize>(revisions)
+                        .map_err(redis_error)
+                        .await?
+                }
+                None => 0,
+            };
+            let executed = pipeline(item, count)?
+                .query_async::<Option<R>>(connection.deref_mut())
+                .map_err(redis_error)
+                .await?;
+            match (executed, if_match) {
+                (Some(replies), _) => return Ok(replies),
+                (None, Some(_)) => return Err(Error::PreconditionFailed(id)),
+                (None, None) => {}
+            }
+        }
+    }
+
+    /// Stores `item` of kind `kind`, appends its revision and publishes the change, atomically with the write.
+    #[allow(clippy::too_many_arguments)]
+    async fn upsert<T, F>(
+        &self,
+        kind: &str,
+        id: Uuid,
+        item: T,
+        current: F,
+        fields: Fields<T>,
+        change: fn(&T) -> Change,
+        if_match: Option<&str>,
+    ) -> Result<T>
+    where
+        T: Etag + Serialize + Clone,
+        F: Fn(HashMap<String, String>) -> Option<T>,
+    {
+        let key = format!("{kind}{SEP}{id}");
+        let revisions = revisions_key(kind, id);
+        self.execute_if_match(
+            key.clone(),
+            Some(&revisions),
+            current,
+            if_match,
+            id,
+            |previous, count| {
+                let mut pipeline = pipe();
+                // Deleting first removes the fields of metadata that is no longer set
+                pipeline
+                    .atomic()
+                    .del(&key)
+                    .ignore()
+                    .hset_multiple(&key, &fields(&item))
+                    .ignore();
+                push_revisions(&mut pipeline, &revisions, count, previous, item.clone())?;
+                pipeline
+                    .publish(CHANGE_CHANNEL, change_message(&change(&item))?)
+                    .ignore();
+                Ok(pipeline)
+            },
+        )
+        .await
+        .map(|()| item)
+    }
+
+    async fn get_revisions<T>(&self, key: String) -> Result<Vec<Revision<T>>>
+    where
+        T: DeserializeOwned,
+    {
+        self.connection()
+            .and_then(|mut connection| async move {
+                connection
+                    .lrange::<&str, Vec<String>>(&key, 0, -1)
+                    .map_err(redis_error)
+                    .await
+            })
+            .await?
+            .iter()
+            .zip(1..)
+            .map(|(json, revision)| to_revision(json, revision))
+            .collect()
+    }
+
+    async fn get_revision<T>(
+        &self,
+        key: String,
+        id: impl Into<Uuid>,
+        revision: u32,
+    ) -> Result<Revision<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let id = id.into();
+        let index = isize::try_from(revision)
+            .ok()
+            .filter(|index| *index > 0)
+            .ok_or(Error::RevisionNotFound(id, revision))?;
+        self.connection()
+            .and_then(|mut connection| async move {
+                connection
+                    .lindex::<&str, Option<String>>(&key, index - 1)
+                    .map_err(redis_error)
+                    .await
+            })
+            .await?
+            .ok_or(Error::RevisionNotFound(id, revision))
+            .and_then(|json| to_revision(&json, index.unsigned_abs()))
     }
 
     async fn connection(&self) -> Result<PooledConnection<'_, RedisConnectionManager>> {
         self.pool.get().map_err(redis_error).await
     }
 
-    async fn delete<F>(&self, id: Uuid, f: F) -> Result<()>
+    async fn delete<I, F>(&self, id: I, f: F, change: Change) -> Result<()>
     where
-        F: Fn(Uuid) -> String,
+        I: Copy + Into<Uuid>,
+        F: Fn(I) -> String,
     {
+        let message = change_message(&change)?;
         self.connection()
-            .and_then(
-                |mut connection| async move { connection.del(f(id)).map_err(redis_error).await },
-            )
+            .and_then(|mut connection| async move {
+                cmd("EVAL")
+                    .arg(DELETE_AND_PUBLISH)
+                    .arg(1)
+                    .arg(f(id))
+                    .arg(CHANGE_CHANNEL)
+                    .arg(message)
+                    .query_async::<u64>(connection.deref_mut())
+                    .map_err(redis_error)
+                    .await
+            })
             .await
+            .and_then(deleted(id))
     }
 
-    async fn get_summary<F>(&self, id: Uuid, key: F) -> Result<Summary>
+    async fn get_summary<I, F>(&self, id: I, key: F) -> Result<Summary>
     where
-        F: Fn(Uuid) -> String,
+        I: Copy + Into<Uuid>,
+        F: Fn(I) -> String,
     {
         self.connection()
             .and_then(|mut connection| async move {
                 connection
                     .hgetall(key(id))
-                    .map_ok(hashmap_to_summary(id))
+                    .map_ok(hashmap_to_summary(id.into()))
                     .map_err(redis_error)
                     .await
             })
@@ -238,43 +645,128 @@ impl RedisRepo {
 }
 
 impl Repo for RedisRepo {
-    async fn delete_lyric(&self, id: Uuid) -> lipl_core::Result<()> {
+    async fn delete_lyric(&self, id: LyricId) -> lipl_core::Result<()> {
         self.delete_lyric_script(id).err_into().await
     }
 
-    async fn delete_playlist(&self, id: Uuid) -> lipl_core::Result<()> {
-        self.delete(id, playlist_key).err_into().await
+    async fn delete_playlist(&self, id: PlaylistId) -> lipl_core::Result<()> {
+        self.delete(id, playlist_key, Change::PlaylistDeleted { id })
+            .err_into()
+            .await
+    }
+
+    async fn upsert_lyric_if_match(
+        &self,
+        lyric: Lyric,
+        if_match: String,
+    ) -> lipl_core::Result<Lyric> {
+        self.upsert(
+            LYRIC,
+            lyric.id.into(),
+            lyric.clone(),
+            current_lyric(lyric.id),
+            lyric_fields,
+            Change::lyric_upserted,
+            Some(&if_match),
+        )
+        .await
+    }
+
+    async fn delete_lyric_if_match(&self, id: LyricId, if_match: String) -> lipl_core::Result<()> {
+        let (playlists,) = self
+            .execute_if_match::<_, _, _, (Option<Vec<String>>,)>(
+                lyric_key(id),
+                None,
+                current_lyric(id),
+                Some(&if_match),
+                id.into(),
+                |_, _| self.delete_lyric_pipeline(id),
+            )
+            .await?;
+        self.publish_playlists(playlists.ok_or(Error::NotFound(id.into()))?)
+            .await
+    }
+
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        if_match: String,
+    ) -> lipl_core::Result<Playlist> {
+        self.check_members(&playlist).await?;
+        self.upsert(
+            PLAYLIST,
+            playlist.id.into(),
+            playlist.clone(),
+            current_playlist(playlist.id),
+            playlist_fields,
+            Change::playlist_upserted,
+            Some(&if_match),
+        )
+        .await
     }
 
-    async fn get_lyric(&self, id: Uuid) -> lipl_core::Result<Lyric> {
+    async fn delete_playlist_if_match(
+        &self,
+        id: PlaylistId,
+        if_match: String,
+    ) -> lipl_core::Result<()> {
+        self.execute_if_match(
+            playlist_key(id),
+            None,
+            current_playlist(id),
+            Some(&if_match),
+            id.into(),
+            |_, _| {
+                let mut pipeline = pipe();
+                pipeline
+                    .atomic()
+                    .del(playlist_key(id))
+                    .ignore()
+                    .publish(
+                        CHANGE_CHANNEL,
+                        change_message(&Change::PlaylistDeleted { id })?,
+                    )
+                    .ignore();
+                Ok(pipeline)
+            },
+        )
+        .await
+    }
+
+    async fn get_lyric(&self, id: LyricId) -> lipl_core::Result<Lyric> {
         self.connection()
             .and_then(|mut connection| async move {
                 connection
                     .hgetall(lyric_key(id))
                     .map_err(redis_error)
-                    .map_ok(hashmap_to_lyric(id))
+                    .map_ok(current_lyric(id))
                     .await
             })
-            .err_into()
-            .await
+            .await?
+            .ok_or(Error::NotFound(id.into()))
     }
 
-    async fn get_playlist(&self, id: Uuid) -> lipl_core::Result<Playlist> {
+    async fn get_playlist(&self, id: PlaylistId) -> lipl_core::Result<Playlist> {
         self.connection()
             .and_then(|mut connection| async move {
                 connection
-                    .hgetall(playlist_key(id))
+                    .hgetall::<String, HashMap<String, String>>(playlist_key(id))
                     .map_err(redis_error)
-                    .map(hashmap_to_playlist(id))
                     .await
             })
-            .err_into()
             .await
+            .and_then(|hm| {
+                if hm.is_empty() {
+                    Err(Error::NotFound(id.into()))
+                } else {
+                    hashmap_to_playlist(id)(Ok(hm))
+                }
+            })
     }
 
     async fn get_lyrics(&self) -> lipl_core::Result<Vec<Lyric>> {
         let mut lyrics = self
-            .get_keys(LYRIC_ALL.concat(), bs58_to_uuid)
+            .get_keys(LYRIC_ALL.concat(), bs58_to_ids)
             .err_into()
             .and_then(|ids| try_join_all(ids.into_iter().map(|id| self.get_lyric(id))))
             .await?;
@@ -284,16 +776,22 @@ impl Repo for RedisRepo {
 
     async fn get_lyric_summaries(&self) -> lipl_core::Result<Vec<Summary>> {
         let mut summaries = self
-            .get_keys(LYRIC_ALL.concat(), bs58_to_uuid)
+            .get_keys(LYRIC_ALL.concat(), bs58_to_ids)
             .and_then(|ids| try_join_all(ids.into_iter().map(|id| self.get_summary(id, lyric_key))))
             .await?;
         summaries.sort_by(by_title);
         Ok(summaries)
     }
 
+    async fn search(&self, query: &str) -> lipl_core::Result<Vec<Hit>> {
+        self.get_lyrics()
+            .map_ok(|lyrics| scan(&lyrics, query))
+            .await
+    }
+
     async fn get_playlists(&self) -> lipl_core::Result<Vec<Playlist>> {
         let mut playlists = self
-            .get_keys(PLAYLIST_ALL.concat(), bs58_to_uuid)
+            .get_keys(PLAYLIST_ALL.concat(), bs58_to_ids)
             .err_into()
             .and_then(|ids| try_join_all(ids.into_iter().map(|id| self.get_playlist(id))))
             .await?;
@@ -303,7 +801,7 @@ impl Repo for RedisRepo {
 
     async fn get_playlist_summaries(&self) -> lipl_core::Result<Vec<Summary>> {
         let mut summaries = self
-            .get_keys(PLAYLIST_ALL.concat(), bs58_to_uuid)
+            .get_keys(PLAYLIST_ALL.concat(), bs58_to_ids)
             .and_then(|ids| {
                 try_join_all(
                     ids.into_iter()
@@ -315,52 +813,139 @@ impl Repo for RedisRepo {
         Ok(summaries)
     }
 
-    async fn upsert_lyric(&self, lyric: Lyric) -> lipl_core::Result<Lyric> {
-        self.connection()
-            .and_then(|mut connection| async move {
-                connection
-                    .hset_multiple::<String, String, String, ()>(
-                        lyric_key(lyric.id),
-                        &[
-                            (TITLE_ATTR.to_owned(), lyric.title.clone()),
-                            (TEXT_ATTR.to_owned(), to_text(&lyric.parts)),
-                        ],
-                    )
-                    .map_ok(|_| lyric)
-                    .map_err(redis_error)
-                    .await
-            })
-            .err_into()
+    async fn get_lyrics_page(&self, page: PageRequest) -> lipl_core::Result<Page<Lyric>> {
+        let summaries = self.get_lyric_summaries_page(page).await?;
+        let lyrics =
+            try_join_all(summaries.items.iter().map(|s| self.get_lyric(s.id.into()))).await?;
+        Ok(Page {
+            items: lyrics,
+            next: summaries.next,
+        })
+    }
+
+    async fn get_lyric_summaries_page(
+        &self,
+        page: PageRequest,
+    ) -> lipl_core::Result<Page<Summary>> {
+        self.get_lyric_summaries()
+            .map_ok(|summaries| paginate(summaries, &page))
+            .await
+    }
+
+    async fn get_playlists_page(&self, page: PageRequest) -> lipl_core::Result<Page<Playlist>> {
+        let summaries = self.get_playlist_summaries_page(page).await?;
+        let playlists = try_join_all(
+            summaries
+                .items
+                .iter()
+                .map(|s| self.get_playlist(s.id.into())),
+        )
+        .await?;
+        Ok(Page {
+            items: playlists,
+            next: summaries.next,
+        })
+    }
+
+    async fn get_playlist_summaries_page(
+        &self,
+        page: PageRequest,
+    ) -> lipl_core::Result<Page<Summary>> {
+        self.get_playlist_summaries()
+            .map_ok(|summaries| paginate(summaries, &page))
             .await
     }
 
+    async fn upsert_lyric(&self, lyric: Lyric) -> lipl_core::Result<Lyric> {
+        self.upsert(
+            LYRIC,
+            lyric.id.into(),
+            lyric.clone(),
+            current_lyric(lyric.id),
+            lyric_fields,
+            Change::lyric_upserted,
+            None,
+        )
+        .await
+    }
+
     async fn upsert_playlist(&self, playlist: Playlist) -> lipl_core::Result<Playlist> {
-        self.connection()
-            .and_then(|mut connection| async move {
-                connection
-                    .hset_multiple::<String, String, String, ()>(
-                        playlist_key(playlist.id),
-                        &[
-                            (TITLE_ATTR.to_owned(), playlist.title.clone()),
-                            (
-                                MEMBERS_ATTR.to_owned(),
-                                playlist
-                                    .members
-                                    .iter()
-                                    .map(|id| id.to_string())
-                                    .collect::<Vec<_>>()
-                                    .join(" "),
-                            ),
-                        ],
-                    )
-                    .map_ok(|_| playlist)
-                    .map_err(redis_error)
-                    .await
-            })
-            .err_into()
+        self.check_members(&playlist).await?;
+        self.upsert(
+            PLAYLIST,
+            playlist.id.into(),
+            playlist.clone(),
+            current_playlist(playlist.id),
+            playlist_fields,
+            Change::playlist_upserted,
+            None,
+        )
+        .await
+    }
+
+    async fn get_lyric_revisions(&self, id: LyricId) -> lipl_core::Result<Vec<RevisionInfo>> {
+        self.get_revisions::<Lyric>(revisions_key(LYRIC, id))
+            .map_ok(|revisions| revisions.into_iter().map(|r| r.info).collect())
+            .await
+    }
+
+    async fn get_lyric_revision(
+        &self,
+        id: LyricId,
+        revision: u32,
+    ) -> lipl_core::Result<Revision<Lyric>> {
+        self.get_revision(revisions_key(LYRIC, id), id, revision)
+            .await
+    }
+
+    async fn restore_lyric_revision(&self, id: LyricId, revision: u32) -> lipl_core::Result<Lyric> {
+        let revision = self.get_lyric_revision(id, revision).await?;
+        self.upsert_lyric(revision.item).await
+    }
+
+    async fn get_playlist_revisions(&self, id: PlaylistId) -> lipl_core::Result<Vec<RevisionInfo>> {
+        self.get_revisions::<Playlist>(revisions_key(PLAYLIST, id))
+            .map_ok(|revisions| revisions.into_iter().map(|r| r.info).collect())
             .await
     }
 
+    async fn get_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> lipl_core::Result<Revision<Playlist>> {
+        self.get_revision(revisions_key(PLAYLIST, id), id, revision)
+            .await
+    }
+
+    async fn restore_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> lipl_core::Result<Playlist> {
+        let revision = self.get_playlist_revision(id, revision).await?;
+        self.upsert_playlist(revision.item).await
+    }
+
+    async fn subscribe(&self) -> lipl_core::Result<ChangeStream> {
+        let mut pubsub = self.client.get_async_pubsub().map_err(redis_error).await?;
+        pubsub
+            .subscribe(CHANGE_CHANNEL)
+            .map_err(redis_error)
+            .await?;
+        let (sink, stream) = pubsub.split();
+        Ok(Box::pin(stream.filter_map(move |message| {
+            // The subscription lasts as long as the sink
+            let _subscribed = &sink;
+            ready(
+                message
+                    .get_payload::<String>()
+                    .ok()
+                    .and_then(|payload| serde_json::from_str(&payload).ok()),
+            )
+        })))
+    }
+
     async fn stop(&self) -> lipl_core::Result<()> {
         Ok(())
     }
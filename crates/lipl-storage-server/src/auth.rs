@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    Extension,
+    extract::Request,
+    http::{
+        HeaderMap, StatusCode,
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose};
+use subtle::ConstantTimeEq;
+
+use crate::Problem;
+use crate::environment::{password, username};
+
+const USERS: &str = "LIPL_USERS";
+const CHALLENGE: &str = r#"Basic realm="lipl""#;
+
+/// The user that is authenticated for the request, who is the author of the revisions it creates
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct User(pub String);
+
+/// The users that may use the api with basic authentication, with their passwords.
+/// Only the admin may use `/admin`.
+#[derive(Clone, Debug)]
+pub struct Users {
+    passwords: Arc<HashMap<String, String>>,
+    admin: String,
+    /// Whether every request needs credentials, otherwise only the requests to `/admin` do
+    required: bool,
+}
+
+impl Users {
+    /// The admin and the other users, each with the password. Every request needs the credentials of one of them.
+    pub fn new(admin: (String, String), users: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            admin: admin.0.clone(),
+            passwords: Arc::new(std::iter::once(admin).chain(users).collect()),
+            required: true,
+        }
+    }
+
+    /// Only the admin with the password, who is needed for `/admin`. The rest of the api is open.
+    pub fn admin(admin: (String, String)) -> Self {
+        Self {
+            required: false,
+            ..Self::new(admin, [])
+        }
+    }
+
+    /// `LIPL_USERNAME` with `LIPL_PASSWORD` as admin and the users in `LIPL_USERS`, given as `name:password` separated by commas.
+    /// Without `LIPL_USERS` only the admin routes need credentials, see `Users::admin`.
+    pub fn from_environment() -> crate::Result<Self> {
+        let admin = (username()?, password()?);
+        let Ok(list) = std::env::var(USERS) else {
+            return Ok(Self::admin(admin));
+        };
+        let mut users = vec![];
+        for credentials in list.split(',').filter(|item| !item.is_empty()) {
+            let (name, password) = credentials
+                .split_once(':')
+                .ok_or(crate::Error::InvalidConfiguration)?;
+            users.push((name.to_owned(), password.to_owned()));
+        }
+        Ok(Self::new(admin, users))
+    }
+
+    /// The user with the credentials in `headers` if the password is right
+    fn authenticated(&self, headers: &HeaderMap) -> Option<User> {
+        let (name, password) = credentials(headers)?;
+        self.passwords
+            .get(&name)
+            .is_some_and(|expected| expected.as_bytes().ct_eq(password.as_bytes()).into())
+            .then_some(User(name))
+    }
+}
+
+/// User name and password from a basic `Authorization` header, if present
+fn credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| general_purpose::STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|credentials| {
+            credentials
+                .split_once(':')
+                .map(|(name, password)| (name.to_owned(), password.to_owned()))
+        })
+}
+
+/// Responds with 401 and asks for basic authentication
+pub(crate) fn unauthorized() -> Response {
+    (
+        [(WWW_AUTHENTICATE, CHALLENGE)],
+        Problem::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "Valid credentials are required",
+        ),
+    )
+        .into_response()
+}
+
+/// Responds with 403, the credentials are valid but not allowed to do this
+pub(crate) fn forbidden(detail: &str) -> Response {
+    Problem::new(StatusCode::FORBIDDEN, "forbidden", detail).into_response()
+}
+
+/// Middleware that answers 401 unless the request has the credentials of one of the `Users`, if they are required.
+/// The user is added to the request as `User`.
+pub(crate) async fn authenticate(
+    Extension(users): Extension<Users>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !users.required {
+        return next.run(request).await;
+    }
+    match users.authenticated(request.headers()) {
+        Some(user) => {
+            request.extensions_mut().insert(user);
+            next.run(request).await
+        }
+        None => unauthorized(),
+    }
+}
+
+/// Middleware that answers 401 without the credentials of one of the `Users` and 403 unless the user is the admin.
+/// Without users nobody is the admin, so it answers 403.
+pub(crate) async fn require_admin(
+    users: Option<Extension<Users>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(Extension(users)) = users else {
+        return forbidden("There is no admin");
+    };
+    match users.authenticated(request.headers()) {
+        Some(user) if user.0 == users.admin => next.run(request).await,
+        Some(_) => forbidden("Only the admin may do this"),
+        None => unauthorized(),
+    }
+}
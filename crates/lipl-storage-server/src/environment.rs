@@ -1,11 +1,71 @@
-use crate::create_router;
 use crate::{Error, RepoConfig, Result};
-use axum::Router;
+use crate::{ReadOnly, TransactionLog, Users, create_router_with_users};
+use axum::{Extension, Router};
+use core::str::FromStr;
+use core::time::Duration;
+use lipl_core::{IdGenerator, Repo, Rules, set_id_generator, transaction::LoggedRepo};
+use lipl_storage_cache::CachedRepo;
+use std::path::PathBuf;
 
 fn var(key: &'static str) -> Result<String> {
     std::env::var(key).map_err(Error::from)
 }
 
+/// Parses the variable if it is set, otherwise returns `default`
+fn parse_or<T>(key: &'static str, default: T) -> Result<T>
+where
+    T: FromStr,
+    Error: From<T::Err>,
+{
+    std::env::var(key).map_or(Ok(default), |s| s.trim().parse::<T>().map_err(Error::from))
+}
+
+/// Validation rules, the default rules changed by the variables that are set
+pub fn rules() -> Result<Rules> {
+    let default = Rules::default();
+    Ok(Rules {
+        max_title_length: parse_or("LIPL_STORAGE_MAX_TITLE_LENGTH", default.max_title_length)?,
+        max_metadata_length: parse_or(
+            "LIPL_STORAGE_MAX_METADATA_LENGTH",
+            default.max_metadata_length,
+        )?,
+        max_parts: parse_or("LIPL_STORAGE_MAX_PARTS", default.max_parts)?,
+        max_part_length: parse_or("LIPL_STORAGE_MAX_PART_LENGTH", default.max_part_length)?,
+        max_members: parse_or("LIPL_STORAGE_MAX_MEMBERS", default.max_members)?,
+        titles: parse_or("LIPL_STORAGE_TITLES", default.titles)?,
+    })
+}
+
+/// Time to live of cached entries if `LIPL_STORAGE_CACHE` is true, none if the repo is not cached.
+/// Entries live until the server changes them unless `LIPL_STORAGE_CACHE_TTL` sets the seconds.
+fn cache() -> Result<Option<Option<Duration>>> {
+    if !parse_or("LIPL_STORAGE_CACHE", false)? {
+        return Ok(None);
+    }
+    let ttl = std::env::var("LIPL_STORAGE_CACHE_TTL")
+        .ok()
+        .map(|s| s.trim().parse::<u64>().map(Duration::from_secs))
+        .transpose()?;
+    Ok(Some(ttl))
+}
+
+/// Whether the server starts in read only mode, false unless `LIPL_STORAGE_READ_ONLY` is true
+pub fn read_only() -> Result<ReadOnly> {
+    parse_or("LIPL_STORAGE_READ_ONLY", false).map(ReadOnly::new)
+}
+
+/// File that every change is appended to if `LIPL_STORAGE_TRANSACTION_LOG` is set
+pub fn transaction_log() -> Result<Option<PathBuf>> {
+    Ok(std::env::var("LIPL_STORAGE_TRANSACTION_LOG")
+        .ok()
+        .map(|s| PathBuf::from(s.trim())))
+}
+
+/// Generator of the ids of new items, `v4` (random) unless `LIPL_STORAGE_ID_GENERATOR` is `v7`
+pub fn id_generator() -> Result<IdGenerator> {
+    parse_or("LIPL_STORAGE_ID_GENERATOR", IdGenerator::default())
+}
+
 #[cfg(feature = "memory")]
 fn include_sample_data() -> Result<bool> {
     var("LIPL_STORAGE_MEMORY_SAMPLE").and_then(|s| s.parse::<bool>().map_err(Error::from))
@@ -16,52 +76,96 @@ pub async fn repo() -> Result<Router> {
     let trimmed = repo_type.trim().to_lowercase();
     let r = trimmed.as_str();
 
-    async fn to_router<T>(repo_config: T) -> Result<Router>
+    async fn to_router<T>(repo_config: T, transaction_log: Option<PathBuf>) -> Result<Router>
     where
         T: RepoConfig + Send + Sync + 'static,
-        <T as RepoConfig>::Repo: Send + Sync + 'static,
+        <T as RepoConfig>::Repo: Clone + Send + Sync + 'static,
     {
-        repo_config
-            .to_repo()
-            .await
-            .map_err(Into::into)
-            .map(create_router)
+        set_id_generator(id_generator()?);
+        let repo = repo_config.to_repo().await?;
+        match transaction_log {
+            Some(path) => {
+                let repo = LoggedRepo::open(repo, path)?;
+                let log = TransactionLog::new(repo.clone());
+                cached_router(repo)
+                    .await
+                    .map(|router| router.layer(Extension(log)))
+            }
+            None => cached_router(repo).await,
+        }
+    }
+
+    async fn cached_router<R>(repo: R) -> Result<Router>
+    where
+        R: Repo + Clone + Send + Sync + 'static,
+    {
+        let rules = rules()?;
+        let read_only = read_only()?;
+        let users = Users::from_environment()?;
+        Ok(match cache()? {
+            Some(ttl) => {
+                let repo = CachedRepo::subscribed(repo, ttl).await?;
+                create_router_with_users(repo, rules, read_only, users)
+            }
+            None => create_router_with_users(repo, rules, read_only, users),
+        })
     }
 
     #[cfg(feature = "postgres")]
     if r == "postgres" {
         use lipl_storage_postgres::PostgresConfig;
         let s = postgres_connection()?;
-        return to_router(PostgresConfig::from(s)).await;
+        return to_router(
+            PostgresConfig::from(s).with_titles(rules()?.titles),
+            transaction_log()?,
+        )
+        .await;
     }
 
     #[cfg(feature = "fs")]
     if r == "fs" {
         use lipl_storage_fs::FileRepoConfig;
-        return to_router(file_path().parse::<FileRepoConfig>()?).await;
+        let dir = file_path();
+        let transaction_log =
+            transaction_log()?.unwrap_or(PathBuf::from(&dir).join(".transaction.log"));
+        return to_router(dir.parse::<FileRepoConfig>()?, Some(transaction_log)).await;
     }
 
     #[cfg(feature = "memory")]
     if r == "memory" {
         use lipl_storage_memory::MemoryRepoConfig;
         let sample_data = include_sample_data()?;
-        return to_router(MemoryRepoConfig {
-            sample_data,
-            transaction_log: None,
-        })
+        let transaction_log = transaction_log()?;
+        return to_router(
+            MemoryRepoConfig {
+                sample_data,
+                transaction_log: transaction_log.clone(),
+            },
+            transaction_log,
+        )
         .await;
     }
 
     #[cfg(feature = "redis")]
     if r == "redis" {
         use lipl_storage_redis::RedisRepoConfig;
-        return to_router(redis_connection()?.parse::<RedisRepoConfig<_>>()?).await;
+        return to_router(
+            redis_connection()?.parse::<RedisRepoConfig<_>>()?,
+            transaction_log()?,
+        )
+        .await;
     }
 
     #[cfg(feature = "turso")]
     if r == "turso" {
         use lipl_storage_turso::TursoConfig;
-        return to_router(turso_connection().map(TursoConfig::from)?).await;
+        return to_router(
+            turso_connection()
+                .map(TursoConfig::from)?
+                .with_titles(rules()?.titles),
+            transaction_log()?,
+        )
+        .await;
     }
 
     Err(Error::InvalidConfiguration)
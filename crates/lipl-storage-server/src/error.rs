@@ -1,26 +1,78 @@
-use std::{env::VarError, str::ParseBoolError};
+use std::{env::VarError, num::ParseIntError, str::ParseBoolError};
 
 use axum::{
     Json,
-    http::StatusCode,
+    http::{StatusCode, header::CONTENT_TYPE},
     response::{IntoResponse, Response},
 };
+use lipl_core::{ErrorKind, validate::FieldError};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Deserialize, Serialize)]
-pub struct ErrorReport {
-    error: String,
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Problem details (RFC 7807) in the body of an error response
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    /// Machine readable code, see `lipl_core::Error::code`
+    pub code: String,
+    /// Problems per field if the input is invalid
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
 }
 
-impl<E: std::error::Error> From<E> for ErrorReport {
-    fn from(error: E) -> Self {
+impl Problem {
+    pub fn new(status: StatusCode, code: &str, detail: impl std::fmt::Display) -> Self {
         Self {
-            error: error.to_string(),
+            problem_type: "about:blank".to_owned(),
+            title: status.canonical_reason().unwrap_or_default().to_owned(),
+            status: status.as_u16(),
+            detail: detail.to_string(),
+            code: code.to_owned(),
+            errors: vec![],
+        }
+    }
+}
+
+fn status(kind: ErrorKind) -> StatusCode {
+    match kind {
+        ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        ErrorKind::Conflict => StatusCode::CONFLICT,
+        ErrorKind::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+        ErrorKind::Validation => StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorKind::Unavailable | ErrorKind::ReadOnly => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl From<&lipl_core::Error> for Problem {
+    fn from(error: &lipl_core::Error) -> Self {
+        let problem = Self::new(status(error.kind()), error.code(), error);
+        match error {
+            lipl_core::Error::Validation(errors) => Self {
+                errors: errors.clone(),
+                ..problem
+            },
+            _ => problem,
         }
     }
 }
 
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        if status.is_server_error() {
+            tracing::error!("{}", self.detail);
+        }
+        (status, [(CONTENT_TYPE, PROBLEM_JSON)], Json(self)).into_response()
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Hyper: {0}")]
@@ -35,16 +87,19 @@ pub enum Error {
     #[error("Environment parse: {0}")]
     EnvironmentParse(#[from] ParseBoolError),
 
+    #[error("Environment parse: {0}")]
+    EnvironmentParseInt(#[from] ParseIntError),
+
     #[error("Core: {0}")]
     Core(#[from] lipl_core::Error),
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorReport::from(self)),
-        )
-            .into_response()
+        match &self {
+            Error::Core(error) => Problem::from(error),
+            _ => Problem::new(StatusCode::INTERNAL_SERVER_ERROR, "internal", &self),
+        }
+        .into_response()
     }
 }
@@ -0,0 +1,139 @@
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json,
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures_util::TryFutureExt;
+use lipl_core::{
+    Repo, RepoDb,
+    reexport::chrono::{DateTime, Utc},
+    transaction::{LoggedRepo, parse_timestamp},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{to_bad_request, to_error_response, to_json_response, to_status_ok};
+use crate::error::Problem;
+use crate::read_only::ReadOnly;
+
+type Compact =
+    dyn Fn() -> Pin<Box<dyn Future<Output = lipl_core::Result<()>> + Send>> + Send + Sync;
+
+/// The transaction log of a `LoggedRepo`, whatever the repo inside
+#[derive(Clone)]
+pub struct TransactionLog {
+    #[cfg_attr(not(feature = "memory"), allow(dead_code))]
+    path: Option<PathBuf>,
+    compact: Arc<Compact>,
+}
+
+impl TransactionLog {
+    pub fn new<R>(repo: LoggedRepo<R>) -> Self
+    where
+        R: Repo + Clone + Send + Sync + 'static,
+    {
+        Self {
+            path: repo.path().map(PathBuf::from),
+            compact: Arc::new(move || {
+                let repo = repo.clone();
+                Box::pin(async move { repo.compact().await })
+            }),
+        }
+    }
+
+    /// The library as it was at `at`, rebuilt in memory from the snapshot and the log
+    #[cfg(feature = "memory")]
+    pub async fn at(&self, at: DateTime<Utc>) -> lipl_core::Result<RepoDb> {
+        use lipl_core::transaction::recover_until;
+        use lipl_storage_memory::MemoryRepo;
+
+        let path = self
+            .path
+            .as_ref()
+            .ok_or(lipl_core::Error::Argument("the log is not a file"))?;
+        let repo = MemoryRepo::default();
+        recover_until(path, repo.clone(), at).await?;
+        Ok(RepoDb {
+            lyrics: repo.get_lyrics().await?,
+            playlists: repo.get_playlists().await?,
+        })
+    }
+
+    /// The library as it was at `at`, which needs the memory backend to rebuild it
+    #[cfg(not(feature = "memory"))]
+    pub async fn at(&self, _at: DateTime<Utc>) -> lipl_core::Result<RepoDb> {
+        Err(lipl_core::Error::Argument(
+            "rebuilding the library needs the memory feature",
+        ))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ReadOnlyState {
+    read_only: bool,
+}
+
+/// Handler for getting the read only mode
+pub async fn get_read_only(Extension(read_only): Extension<ReadOnly>) -> Response {
+    to_json_response(StatusCode::OK)(ReadOnlyState {
+        read_only: read_only.is_enabled(),
+    })
+}
+
+/// Handler for switching the read only mode on or off
+pub async fn put_read_only(
+    Extension(read_only): Extension<ReadOnly>,
+    Json(state): Json<ReadOnlyState>,
+) -> Response {
+    read_only.set(state.read_only);
+    to_json_response(StatusCode::OK)(state)
+}
+
+fn no_transaction_log() -> Response {
+    Problem::new(
+        StatusCode::NOT_FOUND,
+        "not-found",
+        "There is no transaction log",
+    )
+    .into_response()
+}
+
+/// Handler for compacting the transaction log into a snapshot, answers 404 if there is no log
+pub async fn compact(log: Option<Extension<TransactionLog>>) -> Response {
+    match log {
+        Some(Extension(log)) => {
+            (log.compact)()
+                .map_ok_or_else(to_error_response, to_status_ok)
+                .await
+        }
+        None => no_transaction_log(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    at: String,
+}
+
+/// Handler for the lyrics and playlists as they were at `?at=`, an RFC 3339 timestamp,
+/// answers 404 if there is no log
+pub async fn history(
+    log: Option<Extension<TransactionLog>>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let Some(Extension(log)) = log else {
+        return no_transaction_log();
+    };
+    match parse_timestamp(&query.at) {
+        Ok(at) => {
+            log.at(at)
+                .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+                .await
+        }
+        Err(error) => to_bad_request(error),
+    }
+}
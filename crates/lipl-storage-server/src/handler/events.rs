@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    Extension,
+    extract::State,
+    http::HeaderMap,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::{Stream, StreamExt, stream};
+use lipl_core::{IdFormat, Repo, change::Change, id_format, with_id_format_sync};
+use tokio::sync::{
+    OnceCell,
+    broadcast::{self, error::RecvError},
+};
+
+use super::to_error_response;
+
+/// Changes kept for clients that resume with `Last-Event-ID`
+const RECENT: usize = 1000;
+/// Wait before subscribing again after the change stream of the backend ended
+const RESUBSCRIBE: Duration = Duration::from_secs(1);
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// A change with its place in the feed, sent with id `<run>-<sequence>`
+#[derive(Clone)]
+struct Entry {
+    run: u64,
+    sequence: u64,
+    change: Change,
+}
+
+impl Entry {
+    /// The event with the ids of the change in `format`
+    fn event(&self, format: IdFormat) -> Event {
+        with_id_format_sync(format, || {
+            Event::default()
+                .id(format!("{}-{}", self.run, self.sequence))
+                .event("change")
+                .json_data(&self.change)
+                .unwrap_or_default()
+        })
+    }
+}
+
+#[derive(Clone)]
+enum Notice {
+    Change(Entry),
+    /// Changes may be missed, clients have to fetch the library again
+    Reset,
+}
+
+fn reset() -> Event {
+    Event::default().event("reset").data("")
+}
+
+/// The recent changes. A run ends when the change stream of the backend ends,
+/// because changes made until it is subscribed again are missed, and when it reports missed changes.
+struct Recent {
+    run: u64,
+    next: u64,
+    entries: VecDeque<Entry>,
+}
+
+impl Recent {
+    /// The changes after `last_event_id`, or `None` if they are not all kept
+    fn after(&self, last_event_id: &str) -> Option<Vec<Entry>> {
+        let (run, sequence) = last_event_id.split_once('-')?;
+        let run = run.parse::<u64>().ok()?;
+        let sequence = sequence.parse::<u64>().ok()?;
+        let oldest = self.next - self.entries.len() as u64;
+        (run == self.run && sequence < self.next && sequence + 1 >= oldest).then(|| {
+            self.entries
+                .iter()
+                .filter(|entry| entry.sequence > sequence)
+                .cloned()
+                .collect()
+        })
+    }
+}
+
+struct Feed {
+    recent: Mutex<Recent>,
+    sender: broadcast::Sender<Notice>,
+    started: OnceCell<()>,
+}
+
+/// The changes of the library for the clients of `/events`, which share one subscription to the repo.
+/// The subscription starts with the first client.
+#[derive(Clone)]
+pub struct Events(Arc<Feed>);
+
+impl Default for Events {
+    fn default() -> Self {
+        // The run starts at the time in milliseconds, so ids of an earlier server are not resumed
+        let run = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| u64::try_from(elapsed.as_millis()).unwrap_or_default())
+            .unwrap_or_default();
+        Self(Arc::new(Feed {
+            recent: Mutex::new(Recent {
+                run,
+                next: 1,
+                entries: VecDeque::with_capacity(RECENT),
+            }),
+            sender: broadcast::channel(RECENT).0,
+            started: OnceCell::new(),
+        }))
+    }
+}
+
+impl Events {
+    /// Subscribes to `repo` once and forwards its changes until the server stops
+    async fn start<R>(&self, repo: Arc<R>) -> lipl_core::Result<()>
+    where
+        R: Repo + Send + Sync + 'static,
+    {
+        self.0
+            .started
+            .get_or_try_init(|| async {
+                let changes = repo.subscribe().await?;
+                tokio::spawn(self.clone().forward(repo, changes));
+                Ok(())
+            })
+            .await
+            .map(|_| ())
+    }
+
+    async fn forward<R>(self, repo: Arc<R>, mut changes: lipl_core::change::ChangeStream)
+    where
+        R: Repo + Send + Sync + 'static,
+    {
+        loop {
+            while let Some(change) = changes.next().await {
+                match change {
+                    Change::Lagged { missed } => {
+                        tracing::warn!(missed, "Changes of the repo are missed");
+                        self.restart();
+                    }
+                    change => self.publish(change),
+                }
+            }
+            tracing::warn!("The change stream of the repo ended, subscribing again");
+            self.restart();
+            changes = loop {
+                tokio::time::sleep(RESUBSCRIBE).await;
+                match repo.subscribe().await {
+                    Ok(changes) => break changes,
+                    Err(error) => tracing::warn!(%error, "Failed to subscribe to changes"),
+                }
+            };
+        }
+    }
+
+    fn publish(&self, change: Change) {
+        let mut recent = self.0.recent.lock().unwrap();
+        let entry = Entry {
+            run: recent.run,
+            sequence: recent.next,
+            change,
+        };
+        recent.next += 1;
+        if recent.entries.len() == RECENT {
+            recent.entries.pop_front();
+        }
+        recent.entries.push_back(entry.clone());
+        // Sent under the lock, so a client that subscribes gets every change either from recent or live
+        let _ = self.0.sender.send(Notice::Change(entry));
+    }
+
+    fn restart(&self) {
+        let mut recent = self.0.recent.lock().unwrap();
+        recent.run += 1;
+        recent.entries.clear();
+        let _ = self.0.sender.send(Notice::Reset);
+    }
+
+    /// Events after `last_event_id`, which starts with a reset if changes after it are missed.
+    /// The events are serialized when the client reads them, so the ids are written in `format` then.
+    fn stream(
+        &self,
+        last_event_id: Option<&str>,
+        format: IdFormat,
+    ) -> impl Stream<Item = Event> + use<> {
+        let receiver = self.0.sender.subscribe();
+        let (backlog, last) = {
+            let recent = self.0.recent.lock().unwrap();
+            let backlog = match last_event_id.map(|id| recent.after(id)) {
+                None => vec![],
+                Some(Some(entries)) => entries.iter().map(|entry| entry.event(format)).collect(),
+                Some(None) => vec![reset()],
+            };
+            (backlog, recent.next - 1)
+        };
+        let live = stream::unfold((receiver, last), move |(mut receiver, last)| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(Notice::Change(entry)) if entry.sequence > last => {
+                        return Some((entry.event(format), (receiver, entry.sequence)));
+                    }
+                    Ok(Notice::Change(_)) => {}
+                    Ok(Notice::Reset) | Err(RecvError::Lagged(_)) => {
+                        return Some((reset(), (receiver, last)));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        stream::iter(backlog).chain(live)
+    }
+}
+
+/// Handler for the changes as server-sent events, from `Last-Event-ID` on if it is given.
+/// A `reset` event tells the client that changes are missed and the library has to be fetched again.
+pub async fn events<R>(
+    State(repo): State<Arc<R>>,
+    Extension(events): Extension<Events>,
+    headers: HeaderMap,
+) -> Response
+where
+    R: Repo + Send + Sync + 'static,
+{
+    if let Err(error) = events.start(repo).await {
+        return to_error_response(error);
+    }
+    let last_event_id = headers
+        .get(LAST_EVENT_ID)
+        .and_then(|value| value.to_str().ok());
+    Sse::new(
+        events
+            .stream(last_event_id, id_format())
+            .map(Ok::<_, Infallible>),
+    )
+    .keep_alive(KeepAlive::default())
+    .into_response()
+}
@@ -1,19 +1,183 @@
 use std::sync::Arc;
 
 use super::ListQuery;
-use super::{Key, to_error_response, to_json_response, to_status_ok};
+use super::{
+    Key, Revision, TitleLock, if_match, if_none_match, tagged_response, to_bad_request,
+    to_error_response, to_etag_response, to_json_response, to_status_ok, validate,
+};
 use axum::{
     Json,
-    extract::{Query, State},
-    http::StatusCode,
-    response::Response,
+    extract::{Extension, FromRequest, Query, Request, State},
+    http::{
+        HeaderMap, HeaderName, StatusCode,
+        header::{ACCEPT, CONTENT_TYPE, VARY},
+    },
+    response::{IntoResponse, Response},
 };
 use futures_util::TryFutureExt;
-use lipl_core::{LyricPost, Repo};
+use lipl_core::{
+    ChordPro, Etag, Lyric, LyricId, LyricPost, OpenLyrics, Repo, Rules, Summary, Transposition,
+    Uuid, Validate,
+    transpose::transpose_lyric,
+    validate::{title_errors, to_result},
+};
+use serde::Deserialize;
+
+const CHORDPRO: &str = "text/x-chordpro";
+const OPENLYRICS: &str = "application/vnd.openlyrics+xml";
+
+/// Format of a lyric in a request or response body
+#[derive(Clone, Copy)]
+enum LyricFormat {
+    Json,
+    ChordPro,
+    OpenLyrics,
+}
+
+impl LyricFormat {
+    /// The format that header `name` names, json if it names neither `ChordPro` nor `OpenLyrics`
+    fn from_header(headers: &HeaderMap, name: HeaderName) -> Self {
+        let value = headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if value.contains(CHORDPRO) {
+            Self::ChordPro
+        } else if value.contains(OPENLYRICS) {
+            Self::OpenLyrics
+        } else {
+            Self::Json
+        }
+    }
+
+    /// Marks the entity tag of a lyric as the tag of this representation, json keeps the tag of the lyric
+    fn suffix(self) -> &'static str {
+        match self {
+            Self::Json => "",
+            Self::ChordPro => "-chordpro",
+            Self::OpenLyrics => "-openlyrics",
+        }
+    }
+
+    fn etag(self, lyric: &Lyric) -> String {
+        let etag = lyric.etag().unwrap_or_default();
+        match etag.strip_suffix('"') {
+            Some(tag) => format!("{tag}{}\"", self.suffix()),
+            None => etag,
+        }
+    }
+}
+
+/// The value of the `If-Match` header with the tags of the `ChordPro` and `OpenLyrics` representations
+/// replaced by the tag of the lyric, if present
+fn lyric_if_match(headers: &HeaderMap) -> Option<String> {
+    if_match(headers).map(|header| {
+        [LyricFormat::ChordPro, LyricFormat::OpenLyrics]
+            .iter()
+            .fold(header, |header, format| {
+                header.replace(&format!("{}\"", format.suffix()), "\"")
+            })
+    })
+}
+
+fn text_body(media_type: &str, text: String) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, format!("{media_type}; charset=utf-8"))],
+        text,
+    )
+}
+
+/// Responds with the lyric in the format the client accepts, json, `ChordPro` or `OpenLyrics`.
+/// Each format has its own entity tag and the response varies with `Accept`.
+fn to_lyric_response(
+    status_code: StatusCode,
+    if_none_match: Option<String>,
+    format: LyricFormat,
+) -> impl Fn(Lyric) -> Response {
+    move |lyric| {
+        let if_none_match = if_none_match.as_deref();
+        let etag = format.etag(&lyric);
+        let response = match format {
+            LyricFormat::Json => tagged_response(status_code, if_none_match, etag, Json(&lyric)),
+            LyricFormat::ChordPro => {
+                let text = ChordPro::from(lyric).to_string();
+                tagged_response(status_code, if_none_match, etag, text_body(CHORDPRO, text))
+            }
+            LyricFormat::OpenLyrics => {
+                let text = OpenLyrics::from(lyric).to_string();
+                tagged_response(
+                    status_code,
+                    if_none_match,
+                    etag,
+                    text_body(OPENLYRICS, text),
+                )
+            }
+        };
+        ([(VARY, ACCEPT.as_str())], response).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LyricQuery {
+    /// Transposition like `+2` or `-3 flat capo 1`
+    transpose: Option<String>,
+}
+
+impl LyricQuery {
+    fn transposition(&self) -> lipl_core::Result<Option<Transposition>> {
+        self.transpose
+            .as_deref()
+            .map(str::parse::<Transposition>)
+            .transpose()
+    }
+}
+
+/// Lyric in the request body, as json or in `ChordPro` or `OpenLyrics` format
+pub struct LyricBody(pub LyricPost);
+
+impl<S: Send + Sync> FromRequest<S> for LyricBody {
+    type Rejection = Response;
 
-/// Handler for getting all lyrics
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let format = LyricFormat::from_header(request.headers(), CONTENT_TYPE);
+        if let LyricFormat::Json = format {
+            return Json::<LyricPost>::from_request(request, state)
+                .await
+                .map(|Json(lyric_post)| Self(lyric_post))
+                .map_err(IntoResponse::into_response);
+        }
+        let text = String::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        match format {
+            LyricFormat::OpenLyrics => text.parse::<OpenLyrics>().map(LyricPost::from),
+            _ => text.parse::<ChordPro>().map(LyricPost::from),
+        }
+        .map(Self)
+        .map_err(to_bad_request)
+    }
+}
+
+/// Handler for getting all lyrics, optionally one page at a time or matching a search query
 pub async fn list<R: Repo>(State(connection): State<Arc<R>>, query: Query<ListQuery>) -> Response {
-    if query.full == Some(true) {
+    if let Some(q) = query.q.as_deref() {
+        connection
+            .search(q)
+            .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+            .await
+    } else if let Some(page) = query.page() {
+        if query.full == Some(true) {
+            connection
+                .get_lyrics_page(page)
+                .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+                .await
+        } else {
+            connection
+                .get_lyric_summaries_page(page)
+                .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+                .await
+        }
+    } else if query.full == Some(true) {
         connection
             .get_lyrics()
             .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
@@ -26,41 +190,267 @@ pub async fn list<R: Repo>(State(connection): State<Arc<R>>, query: Query<ListQu
     }
 }
 
-/// Handler for getting a specific lyric
-pub async fn item<R: Repo>(State(connection): State<Arc<R>>, key: Key) -> Response {
+/// Handler for getting a specific lyric as json, `ChordPro` or `OpenLyrics`, optionally transposed.
+/// Answers 304 if `If-None-Match` matches
+pub async fn item<R: Repo>(
+    State(connection): State<Arc<R>>,
+    key: Key<LyricId>,
+    headers: HeaderMap,
+    query: Query<LyricQuery>,
+) -> Response {
+    let transposition = match query.transposition() {
+        Ok(transposition) => transposition,
+        Err(error) => return to_bad_request(error),
+    };
     connection
         .get_lyric(key.id)
-        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .map_ok(|lyric| match transposition {
+            Some(transposition) => transpose_lyric(lyric, transposition),
+            None => lyric,
+        })
+        .map_ok_or_else(
+            to_error_response,
+            to_lyric_response(
+                StatusCode::OK,
+                if_none_match(&headers),
+                LyricFormat::from_header(&headers, ACCEPT),
+            ),
+        )
         .await
 }
 
-/// Handler for posting a new lyric
+/// Validates the lyric and checks that its title is not used by another lyric
+async fn validate_lyric<R: Repo>(
+    connection: &R,
+    rules: &Rules,
+    id: Option<LyricId>,
+    lyric_post: &LyricPost,
+) -> lipl_core::Result<()> {
+    validate(
+        rules,
+        id.map(Uuid::from),
+        lyric_post,
+        &lyric_post.title,
+        connection.get_lyric_summaries(),
+    )
+    .await
+}
+
+/// Matches the lyrics to import with the stored lyrics and validates them, with fields named like `[1].title`.
+/// A lyric replaces the stored lyric with the same title, so importing a lyric again does not add a copy.
+/// The titles must differ from each other and from the other stored titles.
+async fn match_import<R: Repo>(
+    connection: &R,
+    rules: &Rules,
+    lyric_posts: &[LyricPost],
+) -> lipl_core::Result<Vec<Option<LyricId>>> {
+    let mut summaries = connection.get_lyric_summaries().await?;
+    let ids = lyric_posts
+        .iter()
+        .map(|lyric_post| {
+            summaries
+                .iter()
+                .find(|summary| summary.title.trim() == lyric_post.title.trim())
+                .map(|summary| LyricId::from(summary.id))
+        })
+        .collect::<Vec<_>>();
+    let mut errors = vec![];
+    for (index, (lyric_post, id)) in lyric_posts.iter().zip(&ids).enumerate() {
+        let mut found = lyric_post.field_errors(rules);
+        found.extend(title_errors(
+            &summaries,
+            id.map(Uuid::from),
+            &lyric_post.title,
+            rules,
+        ));
+        errors.extend(found.into_iter().map(|mut error| {
+            error.field = format!("[{index}].{}", error.field);
+            error
+        }));
+        summaries.push(Summary {
+            id: Uuid::default(),
+            title: lyric_post.title.clone(),
+        });
+    }
+    to_result(errors).map(|()| ids)
+}
+
+/// Stores the lyrics one at a time. If one fails, the lyrics stored before it are rolled back.
+async fn import_lyrics<R: Repo>(
+    connection: &R,
+    lyric_posts: Vec<LyricPost>,
+    ids: Vec<Option<LyricId>>,
+) -> lipl_core::Result<Vec<Lyric>> {
+    let mut imported = vec![];
+    for (lyric_post, id) in lyric_posts.into_iter().zip(ids) {
+        let stored = match id {
+            Some(id) => connection.get_lyric(id).await.map(Some),
+            None => Ok(None),
+        };
+        let result = match stored {
+            Ok(previous) => connection
+                .upsert_lyric((id, lyric_post).into())
+                .await
+                .map(|lyric| (lyric, previous)),
+            Err(error) => Err(error),
+        };
+        match result {
+            Ok(lyric) => imported.push(lyric),
+            Err(error) => {
+                roll_back(connection, imported).await;
+                return Err(error);
+            }
+        }
+    }
+    Ok(imported.into_iter().map(|(lyric, _)| lyric).collect())
+}
+
+/// Undoes an import in reverse order: the replaced lyrics are stored again and the new lyrics deleted
+async fn roll_back<R: Repo>(connection: &R, imported: Vec<(Lyric, Option<Lyric>)>) {
+    for (lyric, previous) in imported.into_iter().rev() {
+        let result = match previous {
+            Some(previous) => connection.upsert_lyric(previous).await.map(|_| ()),
+            None => connection.delete_lyric(lyric.id).await,
+        };
+        if let Err(error) = result {
+            tracing::warn!(%error, "Failed to roll back the import of lyric {}", lyric.id);
+        }
+    }
+}
+
+/// Handler for posting a new lyric, answers 422 if it is invalid
 pub async fn post<R: Repo>(
     State(connection): State<Arc<R>>,
+    Extension(rules): Extension<Arc<Rules>>,
+    Extension(titles): Extension<TitleLock>,
     Json(lyric_post): Json<LyricPost>,
 ) -> Response {
+    let _titles = titles.lock().await;
+    if let Err(error) = validate_lyric(connection.as_ref(), &rules, None, &lyric_post).await {
+        return to_error_response(error);
+    }
     connection
         .upsert_lyric((None, lyric_post).into())
         .map_ok_or_else(to_error_response, to_json_response(StatusCode::CREATED))
         .await
 }
 
-/// Handler for deleting a specific lyric
-pub async fn delete<R: Repo>(State(connection): State<Arc<R>>, key: Key) -> Response {
-    connection
-        .delete_lyric(key.id)
-        .map_ok_or_else(to_error_response, to_status_ok)
+/// Handler for importing a json list of `OpenLyrics` documents, which replace the lyrics with the same title.
+/// Answers 400 without importing anything if one of the documents cannot be read and 422 if one of them is invalid.
+/// The lyrics are stored one at a time and rolled back if one of them fails.
+pub async fn import<R: Repo>(
+    State(connection): State<Arc<R>>,
+    Extension(rules): Extension<Arc<Rules>>,
+    Extension(titles): Extension<TitleLock>,
+    Json(documents): Json<Vec<String>>,
+) -> Response {
+    let lyric_posts = match documents
+        .iter()
+        .map(|document| document.parse::<OpenLyrics>().map(LyricPost::from))
+        .collect::<lipl_core::Result<Vec<_>>>()
+    {
+        Ok(lyric_posts) => lyric_posts,
+        Err(error) => return to_bad_request(error),
+    };
+    let _titles = titles.lock().await;
+    let ids = match match_import(connection.as_ref(), &rules, &lyric_posts).await {
+        Ok(ids) => ids,
+        Err(error) => return to_error_response(error),
+    };
+    import_lyrics(connection.as_ref(), lyric_posts, ids)
+        .map_ok_or_else(to_error_response, to_json_response(StatusCode::CREATED))
         .await
 }
 
-/// Handler for changing a specific lyric
+/// Handler for deleting a specific lyric, answers 412 if `If-Match` does not match
+pub async fn delete<R: Repo>(
+    State(connection): State<Arc<R>>,
+    key: Key<LyricId>,
+    headers: HeaderMap,
+) -> Response {
+    match lyric_if_match(&headers) {
+        Some(etag) => {
+            connection
+                .delete_lyric_if_match(key.id, etag)
+                .map_ok_or_else(to_error_response, to_status_ok)
+                .await
+        }
+        None => {
+            connection
+                .delete_lyric(key.id)
+                .map_ok_or_else(to_error_response, to_status_ok)
+                .await
+        }
+    }
+}
+
+/// Handler for changing a specific lyric from json, `ChordPro` or `OpenLyrics`,
+/// answers 412 if `If-Match` does not match and 422 if it is invalid
 pub async fn put<R: Repo>(
     State(connection): State<Arc<R>>,
-    key: Key,
-    Json(lyric_post): Json<LyricPost>,
+    Extension(rules): Extension<Arc<Rules>>,
+    Extension(titles): Extension<TitleLock>,
+    key: Key<LyricId>,
+    headers: HeaderMap,
+    LyricBody(lyric_post): LyricBody,
 ) -> Response {
+    let _titles = titles.lock().await;
+    if let Err(error) = validate_lyric(connection.as_ref(), &rules, Some(key.id), &lyric_post).await
+    {
+        return to_error_response(error);
+    }
+    let lyric = (Some(key.id), lyric_post).into();
+    let format = LyricFormat::from_header(&headers, ACCEPT);
+    match lyric_if_match(&headers) {
+        Some(etag) => {
+            connection
+                .upsert_lyric_if_match(lyric, etag)
+                .map_ok_or_else(
+                    to_error_response,
+                    to_lyric_response(StatusCode::OK, None, format),
+                )
+                .await
+        }
+        None => {
+            connection
+                .upsert_lyric(lyric)
+                .map_ok_or_else(
+                    to_error_response,
+                    to_lyric_response(StatusCode::OK, None, format),
+                )
+                .await
+        }
+    }
+}
+
+/// Handler for listing the revisions of a specific lyric
+pub async fn revisions<R: Repo>(State(connection): State<Arc<R>>, key: Key<LyricId>) -> Response {
     connection
-        .upsert_lyric((Some(key.id), lyric_post).into())
+        .get_lyric_revisions(key.id)
         .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
         .await
 }
+
+/// Handler for getting a specific revision of a lyric
+pub async fn revision<R: Repo>(
+    State(connection): State<Arc<R>>,
+    key: Key<LyricId>,
+    Revision(revision): Revision,
+) -> Response {
+    connection
+        .get_lyric_revision(key.id, revision)
+        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .await
+}
+
+/// Handler for restoring a lyric as it was in a specific revision
+pub async fn restore<R: Repo>(
+    State(connection): State<Arc<R>>,
+    key: Key<LyricId>,
+    Revision(revision): Revision,
+) -> Response {
+    connection
+        .restore_lyric_revision(key.id, revision)
+        .map_ok_or_else(to_error_response, to_etag_response(StatusCode::OK, None))
+        .await
+}
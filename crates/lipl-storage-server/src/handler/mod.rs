@@ -1,53 +1,113 @@
-use crate::error::ErrorReport;
+use crate::auth::User;
+use crate::error::Problem;
 use axum::{
-    extract::FromRequestParts,
+    extract::{FromRequestParts, Query, RawPathParams, Request},
+    http::{
+        HeaderMap, HeaderName,
+        header::{ETAG, IF_MATCH, IF_NONE_MATCH},
+        request::Parts,
+    },
+    middleware::Next,
     response::{IntoResponse, Json, Response},
 };
 use hyper::StatusCode;
-use lipl_core::Repo;
+use lipl_core::{
+    Cursor, Etag, IdFormat, PageRequest, Rules, Summary, Uuid, Validate, if_none_match_contains,
+    page::DEFAULT_LIMIT,
+    revision::with_author,
+    validate::{TitleRule, title_errors, to_result},
+    with_id_format,
+};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
 
+pub mod admin;
 pub mod db;
+pub mod events;
 pub mod lyric;
 pub mod playlist;
+pub mod presentation;
 
 #[derive(Deserialize)]
 pub struct ListQuery {
     full: Option<bool>,
+    limit: Option<usize>,
+    cursor: Option<Cursor>,
+    q: Option<String>,
+}
+
+impl ListQuery {
+    /// Returns a page request if the client asked for a paginated list
+    pub fn page(&self) -> Option<PageRequest> {
+        if self.limit.is_some() || self.cursor.is_some() {
+            Some(PageRequest::new(
+                self.limit.unwrap_or(DEFAULT_LIMIT),
+                self.cursor.clone(),
+            ))
+        } else {
+            None
+        }
+    }
 }
 
-pub struct Key {
-    pub id: lipl_core::Uuid,
+/// Id of the item in the `{id}` segment of the path, a `LyricId`, a `PlaylistId` or the `Uuid` of a presentation
+pub struct Key<I> {
+    pub id: I,
 }
 
-impl Key {
-    pub fn new(id: lipl_core::Uuid) -> Self {
+impl<I> Key<I> {
+    pub fn new(id: I) -> Self {
         Self { id }
     }
 }
 
-impl<R: Repo + Sync> FromRequestParts<Arc<R>> for Key {
-    type Rejection = StatusCode;
-
-    async fn from_request_parts(
-        parts: &mut axum::http::request::Parts,
-        _state: &Arc<R>,
-    ) -> Result<Self, Self::Rejection> {
-        parts
-            .uri
-            .path()
-            .split('/')
-            .next_back()
-            .ok_or(StatusCode::NOT_FOUND)
-            .and_then(|s| {
-                s.parse::<lipl_core::Uuid>()
-                    .map_err(|_| StatusCode::NOT_FOUND)
-            })
-            .map(Key::new)
+impl<S: Send + Sync, I: FromStr + Send> FromRequestParts<S> for Key<I> {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        path_parameter(parts, state, "id").await.map(Key::new)
     }
 }
 
+/// Number in the `{revision}` segment of the path
+pub struct Revision(pub u32);
+
+impl<S: Send + Sync> FromRequestParts<S> for Revision {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        path_parameter(parts, state, "revision").await.map(Revision)
+    }
+}
+
+/// Parses the path parameter `name`, answers 404 with problem details if it is missing or invalid
+async fn path_parameter<S: Send + Sync, T: FromStr>(
+    parts: &mut Parts,
+    state: &S,
+    name: &str,
+) -> Result<T, Response> {
+    let not_found = || {
+        Problem::new(
+            StatusCode::NOT_FOUND,
+            "not-found",
+            format!("There is no item with this {name}"),
+        )
+        .into_response()
+    };
+    RawPathParams::from_request_parts(parts, state)
+        .await
+        .ok()
+        .and_then(|params| {
+            params
+                .iter()
+                .find(|(key, _)| *key == name)
+                .and_then(|(_, value)| value.parse().ok())
+        })
+        .ok_or_else(not_found)
+}
+
 pub(crate) fn to_json_response<T>(status_code: StatusCode) -> impl Fn(T) -> Response
 where
     T: Serialize,
@@ -55,18 +115,130 @@ where
     move |t| (status_code, Json(t)).into_response()
 }
 
-fn not_found_or_internal_server(error: &lipl_core::Error) -> StatusCode {
-    match error {
-        lipl_core::Error::NoKey(_) => StatusCode::NOT_FOUND,
-        _ => StatusCode::INTERNAL_SERVER_ERROR,
+fn header(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+/// Returns the value of the `If-Match` header, if present
+pub(crate) fn if_match(headers: &HeaderMap) -> Option<String> {
+    header(headers, IF_MATCH)
+}
+
+/// Returns the value of the `If-None-Match` header, if present
+pub(crate) fn if_none_match(headers: &HeaderMap) -> Option<String> {
+    header(headers, IF_NONE_MATCH)
+}
+
+/// Middleware that makes the authenticated user the author of the revisions created by the request
+pub(crate) async fn author(request: Request, next: Next) -> Response {
+    let user = request
+        .extensions()
+        .get::<User>()
+        .map(|user| user.0.clone());
+    with_author(user, next.run(request)).await
+}
+
+#[derive(Deserialize)]
+struct IdFormatQuery {
+    #[serde(default)]
+    id_format: IdFormat,
+}
+
+/// Middleware that writes the ids in the response in the format asked for with `?id_format=bs58` or `?id_format=uuid`
+pub(crate) async fn id_format(request: Request, next: Next) -> Response {
+    match Query::<IdFormatQuery>::try_from_uri(request.uri()) {
+        Ok(Query(query)) => with_id_format(query.id_format, next.run(request)).await,
+        Err(rejection) => {
+            Problem::new(StatusCode::BAD_REQUEST, "parse", rejection.body_text()).into_response()
+        }
     }
 }
-pub(crate) fn to_error_response(error: lipl_core::Error) -> Response {
-    (
-        not_found_or_internal_server(&error),
-        Json(ErrorReport::from(error)),
+
+/// Responds with `body` and the entity tag of `t` in an `ETag` header, answers 304 if it matches `if_none_match`
+pub(crate) fn etag_response<T>(
+    status_code: StatusCode,
+    if_none_match: Option<&str>,
+    t: &T,
+    body: impl IntoResponse,
+) -> Response
+where
+    T: Serialize,
+{
+    tagged_response(
+        status_code,
+        if_none_match,
+        t.etag().unwrap_or_default(),
+        body,
     )
-        .into_response()
+}
+
+/// Responds with `body` and `etag` in an `ETag` header, answers 304 if it matches `if_none_match`
+pub(crate) fn tagged_response(
+    status_code: StatusCode,
+    if_none_match: Option<&str>,
+    etag: String,
+    body: impl IntoResponse,
+) -> Response {
+    if if_none_match.is_some_and(|header| if_none_match_contains(header, &etag)) {
+        (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response()
+    } else {
+        (status_code, [(ETAG, etag)], body).into_response()
+    }
+}
+
+/// Responds with the entity tag in an `ETag` header and answers 304 if it matches `if_none_match`
+pub(crate) fn to_etag_response<T>(
+    status_code: StatusCode,
+    if_none_match: Option<String>,
+) -> impl Fn(T) -> Response
+where
+    T: Serialize,
+{
+    move |t| etag_response(status_code, if_none_match.as_deref(), &t, Json(&t))
+}
+
+/// Responds with the problem details of `error`, with a status that depends on the kind of error
+pub(crate) fn to_error_response(error: lipl_core::Error) -> Response {
+    Problem::from(&error).into_response()
+}
+
+/// Responds with 400 for a request that cannot be read
+pub(crate) fn to_bad_request(error: lipl_core::Error) -> Response {
+    Problem::new(StatusCode::BAD_REQUEST, error.code(), &error).into_response()
+}
+
+/// Serializes the writes that check the title, so two requests cannot both store a title that was free.
+/// The unique indexes of postgres and turso also cover servers that share a database.
+#[derive(Clone, Default)]
+pub struct TitleLock(Arc<Mutex<()>>);
+
+impl TitleLock {
+    /// Held from the title check until the item is stored
+    pub(crate) async fn lock(&self) -> MutexGuard<'_, ()> {
+        self.0.lock().await
+    }
+}
+
+/// Validates `post` and checks `title` against the titles in `summaries`, which are only read if titles must be unique
+pub(crate) async fn validate<T, F>(
+    rules: &Rules,
+    id: Option<Uuid>,
+    post: &T,
+    title: &str,
+    summaries: F,
+) -> lipl_core::Result<()>
+where
+    T: Validate,
+    F: Future<Output = lipl_core::Result<Vec<Summary>>>,
+{
+    let mut errors = post.field_errors(rules);
+    if rules.titles != TitleRule::Any {
+        errors.extend(title_errors(&summaries.await?, id, title, rules));
+    }
+    to_result(errors)
 }
 
 pub(crate) fn to_status_ok<T>(_: T) -> Response {
@@ -1,18 +1,33 @@
 use super::ListQuery;
-use super::{Key, to_error_response, to_json_response, to_status_ok};
+use super::{
+    Key, Revision, TitleLock, if_match, if_none_match, to_error_response, to_etag_response,
+    to_json_response, to_status_ok, validate,
+};
 use axum::{
     Json,
-    extract::{Query, State},
-    http::StatusCode,
+    extract::{Extension, Query, State},
+    http::{HeaderMap, StatusCode},
     response::Response,
 };
 use futures_util::TryFutureExt;
-use lipl_core::{PlaylistPost, Repo};
+use lipl_core::{PlaylistId, PlaylistPost, Repo, Rules, Uuid};
 use std::sync::Arc;
 
-/// Handler for getting all playlists
+/// Handler for getting all playlists, optionally one page at a time
 pub async fn list<R: Repo>(State(connection): State<Arc<R>>, query: Query<ListQuery>) -> Response {
-    if query.full == Some(true) {
+    if let Some(page) = query.page() {
+        if query.full == Some(true) {
+            connection
+                .get_playlists_page(page)
+                .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+                .await
+        } else {
+            connection
+                .get_playlist_summaries_page(page)
+                .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+                .await
+        }
+    } else if query.full == Some(true) {
         connection
             .get_playlists()
             .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
@@ -25,41 +40,140 @@ pub async fn list<R: Repo>(State(connection): State<Arc<R>>, query: Query<ListQu
     }
 }
 
-/// Handler for getting a specific playlist
-pub async fn item<R: Repo>(State(connection): State<Arc<R>>, key: Key) -> Response {
+/// Handler for getting a specific playlist, answers 304 if `If-None-Match` matches
+pub async fn item<R: Repo>(
+    State(connection): State<Arc<R>>,
+    key: Key<PlaylistId>,
+    headers: HeaderMap,
+) -> Response {
     connection
         .get_playlist(key.id)
-        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .map_ok_or_else(
+            to_error_response,
+            to_etag_response(StatusCode::OK, if_none_match(&headers)),
+        )
         .await
 }
 
-/// Handler for posting a new playlist
+/// Validates the playlist and checks that its title is not used by another playlist
+async fn validate_playlist<R: Repo>(
+    connection: &R,
+    rules: &Rules,
+    id: Option<PlaylistId>,
+    playlist_post: &PlaylistPost,
+) -> lipl_core::Result<()> {
+    validate(
+        rules,
+        id.map(Uuid::from),
+        playlist_post,
+        &playlist_post.title,
+        connection.get_playlist_summaries(),
+    )
+    .await
+}
+
+/// Handler for posting a new playlist, answers 422 if it is invalid
 pub async fn post<R: Repo>(
     State(connection): State<Arc<R>>,
+    Extension(rules): Extension<Arc<Rules>>,
+    Extension(titles): Extension<TitleLock>,
     Json(playlist_post): Json<PlaylistPost>,
 ) -> Response {
+    let _titles = titles.lock().await;
+    if let Err(error) = validate_playlist(connection.as_ref(), &rules, None, &playlist_post).await {
+        return to_error_response(error);
+    }
     connection
         .upsert_playlist((None, playlist_post).into())
         .map_ok_or_else(to_error_response, to_json_response(StatusCode::CREATED))
         .await
 }
 
-/// Handler for deleting a specific playlist
-pub async fn delete<R: Repo>(State(connection): State<Arc<R>>, key: Key) -> Response {
-    connection
-        .delete_playlist(key.id)
-        .map_ok_or_else(to_error_response, to_status_ok)
-        .await
+/// Handler for deleting a specific playlist, answers 412 if `If-Match` does not match
+pub async fn delete<R: Repo>(
+    State(connection): State<Arc<R>>,
+    key: Key<PlaylistId>,
+    headers: HeaderMap,
+) -> Response {
+    match if_match(&headers) {
+        Some(etag) => {
+            connection
+                .delete_playlist_if_match(key.id, etag)
+                .map_ok_or_else(to_error_response, to_status_ok)
+                .await
+        }
+        None => {
+            connection
+                .delete_playlist(key.id)
+                .map_ok_or_else(to_error_response, to_status_ok)
+                .await
+        }
+    }
 }
 
-/// Handler for changing a specific playlist
+/// Handler for changing a specific playlist, answers 412 if `If-Match` does not match and 422 if it is invalid
 pub async fn put<R: Repo>(
     State(connection): State<Arc<R>>,
-    key: Key,
+    Extension(rules): Extension<Arc<Rules>>,
+    Extension(titles): Extension<TitleLock>,
+    key: Key<PlaylistId>,
+    headers: HeaderMap,
     Json(playlist_post): Json<PlaylistPost>,
+) -> Response {
+    let _titles = titles.lock().await;
+    if let Err(error) =
+        validate_playlist(connection.as_ref(), &rules, Some(key.id), &playlist_post).await
+    {
+        return to_error_response(error);
+    }
+    let playlist = (Some(key.id), playlist_post).into();
+    match if_match(&headers) {
+        Some(etag) => {
+            connection
+                .upsert_playlist_if_match(playlist, etag)
+                .map_ok_or_else(to_error_response, to_etag_response(StatusCode::OK, None))
+                .await
+        }
+        None => {
+            connection
+                .upsert_playlist(playlist)
+                .map_ok_or_else(to_error_response, to_etag_response(StatusCode::OK, None))
+                .await
+        }
+    }
+}
+
+/// Handler for listing the revisions of a specific playlist
+pub async fn revisions<R: Repo>(
+    State(connection): State<Arc<R>>,
+    key: Key<PlaylistId>,
 ) -> Response {
     connection
-        .upsert_playlist((Some(key.id), playlist_post).into())
+        .get_playlist_revisions(key.id)
         .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
         .await
 }
+
+/// Handler for getting a specific revision of a playlist
+pub async fn revision<R: Repo>(
+    State(connection): State<Arc<R>>,
+    key: Key<PlaylistId>,
+    Revision(revision): Revision,
+) -> Response {
+    connection
+        .get_playlist_revision(key.id, revision)
+        .map_ok_or_else(to_error_response, to_json_response(StatusCode::OK))
+        .await
+}
+
+/// Handler for restoring a playlist as it was in a specific revision
+pub async fn restore<R: Repo>(
+    State(connection): State<Arc<R>>,
+    key: Key<PlaylistId>,
+    Revision(revision): Revision,
+) -> Response {
+    connection
+        .restore_playlist_revision(key.id, revision)
+        .map_ok_or_else(to_error_response, to_etag_response(StatusCode::OK, None))
+        .await
+}
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::collections::hash_map::{Entry, OccupiedEntry};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+use std::time::{Duration, Instant};
+
+use axum::{
+    Extension, Json,
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use lipl_core::{
+    Error, HasSummary, IdFormat, Lyric, PlaylistId, Repo, Summary, Uuid, chords::strip, id_format,
+    parts::expand, with_id_format_sync,
+};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tokio::sync::watch;
+
+use super::{Key, to_error_response, to_json_response};
+use crate::auth::forbidden;
+
+/// Header with the token that the operator of a presentation gets when it is opened
+pub const OPERATOR_TOKEN: &str = "x-operator-token";
+/// A presentation that is not operated for this long is closed
+const IDLE: Duration = Duration::from_secs(4 * 60 * 60);
+/// How often idle presentations are looked for
+const SWEEP: Duration = Duration::from_secs(60);
+
+/// A lyric as it is projected: the parts in play order, without chords
+struct Song {
+    summary: Summary,
+    parts: Vec<Vec<String>>,
+}
+
+impl From<Lyric> for Song {
+    fn from(lyric: Lyric) -> Self {
+        Self {
+            summary: lyric.summary(),
+            parts: expand(&lyric.parts)
+                .into_iter()
+                .map(|part| part.iter().map(|line| strip(line)).collect())
+                .collect(),
+        }
+    }
+}
+
+/// Index of the lyric in the playlist and of the part in that lyric
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Position {
+    pub lyric: usize,
+    pub part: usize,
+}
+
+/// What the followers of a presentation see
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Slide {
+    pub session: Uuid,
+    pub playlist: Summary,
+    pub position: Position,
+    /// The lyric that is shown, none if the playlist has no lyric with parts
+    pub lyric: Option<Summary>,
+    pub part: Vec<String>,
+    /// The part after this one, which can be the first part of the next lyric
+    pub next: Option<Vec<String>>,
+}
+
+/// The lyrics of a presentation and the part that is shown
+struct Deck {
+    session: Uuid,
+    playlist: Summary,
+    songs: Vec<Song>,
+    position: Position,
+}
+
+impl Deck {
+    fn part(&self, position: Position) -> Option<&Vec<String>> {
+        self.songs
+            .get(position.lyric)
+            .and_then(|song| song.parts.get(position.part))
+    }
+
+    /// The first part of the first lyric with parts from `lyric` on
+    fn first_from(&self, lyric: usize) -> Option<Position> {
+        (lyric..self.songs.len())
+            .find(|index| !self.songs[*index].parts.is_empty())
+            .map(|lyric| Position { lyric, part: 0 })
+    }
+
+    fn after(&self, position: Position) -> Option<Position> {
+        let next = Position {
+            part: position.part + 1,
+            ..position
+        };
+        self.part(next)
+            .map(|_| next)
+            .or_else(|| self.first_from(position.lyric + 1))
+    }
+
+    fn before(&self, position: Position) -> Option<Position> {
+        if position.part > 0 {
+            return Some(Position {
+                part: position.part - 1,
+                ..position
+            });
+        }
+        (0..position.lyric)
+            .rev()
+            .find(|index| !self.songs[*index].parts.is_empty())
+            .map(|lyric| Position {
+                lyric,
+                part: self.songs[lyric].parts.len() - 1,
+            })
+    }
+
+    fn slide(&self) -> Slide {
+        Slide {
+            session: self.session,
+            playlist: self.playlist.clone(),
+            position: self.position,
+            lyric: self
+                .part(self.position)
+                .map(|_| self.songs[self.position.lyric].summary.clone()),
+            part: self.part(self.position).cloned().unwrap_or_default(),
+            next: self
+                .after(self.position)
+                .and_then(|next| self.part(next))
+                .cloned(),
+        }
+    }
+}
+
+struct Session {
+    deck: Deck,
+    slides: watch::Sender<Slide>,
+    /// Required to operate the presentation
+    token: String,
+    operated: Instant,
+}
+
+impl Session {
+    fn is_operated_with(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(OPERATOR_TOKEN)
+            .is_some_and(|token| bool::from(token.as_bytes().ct_eq(self.token.as_bytes())))
+    }
+
+    /// Moves to the position that `to` returns, if any
+    fn go<F>(&mut self, to: F) -> Slide
+    where
+        F: FnOnce(&Deck) -> Option<Position>,
+    {
+        let position = to(&self.deck).unwrap_or(self.deck.position);
+        self.show(position)
+    }
+
+    /// Shows the part at `position` to the followers, if it is not shown already
+    fn show(&mut self, position: Position) -> Slide {
+        self.deck.position = position;
+        let slide = self.deck.slide();
+        self.slides.send_if_modified(|shown| {
+            let modified = *shown != slide;
+            if modified {
+                shown.clone_from(&slide);
+            }
+            modified
+        });
+        slide
+    }
+}
+
+type Sessions = Mutex<HashMap<Uuid, Session>>;
+
+/// Closes the sessions that are idle, which closes the connections of their followers
+fn close_idle(sessions: &Sessions) -> MutexGuard<'_, HashMap<Uuid, Session>> {
+    let mut sessions = sessions.lock().unwrap();
+    sessions.retain(|_, session| session.operated.elapsed() < IDLE);
+    sessions
+}
+
+/// The presentations that are running, kept in memory until they are closed or idle for too long.
+/// The lyrics of the playlist are read when the presentation is opened.
+#[derive(Clone, Default)]
+pub struct Presentations {
+    sessions: Arc<Sessions>,
+    /// Starts the sweep for idle sessions with the first presentation
+    sweep: Arc<OnceLock<()>>,
+}
+
+impl Presentations {
+    /// The sessions, without the ones that are idle
+    fn sessions(&self) -> MutexGuard<'_, HashMap<Uuid, Session>> {
+        close_idle(&self.sessions)
+    }
+
+    /// Closes the idle sessions every minute, also when nobody uses the presentations, until they are dropped
+    fn start_sweep(&self) {
+        let sessions = Arc::downgrade(&self.sessions);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP);
+            loop {
+                interval.tick().await;
+                match Weak::upgrade(&sessions) {
+                    Some(sessions) => drop(close_idle(&sessions)),
+                    None => return,
+                }
+            }
+        });
+    }
+
+    /// Opens a presentation and returns the first slide with the operator token
+    fn open(&self, playlist: Summary, lyrics: Vec<Lyric>) -> (Slide, String) {
+        let mut deck = Deck {
+            session: Uuid::default(),
+            playlist,
+            songs: lyrics.into_iter().map(Song::from).collect(),
+            position: Position::default(),
+        };
+        deck.position = deck.first_from(0).unwrap_or_default();
+        let slide = deck.slide();
+        let token = lipl_core::reexport::uuid::Uuid::new_v4()
+            .simple()
+            .to_string();
+        let session = Session {
+            deck,
+            slides: watch::Sender::new(slide.clone()),
+            token: token.clone(),
+            operated: Instant::now(),
+        };
+        self.sweep.get_or_init(|| self.start_sweep());
+        self.sessions().insert(slide.session, session);
+        (slide, token)
+    }
+
+    /// Applies `f` to the session with id `id`
+    fn with<T>(&self, id: Uuid, f: impl FnOnce(&mut Session) -> T) -> lipl_core::Result<T> {
+        self.sessions()
+            .get_mut(&id)
+            .map(f)
+            .ok_or(Error::NotFound(id))
+    }
+
+    /// Applies `f` to the session with id `id` for its operator, answers 403 if `headers` lack the operator token
+    fn operate<F>(&self, id: Uuid, headers: &HeaderMap, f: F) -> Response
+    where
+        F: FnOnce(OccupiedEntry<'_, Uuid, Session>) -> Response,
+    {
+        match self.sessions().entry(id) {
+            Entry::Vacant(_) => to_error_response(Error::NotFound(id)),
+            Entry::Occupied(entry) if !entry.get().is_operated_with(headers) => {
+                forbidden("The operator token of the presentation is required")
+            }
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().operated = Instant::now();
+                f(entry)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Open {
+    playlist: PlaylistId,
+}
+
+/// Handler for opening a presentation of a playlist, which starts at the first part.
+/// The operator token is returned in the `X-Operator-Token` header.
+pub async fn open<R>(
+    State(repo): State<Arc<R>>,
+    Extension(presentations): Extension<Presentations>,
+    Json(open): Json<Open>,
+) -> Response
+where
+    R: Repo + Send + Sync + 'static,
+{
+    let playlist = match repo.get_playlist(open.playlist).await {
+        Ok(playlist) => playlist,
+        Err(error) => return to_error_response(error),
+    };
+    let mut lyrics = vec![];
+    for member in &playlist.members {
+        match repo.get_lyric(*member).await {
+            Ok(lyric) => lyrics.push(lyric),
+            Err(error) => return to_error_response(error),
+        }
+    }
+    let (slide, token) = presentations.open(playlist.summary(), lyrics);
+    (
+        [(OPERATOR_TOKEN, token)],
+        to_json_response(StatusCode::CREATED)(slide),
+    )
+        .into_response()
+}
+
+fn to_slide_response(result: lipl_core::Result<Slide>) -> Response {
+    result.map_or_else(to_error_response, to_json_response(StatusCode::OK))
+}
+
+/// Handler for the slide that is shown
+pub async fn get(Extension(presentations): Extension<Presentations>, key: Key<Uuid>) -> Response {
+    to_slide_response(presentations.with(key.id, |session| session.deck.slide()))
+}
+
+/// Handler for showing the part at a position, answers 422 if there is no part at that position
+pub async fn put(
+    Extension(presentations): Extension<Presentations>,
+    key: Key<Uuid>,
+    headers: HeaderMap,
+    Json(position): Json<Position>,
+) -> Response {
+    presentations.operate(key.id, &headers, |mut entry| {
+        let session = entry.get_mut();
+        to_slide_response(
+            session
+                .deck
+                .part(position)
+                .is_some()
+                .then(|| session.show(position))
+                .ok_or(Error::Argument("there is no part at this position")),
+        )
+    })
+}
+
+/// Handler for showing the next part, at the end the last part stays
+pub async fn next(
+    Extension(presentations): Extension<Presentations>,
+    key: Key<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    presentations.operate(key.id, &headers, |mut entry| {
+        to_slide_response(Ok(entry.get_mut().go(|deck| deck.after(deck.position))))
+    })
+}
+
+/// Handler for showing the previous part, at the start the first part stays
+pub async fn previous(
+    Extension(presentations): Extension<Presentations>,
+    key: Key<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    presentations.operate(key.id, &headers, |mut entry| {
+        to_slide_response(Ok(entry.get_mut().go(|deck| deck.before(deck.position))))
+    })
+}
+
+/// Handler for closing a presentation, which closes the connections of the followers
+pub async fn close(
+    Extension(presentations): Extension<Presentations>,
+    key: Key<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    presentations.operate(key.id, &headers, |entry| {
+        entry.remove();
+        StatusCode::OK.into_response()
+    })
+}
+
+/// Handler for following a presentation over a WebSocket.
+/// The follower gets the slide that is shown as json when it connects and whenever the slide changes.
+pub async fn follow(
+    Extension(presentations): Extension<Presentations>,
+    key: Key<Uuid>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    match presentations.with(key.id, |session| session.slides.subscribe()) {
+        Ok(slides) => {
+            let format = id_format();
+            upgrade.on_upgrade(move |socket| send_slides(socket, slides, format))
+        }
+        Err(error) => to_error_response(error),
+    }
+}
+
+/// Sends the slides with their ids in `format`, which the handler captures because the socket runs in a task of its own
+async fn send_slides(mut socket: WebSocket, mut slides: watch::Receiver<Slide>, format: IdFormat) {
+    loop {
+        let Ok(text) = with_id_format_sync(format, || {
+            serde_json::to_string(&*slides.borrow_and_update())
+        }) else {
+            return;
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            return;
+        }
+        loop {
+            tokio::select! {
+                changed = slides.changed() => {
+                    if changed.is_err() {
+                        // The presentation is closed
+                        let _ = socket.send(Message::Close(None)).await;
+                        return;
+                    }
+                    break;
+                }
+                message = socket.recv() => {
+                    if !matches!(message, Some(Ok(message)) if !matches!(message, Message::Close(_))) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
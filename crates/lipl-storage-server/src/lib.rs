@@ -1,12 +1,13 @@
-use axum::Router;
-use axum::routing::get;
+use axum::middleware::from_fn;
+use axum::routing::{get, post};
+use axum::{Extension, Router};
 use hyper::StatusCode;
-use lipl_core::{Repo, RepoConfig};
+use hyper::header::CONTENT_TYPE;
+use lipl_core::{Repo, RepoConfig, Rules};
 use std::sync::Arc;
 use tokio::signal::unix::{SignalKind, signal};
 use tower::ServiceBuilder;
 use tower::layer::util::{Identity, Stack};
-use tower_http::auth::AddAuthorizationLayer;
 use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
 use tower_http::compression::CompressionLayer;
 use tower_http::trace::{
@@ -15,16 +16,25 @@ use tower_http::trace::{
 };
 use tracing::Level;
 
-use crate::environment::{password, username};
+pub use crate::auth::{User, Users};
+use crate::auth::{authenticate, require_admin};
 #[cfg(feature = "pwa")]
 pub use crate::error::Error;
-use crate::handler::{db, lyric, playlist};
+pub use crate::error::{PROBLEM_JSON, Problem};
+pub use crate::handler::admin::TransactionLog;
+pub use crate::handler::presentation::{OPERATOR_TOKEN, Position, Slide};
+use crate::handler::{admin, author, db, events, id_format, lyric, playlist, presentation};
+pub use crate::metrics::{METRICS_CONTENT_TYPE, Metrics, TracedRepo};
+pub use crate::read_only::{ReadOnly, ReadOnlyRepo};
 
+mod auth;
 pub mod constant;
 pub mod environment;
 mod error;
 mod handler;
 mod message;
+mod metrics;
+mod read_only;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -105,37 +115,140 @@ async fn health() -> StatusCode {
     StatusCode::OK
 }
 
+/// Router that validates lyrics and playlists with the default rules
 pub fn create_router<S>(state: S) -> Router
 where
     S: Repo + 'static + Send + Sync,
 {
-    Router::new()
-        .route(&format!("{}/health", constant::PREFIX), get(health))
-        .nest(
-            constant::PREFIX,
+    create_router_with_rules(state, Rules::default())
+}
+
+/// Router that validates with `rules` and accepts changes until read only mode is switched on
+pub fn create_router_with_rules<S>(state: S, rules: Rules) -> Router
+where
+    S: Repo + 'static + Send + Sync,
+{
+    create_router_with_mode(state, rules, ReadOnly::default())
+}
+
+/// Router without users, the api is open and `/admin` answers 403 because there is no admin
+pub fn create_router_with_mode<S>(state: S, rules: Rules, read_only: ReadOnly) -> Router
+where
+    S: Repo + 'static + Send + Sync,
+{
+    router(state, rules, read_only, None)
+}
+
+/// Router that authenticates `users` with basic credentials, see `Users::new` and `Users::admin` for the routes that need them.
+/// `/admin` answers 403 for the users other than the admin.
+pub fn create_router_with_users<S>(
+    state: S,
+    rules: Rules,
+    read_only: ReadOnly,
+    users: Users,
+) -> Router
+where
+    S: Repo + 'static + Send + Sync,
+{
+    router(state, rules, read_only, Some(users))
+}
+
+/// Router that traces the calls to the repo, with the metrics at `/metrics`.
+/// Changes are rejected while `read_only` is on, which can be switched at `/admin/read-only`.
+/// `/admin/compact` and `/admin/history` work on the transaction log if a `TransactionLog` is added as extension.
+fn router<S>(state: S, rules: Rules, read_only: ReadOnly, users: Option<Users>) -> Router
+where
+    S: Repo + 'static + Send + Sync,
+{
+    let metrics = Arc::new(Metrics::default());
+    let state = ReadOnlyRepo::new(state, read_only.clone());
+    routes(
+        TracedRepo::new(state, metrics.clone()),
+        rules,
+        read_only,
+        users,
+    )
+    .route(
+        "/metrics",
+        get(|| async move { ([(CONTENT_TYPE, METRICS_CONTENT_TYPE)], metrics.render()) }),
+    )
+}
+
+fn routes<S>(state: S, rules: Rules, read_only: ReadOnly, users: Option<Users>) -> Router
+where
+    S: Repo + 'static + Send + Sync,
+{
+    let api = Router::new()
+        .route("/lyric", get(lyric::list::<S>).post(lyric::post::<S>))
+        .route("/lyric/import", post(lyric::import::<S>))
+        .route(
+            "/lyric/{id}",
+            get(lyric::item::<S>)
+                .delete(lyric::delete::<S>)
+                .put(lyric::put::<S>),
+        )
+        .route("/lyric/{id}/revisions", get(lyric::revisions::<S>))
+        .route(
+            "/lyric/{id}/revisions/{revision}",
+            get(lyric::revision::<S>),
+        )
+        .route(
+            "/lyric/{id}/revisions/{revision}/restore",
+            post(lyric::restore::<S>),
+        )
+        .route(
+            "/playlist",
+            get(playlist::list::<S>).post(playlist::post::<S>),
+        )
+        .route(
+            "/playlist/{id}",
+            get(playlist::item::<S>)
+                .delete(playlist::delete::<S>)
+                .put(playlist::put::<S>),
+        )
+        .route("/playlist/{id}/revisions", get(playlist::revisions::<S>))
+        .route(
+            "/playlist/{id}/revisions/{revision}",
+            get(playlist::revision::<S>),
+        )
+        .route(
+            "/playlist/{id}/revisions/{revision}/restore",
+            post(playlist::restore::<S>),
+        )
+        .route("/db", get(db::get::<S>).put(db::put::<S>))
+        .merge(
             Router::new()
-                .route("/lyric", get(lyric::list::<S>).post(lyric::post::<S>))
-                .route(
-                    "/lyric/{id}",
-                    get(lyric::item::<S>)
-                        .delete(lyric::delete::<S>)
-                        .put(lyric::put::<S>),
-                )
-                .route(
-                    "/playlist",
-                    get(playlist::list::<S>).post(playlist::post::<S>),
-                )
                 .route(
-                    "/playlist/{id}",
-                    get(playlist::item::<S>)
-                        .delete(playlist::delete::<S>)
-                        .put(playlist::put::<S>),
+                    "/admin/read-only",
+                    get(admin::get_read_only).put(admin::put_read_only),
                 )
-                .route("/db", get(db::get::<S>).put(db::put::<S>))
-                .layer(AddAuthorizationLayer::basic(
-                    &username().unwrap(),
-                    &password().unwrap(),
-                ))
-                .with_state(Arc::new(state)),
+                .route("/admin/compact", post(admin::compact))
+                .route("/admin/history", get(admin::history))
+                .route_layer(from_fn(require_admin)),
+        )
+        .route("/events", get(events::events::<S>))
+        .route("/presentation", post(presentation::open::<S>))
+        .route(
+            "/presentation/{id}",
+            get(presentation::get)
+                .put(presentation::put)
+                .delete(presentation::close),
         )
+        .route("/presentation/{id}/next", post(presentation::next))
+        .route("/presentation/{id}/previous", post(presentation::previous))
+        .route("/presentation/{id}/follow", get(presentation::follow))
+        .layer(from_fn(author))
+        .layer(from_fn(id_format))
+        .layer(Extension(Arc::new(rules)))
+        .layer(Extension(handler::TitleLock::default()))
+        .layer(Extension(read_only))
+        .layer(Extension(events::Events::default()))
+        .layer(Extension(presentation::Presentations::default()));
+    let api = match users {
+        Some(users) => api.layer(from_fn(authenticate)).layer(Extension(users)),
+        None => api,
+    };
+    Router::new()
+        .route(&format!("{}/health", constant::PREFIX), get(health))
+        .nest(constant::PREFIX, api.with_state(Arc::new(state)))
 }
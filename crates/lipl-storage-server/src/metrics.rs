@@ -0,0 +1,359 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use lipl_core::{
+    Lyric, LyricId, Page, PageRequest, Playlist, PlaylistId, Repo, Result, Revision, RevisionInfo,
+    Summary, change::ChangeStream, search::Hit,
+};
+use tracing::{Instrument, Span, info_span};
+
+/// Upper bounds in seconds of the latency histogram buckets
+const BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Default)]
+struct Operation {
+    /// Calls per bucket, not cumulative
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    seconds: f64,
+    errors: u64,
+}
+
+/// Latency and errors per `Repo` method
+#[derive(Default)]
+pub struct Metrics {
+    operations: Mutex<BTreeMap<&'static str, Operation>>,
+}
+
+impl Metrics {
+    fn record(&self, name: &'static str, elapsed: Duration, failed: bool) {
+        let seconds = elapsed.as_secs_f64();
+        let mut operations = self.operations.lock().unwrap();
+        let operation = operations.entry(name).or_default();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            operation.buckets[bucket] += 1;
+        }
+        operation.count += 1;
+        operation.seconds += seconds;
+        if failed {
+            operation.errors += 1;
+        }
+    }
+
+    /// The metrics in the Prometheus text format
+    #[must_use]
+    pub fn render(&self) -> String {
+        let operations = self.operations.lock().unwrap();
+        let mut text = String::new();
+        text.push_str("# HELP lipl_repo_duration_seconds Duration of repo operations\n");
+        text.push_str("# TYPE lipl_repo_duration_seconds histogram\n");
+        for (name, operation) in operations.iter() {
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(operation.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    text,
+                    "lipl_repo_duration_seconds_bucket{{operation=\"{name}\",le=\"{bound}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                text,
+                "lipl_repo_duration_seconds_bucket{{operation=\"{name}\",le=\"+Inf\"}} {}",
+                operation.count
+            );
+            let _ = writeln!(
+                text,
+                "lipl_repo_duration_seconds_sum{{operation=\"{name}\"}} {}",
+                operation.seconds
+            );
+            let _ = writeln!(
+                text,
+                "lipl_repo_duration_seconds_count{{operation=\"{name}\"}} {}",
+                operation.count
+            );
+        }
+        text.push_str("# HELP lipl_repo_errors_total Failed repo operations\n");
+        text.push_str("# TYPE lipl_repo_errors_total counter\n");
+        for (name, operation) in operations.iter() {
+            let _ = writeln!(
+                text,
+                "lipl_repo_errors_total{{operation=\"{name}\"}} {}",
+                operation.errors
+            );
+        }
+        text
+    }
+}
+
+/// Repo that runs every method of the inner repo in a span and records its latency and errors in `Metrics`
+#[derive(Clone)]
+pub struct TracedRepo<R> {
+    inner: R,
+    metrics: Arc<Metrics>,
+}
+
+impl<R> TracedRepo<R> {
+    pub fn new(inner: R, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    async fn traced<T, F>(&self, name: &'static str, span: Span, f: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let start = Instant::now();
+        let result = f.instrument(span.clone()).await;
+        self.metrics.record(name, start.elapsed(), result.is_err());
+        if let Err(error) = &result {
+            span.in_scope(|| tracing::debug!(%error, "{name} failed"));
+        }
+        result
+    }
+}
+
+macro_rules! span {
+    ($name:literal $(, $($field:tt)*)?) => {
+        info_span!("repo", operation = $name $(, $($field)*)?)
+    };
+}
+
+impl<R: Repo + Sync> Repo for TracedRepo<R> {
+    async fn get_lyrics(&self) -> Result<Vec<Lyric>> {
+        self.traced("get_lyrics", span!("get_lyrics"), self.inner.get_lyrics())
+            .await
+    }
+
+    async fn get_lyric_summaries(&self) -> Result<Vec<Summary>> {
+        self.traced(
+            "get_lyric_summaries",
+            span!("get_lyric_summaries"),
+            self.inner.get_lyric_summaries(),
+        )
+        .await
+    }
+
+    async fn get_lyrics_page(&self, page: PageRequest) -> Result<Page<Lyric>> {
+        self.traced(
+            "get_lyrics_page",
+            span!("get_lyrics_page", limit = page.limit),
+            self.inner.get_lyrics_page(page),
+        )
+        .await
+    }
+
+    async fn get_lyric_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.traced(
+            "get_lyric_summaries_page",
+            span!("get_lyric_summaries_page", limit = page.limit),
+            self.inner.get_lyric_summaries_page(page),
+        )
+        .await
+    }
+
+    async fn get_lyric(&self, id: LyricId) -> Result<Lyric> {
+        self.traced(
+            "get_lyric",
+            span!("get_lyric", %id),
+            self.inner.get_lyric(id),
+        )
+        .await
+    }
+
+    async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
+        let span = span!("upsert_lyric", id = %lyric.id, title = %lyric.title);
+        self.traced("upsert_lyric", span, self.inner.upsert_lyric(lyric))
+            .await
+    }
+
+    async fn delete_lyric(&self, id: LyricId) -> Result<()> {
+        self.traced(
+            "delete_lyric",
+            span!("delete_lyric", %id),
+            self.inner.delete_lyric(id),
+        )
+        .await
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, if_match: String) -> Result<Lyric> {
+        let span = span!("upsert_lyric_if_match", id = %lyric.id, title = %lyric.title);
+        self.traced(
+            "upsert_lyric_if_match",
+            span,
+            self.inner.upsert_lyric_if_match(lyric, if_match),
+        )
+        .await
+    }
+
+    async fn delete_lyric_if_match(&self, id: LyricId, if_match: String) -> Result<()> {
+        self.traced(
+            "delete_lyric_if_match",
+            span!("delete_lyric_if_match", %id),
+            self.inner.delete_lyric_if_match(id, if_match),
+        )
+        .await
+    }
+
+    async fn get_lyric_revisions(&self, id: LyricId) -> Result<Vec<RevisionInfo>> {
+        self.traced(
+            "get_lyric_revisions",
+            span!("get_lyric_revisions", %id),
+            self.inner.get_lyric_revisions(id),
+        )
+        .await
+    }
+
+    async fn get_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Revision<Lyric>> {
+        self.traced(
+            "get_lyric_revision",
+            span!("get_lyric_revision", %id, revision),
+            self.inner.get_lyric_revision(id, revision),
+        )
+        .await
+    }
+
+    async fn restore_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Lyric> {
+        self.traced(
+            "restore_lyric_revision",
+            span!("restore_lyric_revision", %id, revision),
+            self.inner.restore_lyric_revision(id, revision),
+        )
+        .await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        self.traced("search", span!("search", query), self.inner.search(query))
+            .await
+    }
+
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        self.traced(
+            "get_playlists",
+            span!("get_playlists"),
+            self.inner.get_playlists(),
+        )
+        .await
+    }
+
+    async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
+        self.traced(
+            "get_playlist_summaries",
+            span!("get_playlist_summaries"),
+            self.inner.get_playlist_summaries(),
+        )
+        .await
+    }
+
+    async fn get_playlists_page(&self, page: PageRequest) -> Result<Page<Playlist>> {
+        self.traced(
+            "get_playlists_page",
+            span!("get_playlists_page", limit = page.limit),
+            self.inner.get_playlists_page(page),
+        )
+        .await
+    }
+
+    async fn get_playlist_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.traced(
+            "get_playlist_summaries_page",
+            span!("get_playlist_summaries_page", limit = page.limit),
+            self.inner.get_playlist_summaries_page(page),
+        )
+        .await
+    }
+
+    async fn get_playlist(&self, id: PlaylistId) -> Result<Playlist> {
+        self.traced(
+            "get_playlist",
+            span!("get_playlist", %id),
+            self.inner.get_playlist(id),
+        )
+        .await
+    }
+
+    async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
+        let span = span!("upsert_playlist", id = %playlist.id, title = %playlist.title);
+        self.traced(
+            "upsert_playlist",
+            span,
+            self.inner.upsert_playlist(playlist),
+        )
+        .await
+    }
+
+    async fn delete_playlist(&self, id: PlaylistId) -> Result<()> {
+        self.traced(
+            "delete_playlist",
+            span!("delete_playlist", %id),
+            self.inner.delete_playlist(id),
+        )
+        .await
+    }
+
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        if_match: String,
+    ) -> Result<Playlist> {
+        let span = span!("upsert_playlist_if_match", id = %playlist.id, title = %playlist.title);
+        self.traced(
+            "upsert_playlist_if_match",
+            span,
+            self.inner.upsert_playlist_if_match(playlist, if_match),
+        )
+        .await
+    }
+
+    async fn delete_playlist_if_match(&self, id: PlaylistId, if_match: String) -> Result<()> {
+        self.traced(
+            "delete_playlist_if_match",
+            span!("delete_playlist_if_match", %id),
+            self.inner.delete_playlist_if_match(id, if_match),
+        )
+        .await
+    }
+
+    async fn get_playlist_revisions(&self, id: PlaylistId) -> Result<Vec<RevisionInfo>> {
+        self.traced(
+            "get_playlist_revisions",
+            span!("get_playlist_revisions", %id),
+            self.inner.get_playlist_revisions(id),
+        )
+        .await
+    }
+
+    async fn get_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> Result<Revision<Playlist>> {
+        self.traced(
+            "get_playlist_revision",
+            span!("get_playlist_revision", %id, revision),
+            self.inner.get_playlist_revision(id, revision),
+        )
+        .await
+    }
+
+    async fn restore_playlist_revision(&self, id: PlaylistId, revision: u32) -> Result<Playlist> {
+        self.traced(
+            "restore_playlist_revision",
+            span!("restore_playlist_revision", %id, revision),
+            self.inner.restore_playlist_revision(id, revision),
+        )
+        .await
+    }
+
+    async fn subscribe(&self) -> Result<ChangeStream> {
+        self.traced("subscribe", span!("subscribe"), self.inner.subscribe())
+            .await
+    }
+
+    async fn stop(&self) -> Result<()> {
+        self.traced("stop", span!("stop"), self.inner.stop()).await
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use lipl_core::{
+    Error, Lyric, LyricId, Page, PageRequest, Playlist, PlaylistId, Repo, Result, Revision,
+    RevisionInfo, Summary, change::ChangeStream, search::Hit,
+};
+
+/// Switch that freezes the library, shared by the repo and the admin endpoint
+#[derive(Clone, Debug, Default)]
+pub struct ReadOnly(Arc<AtomicBool>);
+
+impl ReadOnly {
+    pub fn new(enabled: bool) -> Self {
+        Self(Arc::new(AtomicBool::new(enabled)))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed);
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.is_enabled() {
+            Err(Error::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Repo that rejects every change with `Error::ReadOnly` while the switch is on
+#[derive(Clone)]
+pub struct ReadOnlyRepo<R> {
+    inner: R,
+    read_only: ReadOnly,
+}
+
+impl<R> ReadOnlyRepo<R> {
+    pub fn new(inner: R, read_only: ReadOnly) -> Self {
+        Self { inner, read_only }
+    }
+}
+
+impl<R: Repo + Sync> Repo for ReadOnlyRepo<R> {
+    async fn get_lyrics(&self) -> Result<Vec<Lyric>> {
+        self.inner.get_lyrics().await
+    }
+
+    async fn get_lyric_summaries(&self) -> Result<Vec<Summary>> {
+        self.inner.get_lyric_summaries().await
+    }
+
+    async fn get_lyrics_page(&self, page: PageRequest) -> Result<Page<Lyric>> {
+        self.inner.get_lyrics_page(page).await
+    }
+
+    async fn get_lyric_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.inner.get_lyric_summaries_page(page).await
+    }
+
+    async fn get_lyric(&self, id: LyricId) -> Result<Lyric> {
+        self.inner.get_lyric(id).await
+    }
+
+    async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
+        self.read_only.check()?;
+        self.inner.upsert_lyric(lyric).await
+    }
+
+    async fn delete_lyric(&self, id: LyricId) -> Result<()> {
+        self.read_only.check()?;
+        self.inner.delete_lyric(id).await
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, if_match: String) -> Result<Lyric> {
+        self.read_only.check()?;
+        self.inner.upsert_lyric_if_match(lyric, if_match).await
+    }
+
+    async fn delete_lyric_if_match(&self, id: LyricId, if_match: String) -> Result<()> {
+        self.read_only.check()?;
+        self.inner.delete_lyric_if_match(id, if_match).await
+    }
+
+    async fn get_lyric_revisions(&self, id: LyricId) -> Result<Vec<RevisionInfo>> {
+        self.inner.get_lyric_revisions(id).await
+    }
+
+    async fn get_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Revision<Lyric>> {
+        self.inner.get_lyric_revision(id, revision).await
+    }
+
+    async fn restore_lyric_revision(&self, id: LyricId, revision: u32) -> Result<Lyric> {
+        self.read_only.check()?;
+        self.inner.restore_lyric_revision(id, revision).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        self.inner.search(query).await
+    }
+
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        self.inner.get_playlists().await
+    }
+
+    async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
+        self.inner.get_playlist_summaries().await
+    }
+
+    async fn get_playlists_page(&self, page: PageRequest) -> Result<Page<Playlist>> {
+        self.inner.get_playlists_page(page).await
+    }
+
+    async fn get_playlist_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.inner.get_playlist_summaries_page(page).await
+    }
+
+    async fn get_playlist(&self, id: PlaylistId) -> Result<Playlist> {
+        self.inner.get_playlist(id).await
+    }
+
+    async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
+        self.read_only.check()?;
+        self.inner.upsert_playlist(playlist).await
+    }
+
+    async fn delete_playlist(&self, id: PlaylistId) -> Result<()> {
+        self.read_only.check()?;
+        self.inner.delete_playlist(id).await
+    }
+
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        if_match: String,
+    ) -> Result<Playlist> {
+        self.read_only.check()?;
+        self.inner
+            .upsert_playlist_if_match(playlist, if_match)
+            .await
+    }
+
+    async fn delete_playlist_if_match(&self, id: PlaylistId, if_match: String) -> Result<()> {
+        self.read_only.check()?;
+        self.inner.delete_playlist_if_match(id, if_match).await
+    }
+
+    async fn get_playlist_revisions(&self, id: PlaylistId) -> Result<Vec<RevisionInfo>> {
+        self.inner.get_playlist_revisions(id).await
+    }
+
+    async fn get_playlist_revision(
+        &self,
+        id: PlaylistId,
+        revision: u32,
+    ) -> Result<Revision<Playlist>> {
+        self.inner.get_playlist_revision(id, revision).await
+    }
+
+    async fn restore_playlist_revision(&self, id: PlaylistId, revision: u32) -> Result<Playlist> {
+        self.read_only.check()?;
+        self.inner.restore_playlist_revision(id, revision).await
+    }
+
+    async fn subscribe(&self) -> Result<ChangeStream> {
+        self.inner.subscribe().await
+    }
+
+    async fn stop(&self) -> Result<()> {
+        self.inner.stop().await
+    }
+}
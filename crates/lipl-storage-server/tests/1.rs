@@ -1,14 +1,37 @@
 use axum::{
-    Router,
+    Extension, Router,
     body::Body,
-    http::{Request, StatusCode},
+    http::{
+        Request, StatusCode,
+        header::{ACCEPT, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH, VARY, WWW_AUTHENTICATE},
+        request::Builder,
+    },
+    response::Response,
 };
 use base64::{Engine, engine::general_purpose};
+use futures_util::{Stream, StreamExt};
 use http_body_util::BodyExt;
-use lipl_core::{Lyric, LyricPost, Playlist, PlaylistPost, RepoConfig, Summary, Uuid};
+use lipl_core::{
+    HasSummary, IdGenerator, Lyric, LyricId, LyricPost, Metadata, Page, Playlist, PlaylistPost,
+    Repo, RepoConfig, RepoDb, RevisionInfo, Rules, Summary, Transposition,
+    change::Change,
+    reexport::chrono::{SecondsFormat, Utc},
+    search::Hit,
+    set_id_generator,
+    transaction::{LoggedRepo, snapshot_path},
+};
 use lipl_storage_memory::MemoryRepoConfig;
-use lipl_storage_server::create_router;
+use lipl_storage_server::{
+    METRICS_CONTENT_TYPE, OPERATOR_TOKEN, PROBLEM_JSON, Position, Problem, ReadOnly, Slide,
+    TransactionLog, Users, create_router, create_router_with_users,
+};
 use serde::{Serialize, de::DeserializeOwned};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{Message, client::IntoClientRequest},
+};
 use tower::ServiceExt;
 
 const LYRIC: &str = "lyric";
@@ -16,8 +39,17 @@ const PLAYLIST: &str = "playlist";
 const HEALTH: &str = "health";
 const PREFIX: &str = "/lipl/api/v1/";
 
+const EDITOR: (&str, &str) = ("anna", "alto");
+
+fn admin() -> (String, String) {
+    (
+        std::env::var("LIPL_USERNAME").unwrap(),
+        std::env::var("LIPL_PASSWORD").unwrap(),
+    )
+}
+
 async fn router() -> Router {
-    create_router(
+    create_router_with_users(
         MemoryRepoConfig {
             sample_data: false,
             transaction_log: None,
@@ -25,20 +57,33 @@ async fn router() -> Router {
         .to_repo()
         .await
         .unwrap(),
+        Rules::default(),
+        ReadOnly::default(),
+        Users::new(admin(), [(EDITOR.0.to_owned(), EDITOR.1.to_owned())]),
     )
 }
 
 fn basic_authentication_header() -> String {
     let username = std::env::var("LIPL_USERNAME").unwrap();
     let password = std::env::var("LIPL_PASSWORD").unwrap();
+    basic(&username, &password)
+}
+
+fn basic(username: &str, password: &str) -> String {
     let authentication = format!("{username}:{password}");
-    let encoded = general_purpose::STANDARD_NO_PAD.encode(authentication);
+    let encoded = general_purpose::STANDARD.encode(authentication);
     format!("Basic {encoded}")
 }
 
 fn daar_bij_die_molen() -> LyricPost {
     LyricPost {
         title: "Daar bij die molen".to_owned(),
+        metadata: Metadata {
+            key: Some("F".to_owned()),
+            language: Some("nl".to_owned()),
+            tags: vec!["smartlap".to_owned()],
+            ..Metadata::default()
+        },
         parts: vec![vec![
             "Daar bij die molen, die mooie molen".to_owned(),
             "Daar woont het meiseje waar ik zo veel van hou".to_owned(),
@@ -51,6 +96,7 @@ fn daar_bij_die_molen() -> LyricPost {
 fn roodkapje() -> LyricPost {
     LyricPost {
         title: "Roodkapje".to_owned(),
+        metadata: Metadata::default(),
         parts: vec![
             vec![
                 "Zeg roodkapje waar ga je hene, zo alleen, zo alleen".to_owned(),
@@ -73,6 +119,175 @@ async fn health_check() {
     assert_eq!(status, StatusCode::OK);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn unauthenticated() {
+    let service = router().await;
+
+    let response = service
+        .clone()
+        .oneshot(
+            Request::get(format!("{PREFIX}{LYRIC}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.headers()[WWW_AUTHENTICATE],
+        r#"Basic realm="lipl""#
+    );
+    assert_eq!(problem(response).await.code, "unauthorized");
+
+    let response = send_as(
+        &service,
+        (EDITOR.0, "bass"),
+        Request::get(format!("{PREFIX}{LYRIC}")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_only() {
+    let service = router().await;
+
+    let response = service
+        .clone()
+        .oneshot(
+            Request::put(format!("{PREFIX}admin/read-only"))
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"read_only":true}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    for (request, body) in [
+        (
+            Request::put(format!("{PREFIX}admin/read-only"))
+                .header(CONTENT_TYPE, "application/json"),
+            Body::from(r#"{"read_only":true}"#),
+        ),
+        (
+            Request::post(format!("{PREFIX}admin/compact")),
+            Body::empty(),
+        ),
+        (
+            Request::get(format!("{PREFIX}admin/history?at=2024-01-01T00:00:00Z")),
+            Body::empty(),
+        ),
+    ] {
+        let response = send_as(&service, EDITOR, request, body).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(problem(response).await.code, "forbidden");
+    }
+
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}admin/read-only")),
+        Body::empty(),
+    )
+    .await;
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&b[..], br#"{"read_only":false}"#);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn open_api() {
+    let repo = MemoryRepoConfig::default().to_repo().await.unwrap();
+    let service = create_router_with_users(
+        repo.clone(),
+        Rules::default(),
+        ReadOnly::default(),
+        Users::admin(admin()),
+    );
+
+    let body = serde_json::to_string(&roodkapje()).unwrap();
+    let response = service
+        .clone()
+        .oneshot(
+            Request::post(format!("{PREFIX}{LYRIC}"))
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    let lyric: Lyric = serde_json::from_slice(&b).unwrap();
+    let response = send_as(
+        &service,
+        EDITOR,
+        Request::delete(format!("{PREFIX}{LYRIC}/{}", lyric.id)),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let revisions = repo.get_lyric_revisions(lyric.id).await.unwrap();
+    assert!(revisions.iter().all(|revision| revision.author.is_none()));
+
+    let response = send_as(
+        &service,
+        EDITOR,
+        Request::get(format!("{PREFIX}admin/read-only")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}admin/read-only")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let service = create_router(repo);
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}admin/read-only")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn metrics() {
+    let service = router().await;
+
+    let _: Vec<Summary> = list(&service, LYRIC).await;
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}{LYRIC}/{}", LyricId::default())),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = service
+        .clone()
+        .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], METRICS_CONTENT_TYPE);
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(b.to_vec()).unwrap();
+    assert!(
+        text.contains("lipl_repo_duration_seconds_count{operation=\"get_lyric_summaries\"} 1\n")
+    );
+    assert!(
+        text.contains("lipl_repo_duration_seconds_bucket{operation=\"get_lyric\",le=\"+Inf\"} 1\n")
+    );
+    assert!(text.contains("lipl_repo_errors_total{operation=\"get_lyric\"} 1\n"));
+    assert!(text.contains("lipl_repo_errors_total{operation=\"get_lyric_summaries\"} 0\n"));
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn lyric_list() {
     let service = router().await;
@@ -85,12 +300,55 @@ async fn lyric_list() {
     assert_eq!(lyrics[1].title, "Roodkapje".to_owned());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn lyric_list_page() {
+    let service = router().await;
+
+    let _daar_bij_die_molen: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+    let _roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+
+    let first: Page<Summary> = page(&service, LYRIC, "limit=1").await;
+    assert_eq!(first.items.len(), 1);
+    assert_eq!(first.items[0].title, "Daar bij die molen".to_owned());
+
+    let cursor = first.next.unwrap();
+    let second: Page<Lyric> = page(
+        &service,
+        LYRIC,
+        &format!("limit=1&full=true&cursor={cursor}"),
+    )
+    .await;
+    assert_eq!(second.items.len(), 1);
+    assert_eq!(second.items[0].title, "Roodkapje".to_owned());
+    assert!(second.next.is_none());
+
+    let all: Page<Summary> = page(&service, LYRIC, &format!("limit={}", usize::MAX)).await;
+    assert_eq!(all.items.len(), 2);
+    assert!(all.next.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lyric_search() {
+    let service = router().await;
+
+    let daar_bij_die_molen: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+    let _roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+
+    let hits: Vec<Hit> = page(&service, LYRIC, "q=Molen").await;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].id, daar_bij_die_molen.id);
+
+    let hits: Vec<Hit> = page(&service, LYRIC, "q=sinterklaas").await;
+    assert!(hits.is_empty());
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn lyric_post() {
     let service = router().await;
 
     let lyric_post = LyricPost {
         title: "Er is er één jarig".to_owned(),
+        metadata: Metadata::default(),
         parts: vec![],
     };
 
@@ -99,6 +357,22 @@ async fn lyric_post() {
     assert_eq!(lyric.parts, lyric_post.parts);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn lyric_post_time_ordered() {
+    let service = router().await;
+
+    let playlist_post = PlaylistPost {
+        title: "Alle 13 goed".to_owned(),
+        ..PlaylistPost::default()
+    };
+    set_id_generator(IdGenerator::V7);
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let playlist: Playlist = post(&service, PLAYLIST, &playlist_post).await;
+    set_id_generator(IdGenerator::V4);
+
+    assert!(playlist.id.created().unwrap() >= lyric.id.created().unwrap());
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn lyric_post_change() {
     let service = router().await;
@@ -109,11 +383,15 @@ async fn lyric_post_change() {
     let id = lyric.id.to_string();
     assert_eq!(lyric.title, lyric_post.title);
     assert_eq!(lyric.parts, lyric_post.parts);
+    assert_eq!(lyric.metadata, lyric_post.metadata);
 
     lyric_post.title = "Daar bij dat molengedrag".to_owned();
+    lyric_post.metadata.tempo = Some(88);
+    lyric_post.metadata.tags.clear();
     let lyric_changed: Lyric = put(&service, LYRIC, &id, &lyric_post).await;
 
     assert_eq!(lyric_changed.title, lyric_post.title);
+    assert_eq!(lyric_changed.metadata, lyric_post.metadata);
 }
 
 #[tokio::test(flavor = "multi_thread")]
@@ -135,6 +413,327 @@ async fn lyric_delete() {
     assert_eq!(list_after_delete.len(), 0);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn lyric_etag() {
+    let service = router().await;
+
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let uri = format!("{PREFIX}{LYRIC}/{}", lyric.id);
+
+    let response = send(&service, Request::get(&uri), Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[ETAG].to_str().unwrap().to_owned();
+
+    let response = send(
+        &service,
+        Request::get(&uri).header(IF_NONE_MATCH, &etag),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let body = serde_json::to_string(&daar_bij_die_molen()).unwrap();
+    let response = send(
+        &service,
+        Request::put(&uri)
+            .header(IF_MATCH, &etag)
+            .header(CONTENT_TYPE, "application/json"),
+        Body::from(body.clone()),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()[ETAG], etag);
+
+    let response = send(
+        &service,
+        Request::put(&uri)
+            .header(IF_MATCH, &etag)
+            .header(CONTENT_TYPE, "application/json"),
+        Body::from(body),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = send(
+        &service,
+        Request::delete(&uri).header(IF_MATCH, &etag),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let changed: Lyric = item(&service, LYRIC, &lyric.id.to_string()).await;
+    assert_eq!(changed.title, "Daar bij die molen".to_owned());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lyric_chordpro() {
+    let service = router().await;
+
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let uri = format!("{PREFIX}{LYRIC}/{}", lyric.id);
+    let chordpro = "{title: Roodkapje}\n{key: C}\n\n[C]Zeg roodkapje waar ga je [G]hene\n\n{soc}\nZo alleen\n{eoc}\n";
+
+    let response = send(
+        &service,
+        Request::put(&uri).header(CONTENT_TYPE, "text/x-chordpro"),
+        Body::from(chordpro),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[ETAG].to_str().unwrap().to_owned();
+
+    let changed: Lyric = item(&service, LYRIC, &lyric.id.to_string()).await;
+    assert_eq!(changed.metadata.key.as_deref(), Some("C"));
+    assert_eq!(
+        changed.parts,
+        vec![
+            vec!["[C]Zeg roodkapje waar ga je [G]hene"],
+            vec!["# chorus", "Zo alleen"],
+        ]
+    );
+
+    let response = send(
+        &service,
+        Request::get(&uri).header(ACCEPT, "text/x-chordpro"),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let chordpro_etag = response.headers()[ETAG].to_str().unwrap().to_owned();
+    assert_eq!(
+        chordpro_etag,
+        format!("{}-chordpro\"", &etag[..etag.len() - 1])
+    );
+    assert_eq!(response.headers()[VARY], "accept");
+    assert!(
+        response.headers()[CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/x-chordpro")
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.starts_with("{title: Roodkapje}\n{key: C}\n\n[C]Zeg roodkapje"));
+    assert!(text.contains("{start_of_chorus: chorus}\nZo alleen\n{end_of_chorus}"));
+
+    let response = send(
+        &service,
+        Request::get(&uri)
+            .header(ACCEPT, "text/x-chordpro")
+            .header(IF_NONE_MATCH, &chordpro_etag),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    let response = send(
+        &service,
+        Request::get(&uri).header(IF_NONE_MATCH, &chordpro_etag),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[ETAG], etag);
+
+    let response = send(
+        &service,
+        Request::put(&uri)
+            .header(CONTENT_TYPE, "text/x-chordpro")
+            .header(IF_MATCH, &chordpro_etag),
+        Body::from(chordpro),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(
+        &service,
+        Request::put(&uri).header(CONTENT_TYPE, "text/x-chordpro"),
+        Body::from("Zonder titel"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lyric_openlyrics() {
+    let service = router().await;
+
+    let document = |title: &str| {
+        format!(
+            "<song xmlns=\"http://openlyrics.info/namespace/2009/song\" version=\"0.9\"><properties><titles><title>{title}</title></titles><authors><author type=\"words\">Onbekend</author></authors><verseOrder>v1 c v1</verseOrder></properties><lyrics><verse name=\"v1\"><lines><chord name=\"G\"/>Klokjes<br/>klinken</lines></verse><verse name=\"c\"><lines>Kling klang</lines></verse></lyrics></song>"
+        )
+    };
+    let body = serde_json::to_string(&[document("Klokjes"), document("Bellen")]).unwrap();
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}{LYRIC}/import")).header(CONTENT_TYPE, "application/json"),
+        Body::from(body),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let lyrics: Vec<Lyric> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(lyrics.len(), 2);
+    assert_eq!(lyrics[1].title, "Bellen");
+    assert_eq!(lyrics[0].metadata.lyricist.as_deref(), Some("Onbekend"));
+    assert_eq!(
+        lyrics[0].parts,
+        vec![
+            vec!["# verse 1", "[G]Klokjes", "klinken"],
+            vec!["# chorus", "Kling klang"],
+            vec!["# verse 1"],
+        ]
+    );
+
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}{LYRIC}/{}", lyrics[0].id))
+            .header(ACCEPT, "application/vnd.openlyrics+xml"),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response.headers()[CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("application/vnd.openlyrics+xml")
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("<title>Klokjes</title>"));
+    assert!(text.contains("<verseOrder>v1 c1 v1</verseOrder>"));
+
+    let body = serde_json::to_string(&[document("Klokjes"), document("Torens")]).unwrap();
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}{LYRIC}/import")).header(CONTENT_TYPE, "application/json"),
+        Body::from(body),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let imported: Vec<Lyric> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(imported[0].id, lyrics[0].id);
+    assert_eq!(imported[1].title, "Torens");
+    let summaries: Vec<Summary> = list(&service, LYRIC).await;
+    assert_eq!(summaries.len(), 3);
+
+    let body = serde_json::to_string(&[document("Kerken"), "<song>".to_owned()]).unwrap();
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}{LYRIC}/import")).header(CONTENT_TYPE, "application/json"),
+        Body::from(body),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let summaries: Vec<Summary> = list(&service, LYRIC).await;
+    assert_eq!(summaries.len(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lyric_transpose() {
+    let service = router().await;
+
+    let lyric_post = LyricPost {
+        metadata: Metadata {
+            key: Some("G".to_owned()),
+            ..Metadata::default()
+        },
+        parts: vec![vec!["[G]Zeg roodkapje waar ga je [D/F#]hene".to_owned()]],
+        ..roodkapje()
+    };
+    let lyric: Lyric = post(&service, LYRIC, &lyric_post).await;
+    let id = lyric.id.to_string();
+
+    let transposed: Lyric = item(&service, LYRIC, &format!("{id}?transpose=%2B2")).await;
+    assert_eq!(transposed.metadata.key.as_deref(), Some("A"));
+    assert_eq!(
+        transposed.parts,
+        vec![vec!["[A]Zeg roodkapje waar ga je [E/G#]hene"]]
+    );
+
+    let transposed: Lyric = item(&service, LYRIC, &format!("{id}?transpose=-2+flat+capo+2")).await;
+    assert_eq!(transposed.metadata.key.as_deref(), Some("F"));
+    assert_eq!(
+        transposed.parts,
+        vec![vec!["[Eb]Zeg roodkapje waar ga je [Bb/D]hene"]]
+    );
+
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}{LYRIC}/{id}?transpose=hoger")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lyric_revisions() {
+    let service = router().await;
+
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let uri = format!("{PREFIX}{LYRIC}/{}", lyric.id);
+
+    let body = serde_json::to_string(&daar_bij_die_molen()).unwrap();
+    let response = send_as(
+        &service,
+        EDITOR,
+        Request::put(&uri).header(CONTENT_TYPE, "application/json"),
+        Body::from(body),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send_as(&service, EDITOR, Request::delete(&uri), Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(
+        &service,
+        Request::get(format!("{uri}/revisions")),
+        Body::empty(),
+    )
+    .await;
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    let revisions: Vec<RevisionInfo> = serde_json::from_slice(&b).unwrap();
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(
+        revisions[0].author,
+        Some(std::env::var("LIPL_USERNAME").unwrap())
+    );
+    assert_eq!(revisions[1].author, Some(EDITOR.0.to_owned()));
+
+    let response = send(
+        &service,
+        Request::post(format!("{uri}/revisions/1/restore")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let restored: Lyric = item(&service, LYRIC, &lyric.id.to_string()).await;
+    assert_eq!(restored.title, "Roodkapje".to_owned());
+
+    let response = send(
+        &service,
+        Request::get(format!("{uri}/revisions/4")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    for request in [
+        Request::get(format!("{PREFIX}{LYRIC}/not-an-id/revisions")),
+        Request::get(format!("{uri}/revisions/first")),
+        Request::post(format!("{PREFIX}{PLAYLIST}/not-an-id/revisions/1/restore")),
+    ] {
+        let response = send(&service, request, Body::empty()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(problem(response).await.code, "not-found");
+    }
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn playlist_list() {
     let service = router().await;
@@ -142,6 +741,7 @@ async fn playlist_list() {
     let playlist_post = PlaylistPost {
         title: "Alle 13 goed".to_owned(),
         members: vec![],
+        ..PlaylistPost::default()
     };
 
     let _playlist: Playlist = post(&service, PLAYLIST, &playlist_post).await;
@@ -157,12 +757,13 @@ async fn playlist_post() {
     let playlist_post = PlaylistPost {
         title: "Alle 13 goed".to_owned(),
         members: vec![],
+        ..PlaylistPost::default()
     };
 
     let playlist: Playlist = post(&service, PLAYLIST, &playlist_post).await;
     assert_eq!(playlist.title, "Alle 13 goed".to_owned());
 
-    let members: Vec<Uuid> = vec![];
+    let members: Vec<LyricId> = vec![];
     assert_eq!(playlist.members, members);
 }
 
@@ -176,15 +777,550 @@ async fn playlist_post_lyric_delete() {
     let playlist_post = PlaylistPost {
         title: "Alle 13 goed".to_owned(),
         members: vec![roodkapje.id, daar_bij_die_molen.id],
+        transpositions: [(roodkapje.id, Transposition::new(-2))]
+            .into_iter()
+            .collect(),
     };
 
     let playlist: Playlist = post(&service, PLAYLIST, &playlist_post).await;
     assert_eq!(playlist.title, "Alle 13 goed".to_owned());
     assert_eq!(playlist.members, vec![roodkapje.id, daar_bij_die_molen.id]);
+    assert_eq!(playlist.transpositions, playlist_post.transpositions);
 
     delete(&service, LYRIC, &roodkapje.id.to_string()).await;
     let playlist: Playlist = item(&service, PLAYLIST, &playlist.id.to_string()).await;
     assert_eq!(playlist.members, vec![daar_bij_die_molen.id]);
+    assert!(playlist.transpositions.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hyphenated_ids() {
+    let service = router().await;
+
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let hyphenated = lyric.id.inner().hyphenated().to_string();
+    let found: Lyric = item(&service, LYRIC, &hyphenated).await;
+    assert_eq!(found, lyric);
+
+    let playlist_post = PlaylistPost {
+        title: "Alle 13 goed".to_owned(),
+        members: vec![hyphenated.parse().unwrap()],
+        ..PlaylistPost::default()
+    };
+    let playlist: Playlist = post(&service, PLAYLIST, &playlist_post).await;
+    assert_eq!(playlist.members, vec![lyric.id]);
+
+    let uri = format!("{PREFIX}{PLAYLIST}/{}", playlist.id);
+    let etag = send(&service, Request::get(&uri), Body::empty())
+        .await
+        .headers()[ETAG]
+        .clone();
+    let response = send(
+        &service,
+        Request::get(format!("{uri}?id_format=uuid")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[ETAG], etag);
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&b).unwrap();
+    assert_eq!(json["id"], playlist.id.inner().hyphenated().to_string());
+    assert_eq!(json["members"][0], hyphenated);
+    let found: Playlist = serde_json::from_value(json).unwrap();
+    assert_eq!(found, playlist);
+
+    let response = send(
+        &service,
+        Request::get(format!("{uri}?id_format=hex")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(problem(response).await.code, "parse");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn problem_details() {
+    let service = router().await;
+
+    let id = LyricId::default();
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}{LYRIC}/{id}")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Problem = problem(response).await;
+    assert_eq!(body.status, 404);
+    assert_eq!(body.code, "not-found");
+
+    let playlist_post = PlaylistPost {
+        title: "Alle 13 goed".to_owned(),
+        members: vec![id],
+        ..PlaylistPost::default()
+    };
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}{PLAYLIST}")).header(CONTENT_TYPE, "application/json"),
+        Body::from(serde_json::to_string(&playlist_post).unwrap()),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(problem(response).await.code, "invalid-member");
+
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let response = send(
+        &service,
+        Request::delete(format!("{PREFIX}{LYRIC}/{}", lyric.id)).header(IF_MATCH, "\"stale\""),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(problem(response).await.code, "precondition-failed");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn validation() {
+    let service = router().await;
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+
+    let invalid = LyricPost {
+        title: " ".to_owned(),
+        parts: vec![vec!["Zeg roodkapje\u{0}".to_owned()]],
+        ..roodkapje()
+    };
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}{LYRIC}")).header(CONTENT_TYPE, "application/json"),
+        Body::from(serde_json::to_string(&invalid).unwrap()),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = problem(response).await;
+    assert_eq!(body.code, "validation");
+    let fields = body
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        vec![("title", "required"), ("parts[0][0]", "control-character")]
+    );
+
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}{LYRIC}")).header(CONTENT_TYPE, "application/json"),
+        Body::from(serde_json::to_string(&roodkapje()).unwrap()),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(problem(response).await.errors[0].code, "not-unique");
+
+    let changed: Lyric = put(&service, LYRIC, &lyric.id.to_string(), &roodkapje()).await;
+    assert_eq!(changed.title, lyric.title);
+
+    let playlist_post = PlaylistPost {
+        title: "Alle 13 goed".to_owned(),
+        members: vec![lyric.id, lyric.id],
+        ..PlaylistPost::default()
+    };
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}{PLAYLIST}")).header(CONTENT_TYPE, "application/json"),
+        Body::from(serde_json::to_string(&playlist_post).unwrap()),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = problem(response).await;
+    assert_eq!(body.errors[0].field, "members[1]");
+    assert_eq!(body.errors[0].code, "duplicate");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_titles() {
+    let service = router().await;
+    let post = || {
+        send(
+            &service,
+            Request::post(format!("{PREFIX}{LYRIC}")).header(CONTENT_TYPE, "application/json"),
+            Body::from(serde_json::to_string(&roodkapje()).unwrap()),
+        )
+    };
+
+    let (first, second) = tokio::join!(post(), post());
+
+    let mut statuses = [first.status(), second.status()];
+    statuses.sort();
+    assert_eq!(
+        statuses,
+        [StatusCode::CREATED, StatusCode::UNPROCESSABLE_ENTITY]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn read_only() {
+    let service = router().await;
+    let lyric: Lyric = post(&service, LYRIC, &roodkapje()).await;
+
+    let response = send(
+        &service,
+        Request::put(format!("{PREFIX}admin/read-only")).header(CONTENT_TYPE, "application/json"),
+        Body::from(r#"{"read_only":true}"#),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}{LYRIC}")).header(CONTENT_TYPE, "application/json"),
+        Body::from(serde_json::to_string(&daar_bij_die_molen()).unwrap()),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(problem(response).await.code, "read-only");
+
+    let response = send(
+        &service,
+        Request::delete(format!("{PREFIX}{LYRIC}/{}", lyric.id)),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let unchanged: Lyric = item(&service, LYRIC, &lyric.id.to_string()).await;
+    assert_eq!(unchanged, lyric);
+
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}admin/read-only")),
+        Body::empty(),
+    )
+    .await;
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&b[..], br#"{"read_only":true}"#);
+
+    let response = send(
+        &service,
+        Request::put(format!("{PREFIX}admin/read-only")).header(CONTENT_TYPE, "application/json"),
+        Body::from(r#"{"read_only":false}"#),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let _: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compact() {
+    let service = router().await;
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}admin/compact")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let log = std::env::temp_dir().join(format!("lipl-{}.log", LyricId::default()));
+    let repo =
+        LoggedRepo::open(MemoryRepoConfig::default().to_repo().await.unwrap(), &log).unwrap();
+    let service = create_router_with_users(
+        repo.clone(),
+        Rules::default(),
+        ReadOnly::default(),
+        Users::admin(admin()),
+    )
+    .layer(Extension(TransactionLog::new(repo)));
+    let _: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    assert!(!std::fs::read_to_string(&log).unwrap().is_empty());
+
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}admin/compact")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(std::fs::read_to_string(&log).unwrap().is_empty());
+    std::fs::remove_file(snapshot_path(&log)).unwrap();
+    std::fs::remove_file(log).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn history() {
+    let log = std::env::temp_dir().join(format!("lipl-{}.log", LyricId::default()));
+    let repo =
+        LoggedRepo::open(MemoryRepoConfig::default().to_repo().await.unwrap(), &log).unwrap();
+    let service = create_router_with_users(
+        repo.clone(),
+        Rules::default(),
+        ReadOnly::default(),
+        Users::admin(admin()),
+    )
+    .layer(Extension(TransactionLog::new(repo)));
+    let roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    std::thread::sleep(Duration::from_millis(10));
+    let at = Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true);
+    std::thread::sleep(Duration::from_millis(10));
+    let _: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+
+    let db: RepoDb = page(&service, "admin/history", &format!("at={at}")).await;
+    assert_eq!(db.lyrics, vec![roodkapje]);
+
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}admin/history?at=sunday")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    std::fs::remove_file(log).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn events() {
+    let service = router().await;
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}events")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+    let mut body = response.into_body();
+
+    let roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let event = next_event(&mut body).await;
+    assert_eq!(event["event"], "change");
+    assert_eq!(
+        serde_json::from_str::<Change>(&event["data"]).unwrap(),
+        Change::lyric_upserted(&roodkapje)
+    );
+
+    let molen: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}events?id_format=uuid"))
+            .header("Last-Event-ID", &event["id"]),
+        Body::empty(),
+    )
+    .await;
+    let resumed = next_event(&mut response.into_body()).await;
+    assert_eq!(
+        serde_json::from_str::<Change>(&resumed["data"]).unwrap(),
+        Change::lyric_upserted(&molen)
+    );
+    assert!(resumed["data"].contains(&molen.id.inner().hyphenated().to_string()));
+
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}events")).header("Last-Event-ID", "1-1"),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(
+        next_event(&mut response.into_body()).await["event"],
+        "reset"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn presentation() {
+    let service = router().await;
+    let roodkapje: Lyric = post(&service, LYRIC, &roodkapje()).await;
+    let molen: Lyric = post(&service, LYRIC, &daar_bij_die_molen()).await;
+    let playlist: Playlist = post(
+        &service,
+        PLAYLIST,
+        &PlaylistPost {
+            title: "Meezingen".to_owned(),
+            members: vec![roodkapje.id, molen.id],
+            ..PlaylistPost::default()
+        },
+    )
+    .await;
+    let response = send(
+        &service,
+        Request::post(format!("{PREFIX}presentation")).header(CONTENT_TYPE, "application/json"),
+        Body::from(serde_json::json!({ "playlist": playlist.id }).to_string()),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let token = response.headers()[OPERATOR_TOKEN]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    let opened: Slide = serde_json::from_slice(&b).unwrap();
+    assert_eq!(opened.position, Position { lyric: 0, part: 0 });
+    assert_eq!(opened.part, roodkapje.parts[0]);
+    assert_eq!(opened.next.as_ref(), Some(&roodkapje.parts[1]));
+    let session = format!("presentation/{}", opened.session);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(axum::serve(listener, service.clone()).into_future());
+    let follow = |query: &str| {
+        let mut request = format!("ws://{address}{PREFIX}{session}/follow{query}")
+            .into_client_request()
+            .unwrap();
+        request.headers_mut().insert(
+            "Authorization",
+            basic_authentication_header().parse().unwrap(),
+        );
+        async { connect_async(request).await.unwrap().0 }
+    };
+
+    let mut follower = follow("").await;
+    assert_eq!(next_slide(&mut follower).await, opened);
+    for request in [
+        Request::post(format!("{PREFIX}{session}/next")),
+        Request::post(format!("{PREFIX}{session}/next")).header(OPERATOR_TOKEN, "wrong"),
+        Request::delete(format!("{PREFIX}{session}")),
+    ] {
+        let response = send(&service, request, Body::empty()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(problem(response).await.code, "forbidden");
+    }
+    let slide: Slide = post_ok(&service, &format!("{session}/next"), &token).await;
+    assert_eq!(next_slide(&mut follower).await, slide);
+    assert_eq!(slide.position, Position { lyric: 0, part: 1 });
+    assert_eq!(slide.next.as_ref(), Some(&molen.parts[0]));
+    drop(follower);
+
+    let last: Slide = post_ok(&service, &format!("{session}/next"), &token).await;
+    assert_eq!(last.position, Position { lyric: 1, part: 0 });
+    assert_eq!(last.lyric, Some(molen.summary()));
+    assert_eq!(last.next, None);
+    let mut follower = follow("?id_format=uuid").await;
+    let Some(Ok(Message::Text(text))) = follower.next().await else {
+        panic!("Expected a slide");
+    };
+    assert!(text.contains(&last.session.inner().hyphenated().to_string()));
+    assert_eq!(serde_json::from_str::<Slide>(&text).unwrap(), last);
+    let end: Slide = post_ok(&service, &format!("{session}/next"), &token).await;
+    assert_eq!(end, last);
+
+    let response = send(
+        &service,
+        Request::put(format!("{PREFIX}{session}"))
+            .header(CONTENT_TYPE, "application/json")
+            .header(OPERATOR_TOKEN, &token),
+        Body::from(r#"{"lyric":2,"part":0}"#),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(problem(response).await.code, "invalid-argument");
+
+    let response = send(
+        &service,
+        Request::delete(format!("{PREFIX}{session}")).header(OPERATOR_TOKEN, &token),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(matches!(
+        follower.next().await,
+        Some(Ok(Message::Close(_))) | None
+    ));
+    let response = send(
+        &service,
+        Request::get(format!("{PREFIX}{session}")),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    for request in [
+        Request::get(format!("{PREFIX}presentation/malformed")),
+        Request::post(format!("{PREFIX}presentation/malformed/next")),
+        Request::get(format!("{PREFIX}presentation/malformed/follow")),
+    ] {
+        let response = send(&service, request, Body::empty()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(problem(response).await.code, "not-found");
+    }
+}
+
+/// The next slide sent to a follower of a presentation
+async fn next_slide<S>(follower: &mut S) -> Slide
+where
+    S: Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    match follower.next().await {
+        Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+        message => panic!("Expected a slide, got {message:?}"),
+    }
+}
+
+/// The json body of a successful post without a body by the operator of a presentation
+async fn post_ok<R: DeserializeOwned>(service: &Router, name: &str, token: &str) -> R {
+    let response = send(
+        service,
+        Request::post(format!("{PREFIX}{name}")).header(OPERATOR_TOKEN, token),
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&b).unwrap()
+}
+
+/// The fields of the next server-sent event on `body`
+async fn next_event(body: &mut Body) -> HashMap<String, String> {
+    let mut text = String::new();
+    while !text.contains("\n\n") {
+        let frame = body.frame().await.unwrap().unwrap();
+        if let Some(data) = frame.data_ref() {
+            text.push_str(std::str::from_utf8(data).unwrap());
+        }
+    }
+    text.lines()
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(field, value)| (field.to_owned(), value.trim_start().to_owned()))
+        .collect()
+}
+
+/// Problem details in the body of an error response
+async fn problem(response: Response) -> Problem {
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), PROBLEM_JSON);
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&b).unwrap()
+}
+
+async fn send_as(
+    service: &Router,
+    (username, password): (&str, &str),
+    request: Builder,
+    body: Body,
+) -> Response {
+    service
+        .clone()
+        .oneshot(
+            request
+                .header("Authorization", basic(username, password))
+                .body(body)
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+async fn send(service: &Router, request: Builder, body: Body) -> Response {
+    service
+        .clone()
+        .oneshot(
+            request
+                .header("Authorization", basic_authentication_header())
+                .body(body)
+                .unwrap(),
+        )
+        .await
+        .unwrap()
 }
 
 async fn health(service: &Router<()>, name: &'static str) -> StatusCode {
@@ -218,6 +1354,25 @@ async fn list<R: DeserializeOwned>(service: &Router<()>, name: &'static str) ->
     r
 }
 
+async fn page<R: DeserializeOwned>(service: &Router<()>, name: &'static str, query: &str) -> R {
+    let response = service
+        .clone()
+        .oneshot(
+            Request::get(format!("{PREFIX}{name}?{query}"))
+                .header("Authorization", basic_authentication_header())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let b = response.into_body().collect().await.unwrap().to_bytes();
+    let r: R = serde_json::from_slice(&b).unwrap();
+    r
+}
+
 async fn item<R: DeserializeOwned>(service: &Router<()>, name: &'static str, uuid: &str) -> R {
     let response = service
         .clone()
@@ -250,7 +1405,7 @@ async fn delete(service: &Router, name: &'static str, id: &str) {
     assert_eq!(response.status(), StatusCode::OK);
 }
 
-async fn post<'a, T: Serialize, R: DeserializeOwned>(service: &'a Router, name: &str, t: &T) -> R {
+async fn post<T: Serialize, R: DeserializeOwned>(service: &Router, name: &str, t: &T) -> R {
     let body = serde_json::to_string(t).unwrap();
     let response = service
         .clone()
@@ -271,8 +1426,8 @@ async fn post<'a, T: Serialize, R: DeserializeOwned>(service: &'a Router, name:
     r
 }
 
-async fn put<'a, T: Serialize, R: DeserializeOwned>(
-    service: &'a Router,
+async fn put<T: Serialize, R: DeserializeOwned>(
+    service: &Router,
     name: &str,
     id: &str,
     t: &T,
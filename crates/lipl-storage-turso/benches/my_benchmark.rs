@@ -42,7 +42,7 @@ fn memory_db() {
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
-    c.bench_function("memory_db", |b| b.iter(|| memory_db()));
+    c.bench_function("memory_db", |b| b.iter(memory_db));
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,4 +1,4 @@
-use lipl_core::{Playlist, Repo, RepoConfig, Uuid};
+use lipl_core::{Playlist, PlaylistId, Repo, RepoConfig, Transpositions};
 use lipl_storage_turso::{TursoConfig, TursoDatabase};
 
 pub const TEST_DATABASE_NAME: &str = "lipl.sqlite";
@@ -38,11 +38,12 @@ async fn main() {
     dbg!(playlists.first());
     assert!(!playlists.is_empty());
 
-    let id = Uuid::default();
+    let id = PlaylistId::default();
     let playlist = Playlist {
         id,
         title: "New Playlist".to_string(),
         members: vec![],
+        transpositions: Transpositions::default(),
     };
     turso_repo.upsert_playlist(playlist).await.unwrap();
 
@@ -1,4 +1,8 @@
-use lipl_core::{Lyric, Playlist, Result, Summary, Uuid, parts::to_parts};
+use lipl_core::{
+    Error, Lyric, LyricId, Metadata, Playlist, Result, Revision, RevisionInfo, Summary,
+    Transpositions, Uuid, metadata::to_tags, parts::to_parts, search::document_text,
+    transpose::to_transpositions,
+};
 use tokio_stream::wrappers::ReceiverStream;
 use turso::{Row, Rows};
 
@@ -10,9 +14,11 @@ fn to_uuid(s: impl AsRef<str>) -> Result<Uuid> {
 
 trait RowExt {
     fn get_uuid(&self, index: usize) -> Result<Uuid>;
-    fn get_uuids(&self, index: usize) -> Result<Vec<Uuid>>;
+    fn get_uuids<I: From<Uuid>>(&self, index: usize) -> Result<Vec<I>>;
     fn get_string(&self, index: usize) -> Result<String>;
     fn get_parts(&self, index: usize) -> Result<Vec<Vec<String>>>;
+    fn get_opt_string(&self, index: usize) -> Result<Option<String>>;
+    fn get_opt_number<T: TryFrom<i64>>(&self, index: usize) -> Result<Option<T>>;
 }
 
 impl RowExt for Row {
@@ -20,9 +26,14 @@ impl RowExt for Row {
         self.get_string(index).and_then(to_uuid)
     }
 
-    fn get_uuids(&self, index: usize) -> Result<Vec<Uuid>> {
-        self.get_string(index)
-            .and_then(|s| s.split(',').map(to_uuid).collect())
+    /// Comma separated ids, `NULL` for a playlist without members
+    fn get_uuids<I: From<Uuid>>(&self, index: usize) -> Result<Vec<I>> {
+        self.get_opt_string(index)?
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(|s| to_uuid(s).map(I::from))
+            .collect()
     }
 
     fn get_string(&self, index: usize) -> Result<String> {
@@ -32,6 +43,39 @@ impl RowExt for Row {
     fn get_parts(&self, index: usize) -> Result<Vec<Vec<String>>> {
         self.get_string(index).map(to_parts)
     }
+
+    fn get_opt_string(&self, index: usize) -> Result<Option<String>> {
+        self.get::<Option<String>>(index).err_into()
+    }
+
+    fn get_opt_number<T: TryFrom<i64>>(&self, index: usize) -> Result<Option<T>> {
+        self.get::<Option<i64>>(index)
+            .err_into()?
+            .map(|number| T::try_from(number).map_err(|_| Error::Parse(format!("{number}"))))
+            .transpose()
+    }
+}
+
+/// The nine metadata columns, in the order of `db::metadata_values`, start at index 3
+const METADATA: usize = 3;
+/// First column after the lyric columns
+const AFTER_LYRIC: usize = METADATA + 9;
+
+fn metadata(row: &Row) -> Result<Metadata> {
+    Ok(Metadata {
+        subtitle: row.get_opt_string(METADATA)?,
+        lyricist: row.get_opt_string(METADATA + 1)?,
+        composer: row.get_opt_string(METADATA + 2)?,
+        key: row.get_opt_string(METADATA + 3)?,
+        tempo: row.get_opt_number(METADATA + 4)?,
+        copyright: row.get_opt_string(METADATA + 5)?,
+        ccli: row.get_opt_number(METADATA + 6)?,
+        language: row.get_opt_string(METADATA + 7)?,
+        tags: row
+            .get_opt_string(METADATA + 8)?
+            .map(to_tags)
+            .unwrap_or_default(),
+    })
 }
 
 pub fn to_list<T: Send + Sync + 'static>(
@@ -52,16 +96,90 @@ where
 }
 
 pub fn to_lyric(row: Row) -> Result<Lyric> {
-    row.get_uuid(0)
-        .and_then(|id| row.get_string(1).map(|title| (id, title)))
-        .and_then(|(id, title)| row.get_parts(2).map(|parts| Lyric { id, title, parts }))
+    lyric(&row)
+}
+
+fn lyric(row: &Row) -> Result<Lyric> {
+    Ok(Lyric {
+        id: row.get_uuid(0)?.into(),
+        title: row.get_string(1)?,
+        metadata: metadata(row)?,
+        parts: row.get_parts(2)?,
+    })
+}
+
+/// Id and search text of a lyric row with the id, title and parts
+pub fn to_search_text(row: Row) -> Result<(Uuid, String)> {
+    Ok((
+        row.get_uuid(0)?,
+        document_text(&row.get_string(1)?, &row.get_parts(2)?),
+    ))
+}
+
+pub fn to_scored_lyric(row: Row) -> Result<(Lyric, f64)> {
+    let score = row.get::<f64>(AFTER_LYRIC).err_into()?;
+    lyric(&row).map(|lyric| (lyric, score))
+}
+
+fn transpositions(row: &Row, members: &[LyricId]) -> Result<Transpositions> {
+    row.get_opt_string(3)?.map_or_else(
+        || Ok(Transpositions::default()),
+        |text| to_transpositions(text, members),
+    )
 }
 
 pub fn to_playlist(row: Row) -> Result<Playlist> {
+    let members = row.get_uuids(2)?;
     Ok(Playlist {
-        id: row.get_uuid(0)?,
+        id: row.get_uuid(0)?.into(),
         title: row.get_string(1)?,
-        members: row.get_uuids(2)?,
+        transpositions: transpositions(&row, &members)?,
+        members,
+    })
+}
+
+pub fn to_count(row: Row) -> Result<usize> {
+    let count = row.get::<i64>(0).err_into()?;
+    usize::try_from(count).map_err(|_| Error::Parse(format!("count {count}")))
+}
+
+/// Revision info in the three columns starting at `first`
+fn revision_info(row: &Row, first: usize) -> Result<RevisionInfo> {
+    let revision = row.get::<i64>(first).err_into()?;
+    Ok(RevisionInfo {
+        revision: u32::try_from(revision)
+            .map_err(|_| Error::Parse(format!("revision {revision}")))?,
+        timestamp: row.get_string(first + 1)?,
+        author: row.get::<Option<String>>(first + 2).err_into()?,
+    })
+}
+
+pub fn to_revision_info(row: Row) -> Result<RevisionInfo> {
+    revision_info(&row, 0)
+}
+
+pub fn to_lyric_revision(row: Row) -> Result<Revision<Lyric>> {
+    Ok(Revision {
+        info: revision_info(&row, AFTER_LYRIC)?,
+        item: lyric(&row)?,
+    })
+}
+
+pub fn to_playlist_revision(row: Row) -> Result<Revision<Playlist>> {
+    let members = row
+        .get_string(2)?
+        .split(',')
+        .filter(|member| !member.is_empty())
+        .map(|member| to_uuid(member).map(LyricId::from))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Revision {
+        info: revision_info(&row, 4)?,
+        item: Playlist {
+            id: row.get_uuid(0)?.into(),
+            title: row.get_string(1)?,
+            transpositions: transpositions(&row, &members)?,
+            members,
+        },
     })
 }
 
@@ -1,19 +1,59 @@
 CREATE TABLE IF NOT EXISTS lyric (
     id VARCHAR PRIMARY KEY,
-    title VARCHAR UNIQUE NOT NULL,
+    title VARCHAR NOT NULL,
     sub_title VARCHAR,
-    parts VARCHAR
+    lyricist VARCHAR,
+    composer VARCHAR,
+    musical_key VARCHAR,
+    tempo INTEGER,
+    copyright VARCHAR,
+    ccli INTEGER,
+    language VARCHAR,
+    tags VARCHAR,
+    parts VARCHAR,
+    search_text VARCHAR
 );
 
 CREATE INDEX IF NOT EXISTS lyric_title ON lyric (title);
 
 CREATE TABLE IF NOT EXISTS playlist (
     id VARCHAR PRIMARY KEY,
-    title VARCHAR UNIQUE NOT NULL
+    title VARCHAR NOT NULL,
+    transpositions VARCHAR
 );
 
 CREATE INDEX IF NOT EXISTS playlist_title ON playlist (title);
 
+CREATE TABLE IF NOT EXISTS lyric_revision (
+    lyric_id VARCHAR NOT NULL,
+    revision INTEGER NOT NULL,
+    timestamp VARCHAR NOT NULL,
+    author VARCHAR,
+    title VARCHAR NOT NULL,
+    sub_title VARCHAR,
+    lyricist VARCHAR,
+    composer VARCHAR,
+    musical_key VARCHAR,
+    tempo INTEGER,
+    copyright VARCHAR,
+    ccli INTEGER,
+    language VARCHAR,
+    tags VARCHAR,
+    parts VARCHAR NOT NULL,
+    PRIMARY KEY (lyric_id, revision)
+);
+
+CREATE TABLE IF NOT EXISTS playlist_revision (
+    playlist_id VARCHAR NOT NULL,
+    revision INTEGER NOT NULL,
+    timestamp VARCHAR NOT NULL,
+    author VARCHAR,
+    title VARCHAR NOT NULL,
+    members VARCHAR NOT NULL,
+    transpositions VARCHAR,
+    PRIMARY KEY (playlist_id, revision)
+);
+
 CREATE TABLE IF NOT EXISTS member (
     playlist_id VARCHAR NOT NULL REFERENCES playlist ON DELETE CASCADE,
     lyric_id VARCHAR NOT NULL REFERENCES lyric ON DELETE CASCADE,
@@ -1,28 +1,340 @@
 use futures_util::{TryFutureExt, TryStreamExt};
-use lipl_core::{Error, Lyric, Playlist, Repo, Result, Summary, Uuid, parts::to_text};
+use lipl_core::{
+    Error, Etag, Lyric, LyricId, Metadata, Page, PageRequest, Playlist, PlaylistId, Repo, Result,
+    Revision, RevisionInfo, Summary, Uuid,
+    change::{Change, ChangeStream},
+    check_etag,
+    metadata::tags_to_text,
+    parts::to_text,
+    revision::to_record,
+    search::{Hit, document_text, rank, snippet, tokens},
+    transpose::transpositions_to_text,
+};
 use tokio_stream::wrappers::ReceiverStream;
-use turso::Value;
+use turso::{Row, Value};
 
-use crate::{ErrInto, TursoDatabase};
+use crate::{ErrInto, TursoDatabase, title_error};
 
 use super::convert;
 
 fn error_on_count(count: u64, uuid: Uuid) -> Result<()> {
     if count < 1 {
-        Err(Error::NoKey(uuid.to_string()))
+        Err(Error::NotFound(uuid))
     } else {
         Ok(())
     }
 }
 
-fn pg_error_to_lipl_core(uuid: Uuid) -> impl Fn(Error) -> lipl_core::Error {
-    move |pg_error| match pg_error {
-        Error::NoResults => Error::NoKey(uuid.to_string()),
-        _ => pg_error,
-    }
+fn metadata_values(metadata: &Metadata) -> Vec<Value> {
+    vec![
+        Value::from(metadata.subtitle.clone()),
+        Value::from(metadata.lyricist.clone()),
+        Value::from(metadata.composer.clone()),
+        Value::from(metadata.key.clone()),
+        Value::from(metadata.tempo),
+        Value::from(metadata.copyright.clone()),
+        Value::from(metadata.ccli),
+        Value::from(metadata.language.clone()),
+        Value::from((!metadata.tags.is_empty()).then(|| tags_to_text(&metadata.tags))),
+    ]
+}
+
+fn lyric_columns(lyric: &Lyric) -> Vec<Value> {
+    [
+        Value::from(lyric.title.clone()),
+        Value::from(to_text(&lyric.parts)),
+    ]
+    .into_iter()
+    .chain(metadata_values(&lyric.metadata))
+    .collect()
+}
+
+fn transpositions_value(playlist: &Playlist) -> Value {
+    Value::from(
+        (!playlist.transpositions.is_empty())
+            .then(|| transpositions_to_text(&playlist.transpositions)),
+    )
+}
+
+fn playlist_columns(playlist: &Playlist) -> Vec<Value> {
+    vec![
+        Value::from(playlist.title.clone()),
+        Value::from(
+            playlist
+                .members
+                .iter()
+                .map(LyricId::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        transpositions_value(playlist),
+    ]
+}
+
+fn page_params(page: &PageRequest) -> Vec<Value> {
+    vec![
+        page.cursor
+            .as_ref()
+            .map_or(Value::Null, |cursor| Value::from(cursor.title.as_str())),
+        page.cursor.as_ref().map_or(Value::Null, |cursor| {
+            Value::from(cursor.id.to_string().as_str())
+        }),
+        Value::from(i64::try_from(page.fetch_limit()).unwrap_or(i64::MAX)),
+    ]
 }
 
 impl TursoDatabase {
+    async fn page<T>(
+        &self,
+        sql: &'static str,
+        convert: fn(turso::Row) -> Result<T>,
+        page: PageRequest,
+    ) -> Result<Page<T>>
+    where
+        T: lipl_core::HasSummary + Send + Sync + 'static,
+    {
+        self.query(sql, convert, page_params(&page))
+            .and_then(TryStreamExt::try_collect)
+            .map_ok(|items| Page::from_fetched(items, &page))
+            .await
+    }
+
+    /// Runs `write` in a transaction. Writes are serialized because clones share a connection.
+    async fn in_transaction<U, F, Fut>(&self, write: F) -> Result<U>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<U>>,
+    {
+        let _writer = self.writer.lock().await;
+        let mut connection = self.inner.clone();
+        let transaction = connection.transaction().await.err_into()?;
+        match write().await {
+            Ok(result) => {
+                transaction.commit().await.err_into()?;
+                Ok(result)
+            }
+            Err(error) => {
+                transaction.rollback().await.err_into()?;
+                Err(error)
+            }
+        }
+    }
+
+    /// Runs `write` in a transaction if the entity tag of the item selected by `read` matches `if_match`.
+    async fn write_if_match<T, U, F, Fut>(
+        &self,
+        id: impl Into<Uuid>,
+        if_match: &str,
+        read: (&'static str, fn(Row) -> Result<T>),
+        write: F,
+    ) -> Result<U>
+    where
+        T: Etag,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<U>>,
+    {
+        let id = id.into();
+        self.in_transaction(|| async move {
+            let current = self
+                .query_opt(read.0, read.1, &[id.to_string().as_str()])
+                .await?;
+            check_etag(current.as_ref(), if_match, id)?;
+            write().await
+        })
+        .await
+    }
+
+    /// Records `item`, that replaces `previous`, as a revision of the item with id `id`.
+    /// `sql` holds the statements that count and insert revisions.
+    async fn record<T>(
+        &self,
+        sql: (&'static str, &'static str),
+        id: impl Into<Uuid>,
+        previous: Option<T>,
+        item: T,
+        columns: fn(&T) -> Vec<Value>,
+    ) -> Result<()> {
+        let id = id.into();
+        let count = self
+            .query_one(sql.0, convert::to_count, &[id.to_string().as_str()])
+            .await?;
+        for revision in to_record(count, previous, item) {
+            let values = vec![
+                Value::from(id.to_string()),
+                Value::from(revision.info.revision),
+                Value::from(revision.info.timestamp),
+                Value::from(revision.info.author),
+            ]
+            .into_iter()
+            .chain(columns(&revision.item))
+            .collect::<Vec<_>>();
+            self.execute(sql.1, values).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_lyric(&self, lyric: &Lyric) -> Result<Lyric> {
+        let previous = self
+            .query_opt(
+                lyric::ITEM,
+                convert::to_lyric,
+                &[lyric.id.to_string().as_str()],
+            )
+            .await?;
+        let lyric = self.upsert_lyric_row(lyric).await?;
+        self.record(
+            (lyric::REVISION_COUNT, lyric::REVISION_INSERT),
+            lyric.id,
+            previous,
+            lyric.clone(),
+            lyric_columns,
+        )
+        .await?;
+        Ok(lyric)
+    }
+
+    async fn upsert_lyric_row(&self, lyric: &Lyric) -> Result<Lyric> {
+        self.query_one(
+            lyric::UPSERT,
+            convert::to_lyric,
+            [
+                Value::from(lyric.id.to_string()),
+                Value::from(lyric.title.as_str()),
+                Value::from(to_text(&lyric.parts)),
+                Value::from(document_text(&lyric.title, &lyric.parts)),
+            ]
+            .into_iter()
+            .chain(metadata_values(&lyric.metadata))
+            .collect::<Vec<_>>(),
+        )
+        .await
+        .map_err(title_error(&lyric.title))
+    }
+
+    async fn check_members(&self, playlist: &Playlist) -> Result<()> {
+        for member in &playlist.members {
+            let count = self
+                .query_one(
+                    lyric::COUNT,
+                    convert::to_count,
+                    &[member.to_string().as_str()],
+                )
+                .await?;
+            if count == 0 {
+                return Err(Error::PlaylistInvalidMember(
+                    playlist.id.to_string(),
+                    member.to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_playlist(&self, playlist: &Playlist) -> Result<()> {
+        self.check_members(playlist).await?;
+        let previous = self
+            .query_opt(
+                playlist::ITEM,
+                convert::to_playlist,
+                &[playlist.id.to_string().as_str()],
+            )
+            .await?;
+        self.execute(
+            playlist::UPSERT,
+            vec![
+                Value::from(playlist.id.to_string()),
+                Value::from(playlist.title.as_str()),
+                transpositions_value(playlist),
+            ],
+        )
+        .await
+        .map_err(title_error(&playlist.title))?;
+        self.execute(member::DELETE, &[playlist.id.to_string().as_str()])
+            .await?;
+        for (index, lyric_id) in playlist.members.iter().enumerate() {
+            self.execute(
+                member::INSERT,
+                vec![
+                    Value::from(playlist.id.to_string().as_str()),
+                    Value::from(lyric_id.to_string().as_str()),
+                    Value::from(index as i64),
+                ],
+            )
+            .await?;
+        }
+        self.record(
+            (playlist::REVISION_COUNT, playlist::REVISION_INSERT),
+            playlist.id,
+            previous,
+            playlist.clone(),
+            playlist_columns,
+        )
+        .await
+    }
+
+    async fn revision<T>(
+        &self,
+        sql: &'static str,
+        convert: fn(Row) -> Result<Revision<T>>,
+        uuid: impl Into<Uuid>,
+        revision: u32,
+    ) -> Result<Revision<T>> {
+        let uuid = uuid.into();
+        self.query_opt(
+            sql,
+            convert,
+            vec![Value::from(uuid.to_string()), Value::from(revision)],
+        )
+        .await?
+        .ok_or(Error::RevisionNotFound(uuid, revision))
+    }
+
+    /// Removes the member rows selected by `member_sql` and the item. Foreign keys are not enforced.
+    async fn remove(
+        &self,
+        (member_sql, sql): (&'static str, &'static str),
+        uuid: impl Into<Uuid>,
+    ) -> Result<()> {
+        let uuid = uuid.into();
+        self.execute(member_sql, &[uuid.to_string().as_str()])
+            .await?;
+        let count = self.execute(sql, &[uuid.to_string().as_str()]).await?;
+        error_on_count(count, uuid)
+    }
+
+    /// Removes the lyric and returns the playlists it is removed from
+    async fn remove_lyric(&self, uuid: LyricId) -> Result<Vec<Playlist>> {
+        let summaries = self
+            .query(
+                playlist::WITH_MEMBER,
+                convert::to_summary,
+                &[uuid.to_string().as_str()],
+            )
+            .and_then(TryStreamExt::try_collect::<Vec<_>>)
+            .await?;
+        self.remove(lyric::DELETE, uuid).await?;
+        let mut playlists = vec![];
+        for summary in summaries {
+            playlists.push(
+                self.item(playlist::ITEM, convert::to_playlist, summary.id)
+                    .await?,
+            );
+        }
+        Ok(playlists)
+    }
+
+    async fn item<T>(
+        &self,
+        sql: &'static str,
+        convert: fn(Row) -> Result<T>,
+        uuid: impl Into<Uuid>,
+    ) -> Result<T> {
+        let uuid = uuid.into();
+        self.query_opt(sql, convert, &[uuid.to_string().as_str()])
+            .await?
+            .ok_or(Error::NotFound(uuid))
+    }
+
     pub async fn lyrics_stream(&self) -> Result<ReceiverStream<Result<Lyric>>> {
         self.query(lyric::LIST_FULL, convert::to_lyric, Vec::<&str>::new())
             .await
@@ -61,30 +373,111 @@ impl Repo for TursoDatabase {
             .await
     }
 
-    async fn get_lyric(&self, uuid: Uuid) -> Result<Lyric> {
-        self.query_one(lyric::ITEM, convert::to_lyric, &[uuid.to_string().as_str()])
-            .map_err(pg_error_to_lipl_core(uuid))
+    async fn get_lyrics_page(&self, page: PageRequest) -> Result<Page<Lyric>> {
+        self.page(lyric::LIST_FULL_PAGE, convert::to_lyric, page)
             .await
     }
 
+    async fn get_lyric_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.page(lyric::LIST_PAGE, convert::to_summary, page).await
+    }
+
+    async fn get_lyric(&self, uuid: LyricId) -> Result<Lyric> {
+        self.item(lyric::ITEM, convert::to_lyric, uuid).await
+    }
+
     async fn upsert_lyric(&self, lyric: Lyric) -> Result<Lyric> {
-        self.query_one(
-            lyric::UPSERT,
-            convert::to_lyric,
-            &[
-                lyric.id.to_string().as_str(),
-                lyric.title.as_str(),
-                to_text(&lyric.parts).as_str(),
-            ],
+        self.in_transaction(|| self.write_lyric(&lyric))
+            .inspect_ok(|lyric| self.changes.send(Change::lyric_upserted(lyric)))
+            .await
+    }
+
+    async fn delete_lyric(&self, uuid: LyricId) -> Result<()> {
+        self.in_transaction(|| self.remove_lyric(uuid))
+            .map_ok(|playlists| {
+                self.changes
+                    .send_all(Change::lyric_deleted(uuid, &playlists));
+            })
+            .await
+    }
+
+    async fn upsert_lyric_if_match(&self, lyric: Lyric, if_match: String) -> Result<Lyric> {
+        self.write_if_match(
+            lyric.id,
+            &if_match,
+            (lyric::ITEM, convert::to_lyric),
+            || self.write_lyric(&lyric),
         )
+        .inspect_ok(|lyric| self.changes.send(Change::lyric_upserted(lyric)))
         .await
     }
 
-    async fn delete_lyric(&self, uuid: Uuid) -> Result<()> {
-        let count = self
-            .execute(lyric::DELETE, &[uuid.to_string().as_str()])
-            .await?;
-        error_on_count(count, uuid)
+    async fn delete_lyric_if_match(&self, uuid: LyricId, if_match: String) -> Result<()> {
+        self.write_if_match(uuid, &if_match, (lyric::ITEM, convert::to_lyric), || {
+            self.remove_lyric(uuid)
+        })
+        .map_ok(|playlists| {
+            self.changes
+                .send_all(Change::lyric_deleted(uuid, &playlists));
+        })
+        .await
+    }
+
+    async fn get_lyric_revisions(&self, uuid: LyricId) -> Result<Vec<RevisionInfo>> {
+        self.query(
+            lyric::REVISIONS,
+            convert::to_revision_info,
+            &[uuid.to_string().as_str()],
+        )
+        .and_then(TryStreamExt::try_collect)
+        .await
+    }
+
+    async fn get_lyric_revision(&self, uuid: LyricId, revision: u32) -> Result<Revision<Lyric>> {
+        self.revision(
+            lyric::REVISION_ITEM,
+            convert::to_lyric_revision,
+            uuid,
+            revision,
+        )
+        .await
+    }
+
+    async fn restore_lyric_revision(&self, uuid: LyricId, revision: u32) -> Result<Lyric> {
+        let revision = self.get_lyric_revision(uuid, revision).await?;
+        self.upsert_lyric(revision.item).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Hit>> {
+        let tokens = tokens(query);
+        if tokens.is_empty() {
+            return Ok(vec![]);
+        }
+        let required = tokens
+            .iter()
+            .map(|token| format!("+{token}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.query(
+            lyric::SEARCH,
+            convert::to_scored_lyric,
+            &[required.as_str()],
+        )
+        .and_then(TryStreamExt::try_collect::<Vec<_>>)
+        .map_ok(|lyrics| {
+            rank(
+                lyrics
+                    .into_iter()
+                    .map(|(lyric, score)| Hit {
+                        snippet: snippet(&lyric.parts, query),
+                        id: lyric.id,
+                        title: lyric.title,
+                        score: score as f32,
+                    })
+                    .collect(),
+            )
+        })
+        .await
     }
 
     async fn get_playlist_summaries(&self) -> Result<Vec<Summary>> {
@@ -99,75 +492,171 @@ impl Repo for TursoDatabase {
             .await
     }
 
-    async fn get_playlist(&self, uuid: Uuid) -> Result<Playlist> {
-        let playlist = self
-            .query_one(
-                playlist::ITEM,
-                convert::to_playlist,
-                &[uuid.to_string().as_str()],
-            )
-            .map_err(pg_error_to_lipl_core(uuid))
-            .await?;
-        Ok(playlist)
+    async fn get_playlists_page(&self, page: PageRequest) -> Result<Page<Playlist>> {
+        self.page(playlist::LIST_FULL_PAGE, convert::to_playlist, page)
+            .await
     }
 
-    async fn delete_playlist(&self, uuid: Uuid) -> Result<()> {
-        let count = self
-            .execute(playlist::DELETE, &[uuid.to_string().as_str()])
-            .await?;
-        error_on_count(count, uuid)
+    async fn get_playlist_summaries_page(&self, page: PageRequest) -> Result<Page<Summary>> {
+        self.page(playlist::LIST_PAGE, convert::to_summary, page)
+            .await
     }
 
-    async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
-        let mut connection = self.inner.clone();
-        let transaction = connection.transaction().await.err_into()?;
-        let _ = transaction
-            .execute(
-                playlist::UPSERT,
-                &[playlist.id.to_string().as_str(), playlist.title.as_str()],
-            )
-            .await
-            .err_into()?;
-        let _ = transaction
-            .execute(member::DELETE, &[playlist.id.to_string().as_str()])
+    async fn get_playlist(&self, uuid: PlaylistId) -> Result<Playlist> {
+        self.item(playlist::ITEM, convert::to_playlist, uuid).await
+    }
+
+    async fn delete_playlist(&self, uuid: PlaylistId) -> Result<()> {
+        self.in_transaction(|| self.remove(playlist::DELETE, uuid))
+            .inspect_ok(|()| self.changes.send(Change::PlaylistDeleted { id: uuid }))
             .await
-            .err_into()?;
-        for (index, lyric_id) in playlist.members.iter().enumerate() {
-            let _ = transaction
-                .execute(
-                    member::INSERT,
-                    &[
-                        Value::from(playlist.id.to_string().as_str()),
-                        Value::from(lyric_id.to_string().as_str()),
-                        Value::from(index as i64),
-                    ],
-                )
-                .await
-                .err_into()?;
-        }
-        transaction.commit().await.err_into()?;
+    }
+
+    async fn upsert_playlist(&self, playlist: Playlist) -> Result<Playlist> {
+        self.in_transaction(|| self.write_playlist(&playlist))
+            .await?;
+        self.changes.send(Change::playlist_upserted(&playlist));
+        Ok(playlist)
+    }
+
+    async fn upsert_playlist_if_match(
+        &self,
+        playlist: Playlist,
+        if_match: String,
+    ) -> Result<Playlist> {
+        self.write_if_match(
+            playlist.id,
+            &if_match,
+            (playlist::ITEM, convert::to_playlist),
+            || self.write_playlist(&playlist),
+        )
+        .await?;
+        self.changes.send(Change::playlist_upserted(&playlist));
         Ok(playlist)
     }
 
+    async fn delete_playlist_if_match(&self, uuid: PlaylistId, if_match: String) -> Result<()> {
+        self.write_if_match(
+            uuid,
+            &if_match,
+            (playlist::ITEM, convert::to_playlist),
+            || self.remove(playlist::DELETE, uuid),
+        )
+        .inspect_ok(|()| self.changes.send(Change::PlaylistDeleted { id: uuid }))
+        .await
+    }
+
+    async fn get_playlist_revisions(&self, uuid: PlaylistId) -> Result<Vec<RevisionInfo>> {
+        self.query(
+            playlist::REVISIONS,
+            convert::to_revision_info,
+            &[uuid.to_string().as_str()],
+        )
+        .and_then(TryStreamExt::try_collect)
+        .await
+    }
+
+    async fn get_playlist_revision(
+        &self,
+        uuid: PlaylistId,
+        revision: u32,
+    ) -> Result<Revision<Playlist>> {
+        self.revision(
+            playlist::REVISION_ITEM,
+            convert::to_playlist_revision,
+            uuid,
+            revision,
+        )
+        .await
+    }
+
+    async fn restore_playlist_revision(&self, uuid: PlaylistId, revision: u32) -> Result<Playlist> {
+        let revision = self.get_playlist_revision(uuid, revision).await?;
+        self.upsert_playlist(revision.item).await
+    }
+
+    async fn subscribe(&self) -> Result<ChangeStream> {
+        Ok(self.changes.subscribe())
+    }
+
     async fn stop(&self) -> Result<()> {
         Ok(())
     }
 }
 
 mod lyric {
+    /// Columns read by `convert::to_lyric`, besides id, title and parts
+    macro_rules! metadata {
+        () => {
+            "sub_title, lyricist, composer, musical_key, tempo, copyright, ccli, language, tags"
+        };
+    }
+
     pub const LIST: &str = "SELECT id, title FROM lyric ORDER BY title;";
-    pub const LIST_FULL: &str = "SELECT id, title, parts FROM lyric ORDER BY title;";
-    pub const ITEM: &str = "SELECT * FROM lyric WHERE id = $1;";
-    pub const DELETE: &str = "DELETE FROM lyric WHERE id = $1;";
-    pub const UPSERT: &str = "INSERT INTO lyric (id, title, parts) VALUES ($1, $2, $3) ON CONFLICT (id) DO UPDATE SET title = $2, parts = $3 RETURNING id, title, parts;";
+    pub const LIST_FULL: &str = concat!(
+        "SELECT id, title, parts, ",
+        metadata!(),
+        " FROM lyric ORDER BY title;"
+    );
+    pub const LIST_PAGE: &str = "SELECT id, title FROM lyric WHERE $1 IS NULL OR title > $1 OR (title = $1 AND id > $2) ORDER BY title, id LIMIT $3;";
+    pub const LIST_FULL_PAGE: &str = concat!(
+        "SELECT id, title, parts, ",
+        metadata!(),
+        " FROM lyric WHERE $1 IS NULL OR title > $1 OR (title = $1 AND id > $2) ORDER BY title, id LIMIT $3;"
+    );
+    pub const ITEM: &str = concat!(
+        "SELECT id, title, parts, ",
+        metadata!(),
+        " FROM lyric WHERE id = $1;"
+    );
+    pub const COUNT: &str = "SELECT COUNT(*) FROM lyric WHERE id = $1;";
+    pub const DELETE: (&str, &str) = (
+        "DELETE FROM member WHERE lyric_id = $1;",
+        "DELETE FROM lyric WHERE id = $1;",
+    );
+    pub const SEARCH: &str = concat!(
+        "SELECT id, title, parts, ",
+        metadata!(),
+        ", fts_score(search_text, $1) AS score FROM lyric WHERE fts_match(search_text, $1);"
+    );
+    pub const UPSERT: &str = concat!(
+        "INSERT INTO lyric (id, title, parts, search_text, ",
+        metadata!(),
+        ") VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) ON CONFLICT (id) DO UPDATE SET title = $2, parts = $3, search_text = $4, sub_title = $5, lyricist = $6, composer = $7, musical_key = $8, tempo = $9, copyright = $10, ccli = $11, language = $12, tags = $13 RETURNING id, title, parts, ",
+        metadata!(),
+        ";"
+    );
+    pub const REVISION_COUNT: &str = "SELECT COUNT(*) FROM lyric_revision WHERE lyric_id = $1;";
+    pub const REVISION_INSERT: &str = concat!(
+        "INSERT INTO lyric_revision (lyric_id, revision, timestamp, author, title, parts, ",
+        metadata!(),
+        ") VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15);"
+    );
+    pub const REVISIONS: &str = "SELECT revision, timestamp, author FROM lyric_revision WHERE lyric_id = $1 ORDER BY revision;";
+    pub const REVISION_ITEM: &str = concat!(
+        "SELECT lyric_id, title, parts, ",
+        metadata!(),
+        ", revision, timestamp, author FROM lyric_revision WHERE lyric_id = $1 AND revision = $2;"
+    );
 }
 
 mod playlist {
     pub const LIST: &str = "SELECT id, title FROM playlist ORDER BY title;";
-    pub const LIST_FULL: &str = "SELECT playlist.id AS id, title, GROUP_CONCAT(lyric_id) members FROM playlist LEFT JOIN (SELECT * FROM member ORDER BY ordering) ON playlist.id = playlist_id GROUP BY playlist.id ORDER BY playlist.title;";
-    pub const ITEM: &str = "SELECT playlist.id AS id, title, GROUP_CONCAT(lyric_id) members FROM playlist LEFT JOIN (SELECT * FROM member ORDER BY ordering) ON playlist.id = playlist_id GROUP BY playlist.id HAVING playlist.id = $1;";
-    pub const DELETE: &str = "DELETE FROM playlist WHERE id = $1;";
-    pub const UPSERT: &str = "INSERT INTO playlist (id, title) VALUES ($1, $2) ON CONFLICT (id) DO UPDATE SET title = $2;";
+    pub const LIST_FULL: &str = "SELECT playlist.id AS id, title, (SELECT GROUP_CONCAT(lyric_id) FROM (SELECT lyric_id FROM member WHERE playlist_id = playlist.id ORDER BY ordering)) members, transpositions FROM playlist ORDER BY playlist.title;";
+    pub const LIST_PAGE: &str = "SELECT id, title FROM playlist WHERE $1 IS NULL OR title > $1 OR (title = $1 AND id > $2) ORDER BY title, id LIMIT $3;";
+    pub const LIST_FULL_PAGE: &str = "SELECT playlist.id AS id, title, (SELECT GROUP_CONCAT(lyric_id) FROM (SELECT lyric_id FROM member WHERE playlist_id = playlist.id ORDER BY ordering)) members, transpositions FROM playlist WHERE $1 IS NULL OR title > $1 OR (title = $1 AND playlist.id > $2) ORDER BY playlist.title, playlist.id LIMIT $3;";
+    pub const ITEM: &str = "SELECT playlist.id AS id, title, (SELECT GROUP_CONCAT(lyric_id) FROM (SELECT lyric_id FROM member WHERE playlist_id = playlist.id ORDER BY ordering)) members, transpositions FROM playlist WHERE playlist.id = $1;";
+    pub const WITH_MEMBER: &str = "SELECT id, title FROM playlist WHERE id IN (SELECT playlist_id FROM member WHERE lyric_id = $1);";
+    pub const DELETE: (&str, &str) = (
+        "DELETE FROM member WHERE playlist_id = $1;",
+        "DELETE FROM playlist WHERE id = $1;",
+    );
+    pub const UPSERT: &str = "INSERT INTO playlist (id, title, transpositions) VALUES ($1, $2, $3) ON CONFLICT (id) DO UPDATE SET title = $2, transpositions = $3;";
+    pub const REVISION_COUNT: &str =
+        "SELECT COUNT(*) FROM playlist_revision WHERE playlist_id = $1;";
+    pub const REVISION_INSERT: &str = "INSERT INTO playlist_revision (playlist_id, revision, timestamp, author, title, members, transpositions) VALUES ($1, $2, $3, $4, $5, $6, $7);";
+    pub const REVISIONS: &str = "SELECT revision, timestamp, author FROM playlist_revision WHERE playlist_id = $1 ORDER BY revision;";
+    pub const REVISION_ITEM: &str = "SELECT playlist_id, title, members, transpositions, revision, timestamp, author FROM playlist_revision WHERE playlist_id = $1 AND revision = $2;";
 }
 
 mod member {
@@ -1,5 +1,8 @@
-use futures_util::TryFutureExt;
-use lipl_core::{RepoConfig, Result};
+use std::sync::Arc;
+
+use futures_util::{TryFutureExt, TryStreamExt};
+use lipl_core::{RepoConfig, Result, change::Changes, validate::TitleRule};
+use tokio::sync::Mutex;
 use tokio_stream::wrappers::ReceiverStream;
 use turso::{Builder, IntoParams, Row};
 
@@ -8,6 +11,64 @@ mod db;
 mod row_stream;
 
 pub const CREATE_DB: &str = include_str!("create_db.sql");
+const CREATE_SEARCH_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS lyric_fts ON lyric USING fts (search_text);";
+const ADD_SEARCH_TEXT: &str = "ALTER TABLE lyric ADD COLUMN search_text VARCHAR;";
+const SELECT_WITHOUT_SEARCH_TEXT: &str =
+    "SELECT id, title, parts FROM lyric WHERE search_text IS NULL;";
+const UPDATE_SEARCH_TEXT: &str = "UPDATE lyric SET search_text = $1 WHERE id = $2;";
+/// Names of the unique indexes on the titles, by title rule
+const TITLE_INDEXES: [(TitleRule, &str, &str); 2] = [
+    (TitleRule::Unique, "title_unique", "trim(title)"),
+    (
+        TitleRule::UniqueIgnoreCase,
+        "title_unique_ignore_case",
+        "lower(trim(title))",
+    ),
+];
+
+/// Creates the unique index on the titles that `titles` requires and drops the others,
+/// so concurrent writes cannot store the same title
+fn title_indexes(titles: TitleRule) -> String {
+    let mut sql = String::new();
+    for table in ["lyric", "playlist"] {
+        for (rule, name, expression) in TITLE_INDEXES {
+            if rule == titles {
+                sql.push_str(&format!(
+                    "CREATE UNIQUE INDEX IF NOT EXISTS {table}_{name} ON {table} ({expression});\n"
+                ));
+            } else {
+                sql.push_str(&format!("DROP INDEX IF EXISTS {table}_{name};\n"));
+            }
+        }
+    }
+    sql
+}
+
+/// Column names with their types
+type Columns = &'static [(&'static str, &'static str)];
+
+/// Columns that databases created by older versions lack, by table
+const ADDED_COLUMNS: [(&[&str], Columns); 2] = [
+    (&["lyric", "lyric_revision"], &METADATA_COLUMNS),
+    (
+        &["playlist", "playlist_revision"],
+        &[("transpositions", "VARCHAR")],
+    ),
+];
+
+/// Lyric metadata columns
+const METADATA_COLUMNS: [(&str, &str); 9] = [
+    ("sub_title", "VARCHAR"),
+    ("lyricist", "VARCHAR"),
+    ("composer", "VARCHAR"),
+    ("musical_key", "VARCHAR"),
+    ("tempo", "INTEGER"),
+    ("copyright", "VARCHAR"),
+    ("ccli", "INTEGER"),
+    ("language", "VARCHAR"),
+    ("tags", "VARCHAR"),
+];
 
 trait ErrInto<T> {
     fn err_into(self) -> Result<T>;
@@ -22,6 +83,23 @@ where
     }
 }
 
+/// A write that stores a title that is already used violates the unique index on the titles,
+/// or the unique constraint of databases created by older versions.
+/// It fails with a `not-unique` error for the title, instead of a database error.
+fn title_error(title: &str) -> impl FnOnce(lipl_core::Error) -> lipl_core::Error + '_ {
+    move |error| match &error {
+        lipl_core::Error::Turso(source) => match source.downcast_ref::<turso::Error>() {
+            Some(turso::Error::Constraint(message))
+                if message.contains("UNIQUE") && message.contains("title") =>
+            {
+                lipl_core::Error::Validation(vec![lipl_core::validate::not_unique(title)])
+            }
+            _ => error,
+        },
+        _ => error,
+    }
+}
+
 trait OkInto<E, T> {
     fn ok_into(self) -> std::result::Result<T, E>;
 }
@@ -38,11 +116,21 @@ where
 #[derive(Clone, Debug)]
 pub struct TursoDatabase {
     inner: turso::Connection,
+    /// Clones share one connection, so writes are serialized to keep transactions apart
+    writer: Arc<Mutex<()>>,
+    changes: Changes,
+    /// The title rule that [`TursoDatabase::schema`] creates the unique index on the titles for
+    titles: TitleRule,
 }
 
 impl From<turso::Connection> for TursoDatabase {
     fn from(inner: turso::Connection) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            writer: Arc::default(),
+            changes: Changes::default(),
+            titles: TitleRule::default(),
+        }
     }
 }
 
@@ -90,8 +178,69 @@ impl TursoDatabase {
             .and_then(convert)
     }
 
+    async fn query_opt<T>(
+        &self,
+        sql: &'static str,
+        convert: fn(Row) -> Result<T>,
+        params: impl IntoParams,
+    ) -> Result<Option<T>> {
+        let mut rows = self
+            .inner
+            .prepare(sql)
+            .and_then(|mut statement| async move { statement.query(params).await })
+            .await
+            .err_into()?;
+        rows.next().await.err_into()?.map(convert).transpose()
+    }
+
+    /// Creates or upgrades the schema. Fails if the stored titles break the title rule.
     pub async fn schema(&self) -> Result<()> {
-        self.batch_execute(include_str!("create_db.sql")).await
+        self.batch_execute(CREATE_DB).await?;
+        if self
+            .inner
+            .prepare("SELECT search_text FROM lyric LIMIT 0;")
+            .await
+            .is_err()
+        {
+            self.batch_execute(ADD_SEARCH_TEXT).await?;
+        }
+        self.fill_search_text().await?;
+        for (tables, columns) in ADDED_COLUMNS {
+            for table in tables {
+                for (column, column_type) in columns {
+                    if self
+                        .inner
+                        .prepare(&format!("SELECT {column} FROM {table} LIMIT 0;"))
+                        .await
+                        .is_err()
+                    {
+                        self.batch_execute(&format!(
+                            "ALTER TABLE {table} ADD COLUMN {column} {column_type};"
+                        ))
+                        .await?;
+                    }
+                }
+            }
+        }
+        self.batch_execute(&title_indexes(self.titles)).await?;
+        self.batch_execute(CREATE_SEARCH_INDEX).await
+    }
+
+    /// Fills the search text of lyrics stored before it was added
+    async fn fill_search_text(&self) -> Result<()> {
+        let lyrics = self
+            .query(SELECT_WITHOUT_SEARCH_TEXT, convert::to_search_text, ())
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        for (id, search_text) in lyrics {
+            self.execute(
+                UPDATE_SEARCH_TEXT,
+                &[search_text.as_str(), id.to_string().as_str()],
+            )
+            .await?;
+        }
+        Ok(())
     }
 
     pub async fn clear(&self) -> Result<()> {
@@ -101,11 +250,23 @@ impl TursoDatabase {
 
 pub struct TursoConfig {
     path: String,
+    titles: TitleRule,
 }
 
 impl From<String> for TursoConfig {
     fn from(path: String) -> Self {
-        Self { path }
+        Self {
+            path,
+            titles: TitleRule::default(),
+        }
+    }
+}
+
+impl TursoConfig {
+    /// Sets the title rule that the unique index on the titles enforces, `Unique` by default
+    #[must_use]
+    pub fn with_titles(self, titles: TitleRule) -> Self {
+        Self { titles, ..self }
     }
 }
 
@@ -116,12 +277,111 @@ impl RepoConfig for TursoConfig {
             db.connect()
         }
 
+        let titles = self.titles;
         Builder::new_local(&self.path)
             .experimental_materialized_views(true)
+            .experimental_index_method(true)
             .build()
             .await
             .and_then(connect)
             .ok_into()
             .err_into()
+            .map(|database: TursoDatabase| TursoDatabase { titles, ..database })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lipl_core::{
+        Error, Lyric, LyricPost, Metadata, Repo, RepoConfig, Uuid, validate::TitleRule,
+    };
+
+    use super::{TursoConfig, TursoDatabase};
+
+    fn lyric(title: &str) -> Lyric {
+        Lyric::from((
+            None,
+            LyricPost {
+                title: title.to_owned(),
+                metadata: Metadata::default(),
+                parts: vec![],
+            },
+        ))
+    }
+
+    async fn database(titles: TitleRule) -> TursoDatabase {
+        let repo = TursoConfig::from(":memory:".to_owned())
+            .with_titles(titles)
+            .to_repo()
+            .await
+            .unwrap();
+        repo.schema().await.unwrap();
+        repo
+    }
+
+    fn is_not_unique(result: lipl_core::Result<Lyric>) -> bool {
+        matches!(result, Err(Error::Validation(errors)) if errors[0].code == "not-unique")
+    }
+
+    #[tokio::test]
+    async fn search_text_of_older_lyrics_is_filled() {
+        let repo = TursoConfig::from(":memory:".to_owned())
+            .to_repo()
+            .await
+            .unwrap();
+        repo.schema().await.unwrap();
+        repo.inner
+            .execute(
+                "INSERT INTO lyric (id, title, parts) VALUES ($1, $2, $3);",
+                [
+                    Uuid::default().to_string().as_str(),
+                    "Sinterklaas kapoentje",
+                    "Gooi wat in mijn schoentje",
+                ],
+            )
+            .await
+            .unwrap();
+
+        repo.schema().await.unwrap();
+
+        let hits = repo.search("schoentje").await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "Sinterklaas kapoentje");
+    }
+
+    #[tokio::test]
+    async fn duplicate_title_of_older_database_is_not_unique() {
+        let repo = TursoConfig::from(":memory:".to_owned())
+            .to_repo()
+            .await
+            .unwrap();
+        repo.inner
+            .execute(
+                "CREATE TABLE lyric (id VARCHAR PRIMARY KEY, title VARCHAR UNIQUE NOT NULL, parts VARCHAR);",
+                (),
+            )
+            .await
+            .unwrap();
+        repo.schema().await.unwrap();
+        repo.upsert_lyric(lyric("Klokjes")).await.unwrap();
+
+        assert!(is_not_unique(repo.upsert_lyric(lyric("Klokjes")).await));
+    }
+
+    #[tokio::test]
+    async fn titles_are_unique_by_rule() {
+        let repo = database(TitleRule::Unique).await;
+        let stored = repo.upsert_lyric(lyric("Klokjes")).await.unwrap();
+        assert!(is_not_unique(repo.upsert_lyric(lyric(" Klokjes ")).await));
+        repo.upsert_lyric(lyric("klokjes")).await.unwrap();
+        repo.upsert_lyric(stored).await.unwrap();
+
+        let repo = database(TitleRule::UniqueIgnoreCase).await;
+        repo.upsert_lyric(lyric("Klokjes")).await.unwrap();
+        assert!(is_not_unique(repo.upsert_lyric(lyric("klokjes")).await));
+
+        let repo = database(TitleRule::Any).await;
+        repo.upsert_lyric(lyric("Klokjes")).await.unwrap();
+        repo.upsert_lyric(lyric("Klokjes")).await.unwrap();
     }
 }
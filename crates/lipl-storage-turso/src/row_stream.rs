@@ -76,7 +76,7 @@ mod tests {
 
     async fn list_lyrics(con: &turso::Connection) -> Rows {
         con.query(
-            "SELECT id, title, parts FROM lyric ORDER BY title",
+            "SELECT id, title, parts, sub_title, lyricist, composer, musical_key, tempo, copyright, ccli, language, tags FROM lyric ORDER BY title",
             params!(),
         )
         .await